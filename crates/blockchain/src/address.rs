@@ -0,0 +1,194 @@
+use crate::error::ParseAddressError;
+
+/// Length of the decoded address: the network byte, the RIPEMD-160 hash of
+/// the public key and the checksum.
+const DECODED_LEN: usize = 25;
+const CHECKSUM_LEN: usize = 4;
+const NETWORK_BYTE: u8 = 0;
+
+/// The address of a user, which is the Base58-encoded hash of his public key
+/// with a checksum, or the address of the storage.
+///
+/// It can only be created from a valid string, see [`parse`](Address::parse).
+#[derive(
+	Clone,
+	Debug,
+	Eq,
+	Hash,
+	Ord,
+	PartialEq,
+	PartialOrd,
+	serde::Deserialize,
+	serde::Serialize,
+)]
+#[serde(into = "String", try_from = "String")]
+pub struct Address(std::borrow::Cow<'static, str>);
+
+impl Address {
+	/// The address of the storage, which pays the mining rewards. It has no
+	/// private key, so it is not a valid Base58 address.
+	#[inline]
+	#[must_use]
+	pub const fn storage() -> Self {
+		Self(std::borrow::Cow::Borrowed(crate::consts::STORAGE_ADDRESS))
+	}
+
+	/// Validates that `s` is the storage address or a Base58 string with the
+	/// network byte and a valid checksum.
+	///
+	/// The length of `s` is checked before decoding, so the oversized strings
+	/// are rejected cheaply.
+	pub fn parse(s: &str) -> Result<Self, ParseAddressError> {
+		use base58::FromBase58 as _;
+
+		if s == crate::consts::STORAGE_ADDRESS {
+			return Ok(Self::storage());
+		}
+		if !common::consts::ADDRESS_ENCODED_LEN_RANGE.contains(&s.len()) {
+			return Err(ParseAddressError::InvalidEncodedLen);
+		}
+		let bytes = s.from_base58().map_err(ParseAddressError::FromBase58)?;
+		if bytes.len() != DECODED_LEN {
+			return Err(ParseAddressError::InvalidLen);
+		} else if bytes[0] != NETWORK_BYTE {
+			return Err(ParseAddressError::InvalidNetworkByte);
+		}
+		let (body, checksum) = bytes.split_at(DECODED_LEN - CHECKSUM_LEN);
+		if crate::helpers::get_checksum(body) != hex::encode(checksum) {
+			return Err(ParseAddressError::InvalidChecksum);
+		}
+		Ok(Self(std::borrow::Cow::Owned(s.to_owned())))
+	}
+
+	/// Wraps the address that was just made from a public key.
+	pub(crate) fn from_public_key_hash(s: String) -> Self {
+		debug_assert!(Self::parse(&s).is_ok());
+		Self(std::borrow::Cow::Owned(s))
+	}
+
+	#[inline]
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+
+	#[inline]
+	#[must_use]
+	pub fn is_storage(&self) -> bool {
+		self.0 == crate::consts::STORAGE_ADDRESS
+	}
+}
+
+impl AsRef<str> for Address {
+	#[inline]
+	fn as_ref(&self) -> &str {
+		&self.0
+	}
+}
+
+impl std::fmt::Display for Address {
+	#[inline]
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl std::str::FromStr for Address {
+	type Err = ParseAddressError;
+
+	#[inline]
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::parse(s)
+	}
+}
+
+impl std::convert::TryFrom<String> for Address {
+	type Error = ParseAddressError;
+
+	#[inline]
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		Self::parse(&s)
+	}
+}
+
+impl From<Address> for String {
+	#[inline]
+	fn from(address: Address) -> Self {
+		address.0.into_owned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_parse_valid() {
+		let user = crate::test_helpers::create_test_user();
+		let address = super::Address::parse(user.address().as_str()).unwrap();
+		assert_eq!(&address, user.address());
+		assert!(!address.is_storage());
+
+		let storage = super::Address::parse("STORAGE").unwrap();
+		assert_eq!(storage, super::Address::storage());
+		assert!(storage.is_storage());
+	}
+
+	#[test]
+	fn test_parse_invalid() {
+		use {crate::error::ParseAddressError, base58::ToBase58 as _};
+
+		let user = crate::test_helpers::create_test_user();
+		let valid = user.address().as_str();
+
+		// Not Base58
+		assert!(matches!(
+			super::Address::parse(&"0OIl".repeat(8)),
+			Err(ParseAddressError::FromBase58(_))
+		));
+		// Too short
+		assert!(matches!(
+			super::Address::parse(""),
+			Err(ParseAddressError::InvalidEncodedLen)
+		));
+		assert!(matches!(
+			super::Address::parse("recipient"),
+			Err(ParseAddressError::InvalidEncodedLen)
+		));
+		assert!(matches!(
+			super::Address::parse(&valid[..25]),
+			Err(ParseAddressError::InvalidLen)
+		));
+		// Too long
+		assert!(matches!(
+			super::Address::parse(&"1".repeat(36)),
+			Err(ParseAddressError::InvalidEncodedLen)
+		));
+		// Another network
+		let mut bytes = base58::FromBase58::from_base58(valid).unwrap();
+		bytes[0] = 1;
+		assert!(matches!(
+			super::Address::parse(&bytes.to_base58()),
+			Err(ParseAddressError::InvalidNetworkByte)
+		));
+		// Changed hash
+		bytes[0] = 0;
+		bytes[1] ^= 1;
+		assert!(matches!(
+			super::Address::parse(&bytes.to_base58()),
+			Err(ParseAddressError::InvalidChecksum)
+		));
+	}
+
+	#[test]
+	fn test_deserialize_invalid() {
+		assert!(
+			serde_json::from_str::<super::Address>("\"recipient\"").is_err()
+		);
+
+		let user = crate::test_helpers::create_test_user();
+		let json = serde_json::to_string(user.address()).unwrap();
+		assert_eq!(
+			&serde_json::from_str::<super::Address>(&json).unwrap(),
+			user.address()
+		);
+	}
+}
@@ -1,9 +1,11 @@
 use crate::error::{
 	ComputeBlockHashError, GenerateBlockProofOfWorkError, SignBlockError,
-	ValidateBlockBalanceStateError, ValidateBlockCreatedAtError,
+	ValidateBlockBalanceStateError, ValidateBlockChainIdError,
+	ValidateBlockCreatedAtError, ValidateBlockGenesisError,
 	ValidateBlockIntegrityError, ValidateBlockIsSignedError,
-	ValidateBlockMinerSignatureError, ValidateBlockPreviousHashError,
-	ValidateBlockProofOfWorkError, ValidateBlockTransactionsError,
+	ValidateBlockMinerError, ValidateBlockMinerSignatureError,
+	ValidateBlockPreviousHashError, ValidateBlockProofOfWorkError,
+	ValidateBlockStandaloneError, ValidateBlockTransactionsError,
 };
 
 pub(crate) type Transactions<'a> = arrayvec::ArrayVec<
@@ -11,65 +13,310 @@ pub(crate) type Transactions<'a> = arrayvec::ArrayVec<
 	{ crate::consts::TRANSACTIONS_PER_BLOCK as usize },
 >;
 
+/// Short information about the block, which is enough to chart the block
+/// production without pulling full blocks.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct BlockStat {
+	height: usize,
+	created_at: f64,
+	transactions_count: usize,
+	miner: String,
+}
+
+impl BlockStat {
+	common::accessor!(copy height -> usize);
+
+	common::accessor!(copy created_at -> f64);
+
+	common::accessor!(copy transactions_count -> usize);
+
+	common::accessor!(& miner -> &str);
+
+	#[must_use]
+	pub(crate) fn new(height: usize, block: &Block) -> Self {
+		Self {
+			height,
+			created_at: block.created_at,
+			transactions_count: block.transactions.len(),
+			miner: block.miner.to_string(),
+		}
+	}
+}
+
+/// The balance of the `address` in the balance state of the block at
+/// `height`, which differs from the one computed from the transactions.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BalanceStateDiscrepancy {
+	height: usize,
+	address: String,
+	stored: u64,
+	computed: u64,
+}
+
+impl BalanceStateDiscrepancy {
+	common::accessor!(copy height -> usize);
+
+	common::accessor!(& address -> &str);
+
+	common::accessor!(copy stored -> u64);
+
+	common::accessor!(copy computed -> u64);
+
+	#[must_use]
+	pub(crate) fn new(
+		height: usize,
+		address: String,
+		stored: u64,
+		computed: u64,
+	) -> Self {
+		Self { height, address, stored, computed }
+	}
+}
+
+/// The proof that a transaction was included in the block at `height`.
+///
+/// It carries the whole block instead of a Merkle branch. The hash of the
+/// block commits to its transactions through their Merkle root, so the
+/// recomputed hash checks all of them. The block is at most
+/// `crate::consts::TRANSACTIONS_PER_BLOCK` transactions long.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct InclusionProof<'a> {
+	height: usize,
+	block_hash: String,
+	block: Block<'a>,
+}
+
+impl<'a> InclusionProof<'a> {
+	common::accessor!(copy height -> usize);
+
+	common::accessor!(& block_hash -> &str);
+
+	common::accessor!(& block -> &Block<'a>);
+
+	pub(crate) fn new(
+		height: usize,
+		block: Block<'a>,
+	) -> Result<Self, ComputeBlockHashError> {
+		Ok(Self { height, block_hash: block.compute_hash()?, block })
+	}
+
+	/// Checks that the attached block has `self.block_hash()` and contains
+	/// the transaction with `random_string`.
+	///
+	/// The verifier still has to check that the block with
+	/// `self.block_hash()` is at `self.height()` in its copy of the chain.
+	pub fn verify(
+		&self,
+		random_string: &str,
+	) -> Result<bool, ComputeBlockHashError> {
+		Ok(self.block.compute_hash()? == self.block_hash
+			&& self
+				.block
+				.transactions
+				.iter()
+				.any(|t| t.random_string() == random_string))
+	}
+}
+
+/// The fields of a [`Block`] which are hashed. They are sorted by name, as
+/// the keys of a JSON object, so the hashes of the blocks which were made
+/// before this structure stay the same. The balance deltas are hashed only
+/// if there are some and the chain id only if it is not zero, for the same
+/// reason.
+///
+/// The transactions are hashed only by their Merkle root, unless the block
+/// has none, as the blocks which were made before it.
+#[derive(serde::Serialize)]
+struct HashedBlockBody<'b> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	balance_deltas: Option<String>,
+	balance_state: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	chain_id: Option<u32>,
+	created_at: f64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	merkle_root: Option<&'b str>,
+	miner: &'b str,
+	nonce: u64,
+	previous_hash: Option<&'b str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	transactions: Option<String>,
+}
+
 /// The structure that represents the block, accompanied by transactions in the
-/// quantity `crate::consts::TRANSACTIONS_PER_BLOCK`.
+/// quantity `crate::consts::TRANSACTIONS_PER_BLOCK`. Unknown fields are
+/// rejected.
+///
+/// The balances are stored either in `balance_state` or, if the consensus
+/// has `balance_state_deltas`, as changes in `balance_deltas`.
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Block<'a> {
+	/// The chain id of the consensus. It is zero, the default one, in the
+	/// blocks which were made before it.
+	#[serde(default)]
+	chain_id: u32,
 	miner: std::borrow::Cow<'a, str>,
 	previous_hash: Option<std::borrow::Cow<'a, str>>,
 	transactions: Transactions<'a>,
+	/// The root of the Merkle tree of the hashes of `transactions`. It is
+	/// empty in the blocks which were made before it.
+	#[serde(default)]
+	merkle_root: String,
+	#[serde(with = "crate::helpers::balance_state_serde")]
 	balance_state: crate::helpers::BalanceState,
+	#[serde(default, with = "crate::helpers::balance_deltas_serde")]
+	balance_deltas: crate::helpers::BalanceDeltas,
 	nonce: u64,
 	created_at: f64,
 	miner_signature: Option<String>,
 }
 
 impl<'a> Block<'a> {
+	common::accessor!(copy chain_id -> u32);
+
 	common::accessor!(& miner -> &str);
 
 	common::accessor!(as_deref previous_hash -> Option<&str>);
 
+	common::accessor!(& merkle_root -> &str);
+
 	common::accessor!(& balance_state -> &crate::helpers::BalanceState);
 
+	common::accessor!(& balance_deltas -> &crate::helpers::BalanceDeltas);
+
 	common::accessor!(copy nonce -> u64);
 
+	common::accessor!(copy created_at -> f64);
+
+	#[inline]
+	#[must_use]
+	pub fn transactions(&self) -> &[crate::transaction::Transaction<'a>] {
+		&self.transactions
+	}
+
 	#[must_use = "Add block via `crate::blockchain::Blockchain`."]
 	pub(crate) fn new(
 		miner: impl Into<std::borrow::Cow<'a, str>>,
 		previous_hash: Option<impl Into<std::borrow::Cow<'a, str>>>,
 		transactions: Transactions<'a>,
 		balance_state: crate::helpers::BalanceState,
+		chain_id: u32,
 	) -> Self {
-		Self {
+		let mut rv = Self {
+			chain_id,
 			miner: miner.into(),
 			previous_hash: previous_hash.map(Into::into),
 			transactions,
+			merkle_root: String::new(),
 			balance_state,
+			balance_deltas: crate::helpers::BalanceDeltas::new(),
 			nonce: 0,
 			created_at: crate::helpers::get_timestamp(),
 			miner_signature: None,
-		}
+		};
+		rv.merkle_root = rv.compute_merkle_root();
+		rv
 	}
 
 	/// Simplification for calling all integrity validating functions.
 	///
 	/// The function is designed to validate only new blocks that have not yet
-	/// been entered into the database. To find out why, see
-	/// `self.validate_previous_hash` description.
+	/// been entered into the database: the block must follow the last block.
+	/// To validate a stored block, use
+	/// [`validate_block_at`](crate::blockchain::Blockchain::validate_block_at).
 	#[tracing::instrument(level = tracing::Level::DEBUG, skip(blockchain), ret)]
-	pub fn validate_integrity(
+	pub fn validate_integrity<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockIntegrityError>
+	where
+		S: crate::store::BlockStore,
+	{
+		let previous_hash = blockchain
+			.get_last_block_hash()
+			.map_err(ValidateBlockPreviousHashError::from)?;
+		let height =
+			blockchain.len().map_err(ValidateBlockMinerError::from)?;
+		self.validate_integrity_at(blockchain, height, &previous_hash)
+	}
+
+	/// Same as [`validate_integrity`](Block::validate_integrity), but the
+	/// block is at the `height` and follows the block with the
+	/// `previous_hash`.
+	pub(crate) fn validate_integrity_at<S>(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
-	) -> Result<(), ValidateBlockIntegrityError> {
-		self.validate_previous_hash(blockchain)?;
-		self.validate_proof_of_work()?;
+		blockchain: &crate::blockchain::Blockchain<S>,
+		height: usize,
+		previous_hash: &str,
+	) -> Result<(), ValidateBlockIntegrityError>
+	where
+		S: crate::store::BlockStore,
+	{
+		self.validate_chain_id(blockchain)?;
+		self.validate_previous_hash(previous_hash)?;
+		if blockchain.consensus().require_proof_of_work() {
+			self.validate_proof_of_work()?;
+		}
 		self.validate_is_signed()?;
+		self.validate_miner(blockchain, height)?;
 		self.validate_miner_signature()?;
-		self.validate_transactions(blockchain)?;
+		self.validate_transactions(blockchain, previous_hash)?;
 		self.validate_created_at(blockchain)?;
 		Ok(())
 	}
 
+	/// Validates only the parts of the block which do not depend on the
+	/// chain: the chain id, the proof of work (if the consensus requires it)
+	/// and the miner signature. Use it before the block is kept aside, while
+	/// its parent is unknown.
+	pub fn validate_standalone<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockStandaloneError>
+	where
+		S: crate::store::BlockStore,
+	{
+		self.validate_chain_id(blockchain)?;
+		if blockchain.consensus().require_proof_of_work() {
+			self.validate_proof_of_work()?;
+		}
+		self.validate_is_signed()?;
+		self.validate_miner_signature()?;
+		Ok(())
+	}
+
+	/// Checks that the genesis block is mined for the consensus network and
+	/// has the consensus
+	/// [`genesis_balance_state`](crate::blockchain::Blockchain::genesis_balance_state),
+	/// so a chain with other economics is not joined.
+	pub fn validate_genesis<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockGenesisError>
+	where
+		S: crate::store::BlockStore,
+	{
+		self.validate_chain_id(blockchain)?;
+		let genesis_balance_state =
+			blockchain.genesis_balance_state(&self.miner);
+		let equals = if blockchain.consensus().balance_state_deltas() {
+			self.balance_state.is_empty()
+				&& self.balance_deltas
+					== genesis_balance_state
+						.into_iter()
+						.map(|(a, b)| (a, i128::from(b)))
+						.collect::<crate::helpers::BalanceDeltas>()
+		} else {
+			self.balance_deltas.is_empty()
+				&& self.balance_state == genesis_balance_state
+		};
+		if !equals {
+			return Err(ValidateBlockGenesisError::BalanceStateNotEquals);
+		}
+		Ok(())
+	}
+
 	/// Signs the hash of the block and puts it in `self.miner_signature`.
 	#[tracing::instrument(level = tracing::Level::DEBUG, skip(miner), ret)]
 	pub fn sign(
@@ -83,30 +330,119 @@ impl<'a> Block<'a> {
 		Ok(())
 	}
 
-	/// Calculates block body hash (without `self.miner_signature` and
-	/// `self.proof_of_work`).
+	/// Calculates block body hash (without `self.miner_signature`) from
+	/// [`canonical_bytes`](Block::canonical_bytes).
 	#[tracing::instrument(level = tracing::Level::TRACE, ret)]
 	pub fn compute_hash(&self) -> Result<String, ComputeBlockHashError> {
 		use sha2::Digest as _;
 
-		let json = serde_json::json!({
-			"miner": self.miner,
-			"previous_hash": self.previous_hash,
-			"transactions": serde_json::to_string(&self.transactions)
-				.map_err(ComputeBlockHashError::TransactionsToJson)?,
-			"balance_state": serde_json::to_string(&self.balance_state)
-				.map_err(ComputeBlockHashError::BalanceStateToJson)?,
-			"nonce": self.nonce,
-			"created_at": self.created_at,
-		});
-		let hash = sha2::Sha256::digest(json.to_string().as_bytes());
+		let hash = sha2::Sha256::digest(&self.canonical_bytes()?);
 		Ok(hex::encode(hash))
 	}
 
+	/// Serializes the block body (without `self.miner_signature`) in the only
+	/// way it is hashed. So the miner signs, a validator verifies and the
+	/// next block links to the same bytes, even after the block is stored
+	/// and loaded back.
+	pub(crate) fn canonical_bytes(
+		&self,
+	) -> Result<Vec<u8>, ComputeBlockHashError> {
+		let body = HashedBlockBody {
+			balance_deltas: if self.balance_deltas.is_empty() {
+				None
+			} else {
+				Some(
+					serde_json::to_string(&self.balance_deltas)
+						.map_err(ComputeBlockHashError::BalanceDeltasToJson)?,
+				)
+			},
+			balance_state: serde_json::to_string(&self.balance_state)
+				.map_err(ComputeBlockHashError::BalanceStateToJson)?,
+			chain_id: (self.chain_id != 0).then_some(self.chain_id),
+			created_at: self.created_at,
+			merkle_root: (!self.merkle_root.is_empty())
+				.then_some(self.merkle_root.as_str()),
+			miner: &self.miner,
+			nonce: self.nonce,
+			previous_hash: self.previous_hash.as_deref(),
+			transactions: if self.merkle_root.is_empty() {
+				Some(
+					serde_json::to_string(&self.transactions)
+						.map_err(ComputeBlockHashError::TransactionsToJson)?,
+				)
+			} else {
+				None
+			},
+		};
+		serde_json::to_vec(&body).map_err(ComputeBlockHashError::BodyToJson)
+	}
+
+	/// Computes the root of the Merkle tree over the hashes of the
+	/// transactions. A level of an odd length is completed with a copy of its
+	/// last hash. The root of no transactions is the hash of nothing.
+	#[must_use]
+	pub fn compute_merkle_root(&self) -> String {
+		use sha2::Digest as _;
+
+		let mut level: Vec<[u8; 32]> = self
+			.transactions
+			.iter()
+			.map(crate::transaction::Transaction::compute_digest)
+			.collect();
+		if level.is_empty() {
+			return hex::encode(sha2::Sha256::digest(&[]));
+		}
+		while level.len() > 1 {
+			if level.len() % 2 == 1 {
+				level.push(level[level.len() - 1]);
+			}
+			level = level
+				.chunks(2)
+				.map(|pair| {
+					sha2::Sha256::new()
+						.chain(pair[0])
+						.chain(pair[1])
+						.finalize()
+						.into()
+				})
+				.collect();
+		}
+		hex::encode(level[0])
+	}
+
+	/// Whether the `transaction` is in the block and the stored
+	/// `self.merkle_root` is the root of its transactions. Always `false` for
+	/// the blocks without the root.
+	#[must_use]
+	pub fn verify_transaction_inclusion(
+		&self,
+		transaction: &crate::transaction::Transaction<'_>,
+	) -> bool {
+		let digest = transaction.compute_digest();
+		!self.merkle_root.is_empty()
+			&& self.merkle_root == self.compute_merkle_root()
+			&& self.transactions.iter().any(|t| t.compute_digest() == digest)
+	}
+
+	/// Seconds since `self.created_at`.
+	#[inline]
+	#[must_use]
+	pub fn age(&self) -> f64 {
+		crate::helpers::get_timestamp() - self.created_at
+	}
+
 	/// Generates a proof of work or, in other words, starts mining. Mining
 	/// and, accordingly, increasing `self.nonce` occurs until the hash
 	/// contains `crate::consts::PROOF_OF_WORK_DIFFICULTY` zeros.
 	///
+	/// The nonce is searched by `crate::consts::MINING_THREADS` threads. The
+	/// thread `i` starts from `self.nonce + i` and strides by the number of
+	/// threads. The block of the first thread, which finds a valid hash, is
+	/// written back and the others are stopped.
+	///
+	/// If the nonce space of a thread is exhausted, its `created_at` is
+	/// updated and its nonce starts from `i` again, so the hashes are new.
+	///
 	/// # Errors
 	///
 	/// `GenerateBlockProofOfWorkError::Stopped` If you set
@@ -120,43 +456,111 @@ impl<'a> Block<'a> {
 	pub(crate) fn generate_proof_of_work(
 		&mut self,
 	) -> Result<(), GenerateBlockProofOfWorkError> {
-		use std::sync::atomic::Ordering;
+		use std::sync::atomic::{AtomicBool, Ordering};
 
 		debug_assert!(crate::blockchain::IS_MINING.load(Ordering::Acquire));
+		let threads = crate::consts::MINING_THREADS.get();
+		// Set by the thread which finds a valid hash or fails
+		let done = AtomicBool::new(false);
+		let results: Vec<_> = std::thread::scope(|scope| {
+			let handles: Vec<_> = (0..threads)
+				.map(|offset| {
+					let mut block = self.clone();
+					let done = &done;
+					scope.spawn(move || {
+						let rv = block.search_nonce(
+							offset as u64,
+							threads as u64,
+							done,
+						);
+						if rv.is_err() {
+							done.store(true, Ordering::Release);
+						}
+						rv.map(|found| found.then_some(block))
+					})
+				})
+				.collect();
+			handles.into_iter().map(|h| h.join().unwrap()).collect()
+		});
+
+		let mut rv = Err(GenerateBlockProofOfWorkError::Stopped);
+		for result in results {
+			match result {
+				Ok(Some(block)) => {
+					*self = block;
+					return Ok(());
+				}
+				Ok(None) | Err(GenerateBlockProofOfWorkError::Stopped) => {}
+				Err(e) => rv = Err(e),
+			}
+		}
+		rv
+	}
+
+	/// Searches the nonce of the proof of work from `self.nonce + offset`
+	/// with the `stride`, until it is found or the search is `done` by
+	/// another thread.
+	///
+	/// Returns `true`, if the nonce is found by this search first.
+	fn search_nonce(
+		&mut self,
+		offset: u64,
+		stride: u64,
+		done: &std::sync::atomic::AtomicBool,
+	) -> Result<bool, GenerateBlockProofOfWorkError> {
+		use std::sync::atomic::Ordering;
+
+		let mut nonce = self.nonce.checked_add(offset);
 		loop {
-			if !crate::blockchain::IS_MINING.load(Ordering::Acquire) {
+			if let Some(n) = nonce {
+				self.nonce = n;
+			} else {
+				tracing::warn!("The nonce space is exhausted.");
+				self.created_at = crate::helpers::get_timestamp();
+				self.nonce = offset;
+			}
+			if done.load(Ordering::Acquire) {
+				return Ok(false);
+			} else if !crate::blockchain::IS_MINING.load(Ordering::Acquire) {
 				return Err(GenerateBlockProofOfWorkError::Stopped);
 			}
 			match self.validate_proof_of_work() {
-				Ok(()) => break,
+				Ok(()) => return Ok(!done.swap(true, Ordering::AcqRel)),
 				Err(ValidateBlockProofOfWorkError::Invalid) => {}
 				Err(e) => return Err(e)?,
 			}
-			self.nonce += 1;
+			nonce = self.nonce.checked_add(stride);
+		}
+	}
+
+	/// Checks that the block is mined for the consensus network.
+	pub(crate) fn validate_chain_id<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockChainIdError>
+	where
+		S: crate::store::BlockStore,
+	{
+		if self.chain_id != blockchain.consensus().chain_id() {
+			return Err(ValidateBlockChainIdError::NotEquals);
 		}
 		Ok(())
 	}
 
-	/// This function could find the previous block from the current block and
-	/// compare `self.previous_hash` already with it. This is not implemented,
-	/// because it is simply not used. This function is designed for blocks
-	/// that have not yet been added to the database. That is,
-	/// `self.previous_hash` is compared to the hash of the last block from the
-	/// database using the `crate::blockchain::Blockchain::get_last_block_hash`
-	/// method.
+	/// Compares `self.previous_hash` with the hash of the block before it:
+	/// the last block from the database for a new block, or the stored
+	/// predecessor for a stored one.
 	fn validate_previous_hash(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
+		previous_hash: &str,
 	) -> Result<(), ValidateBlockPreviousHashError> {
-		let previous_hash = blockchain.get_last_block_hash()?;
-		if self.previous_hash.as_deref().map_or(false, |h| h != previous_hash)
-		{
+		if self.previous_hash.as_deref().is_some_and(|h| h != previous_hash) {
 			return Err(ValidateBlockPreviousHashError::HashesNotEquals);
 		}
 		Ok(())
 	}
 
-	fn validate_proof_of_work(
+	pub(crate) fn validate_proof_of_work(
 		&self,
 	) -> Result<(), ValidateBlockProofOfWorkError> {
 		let hash = self.compute_hash()?;
@@ -167,19 +571,48 @@ impl<'a> Block<'a> {
 		Ok(())
 	}
 
-	fn validate_is_signed(&self) -> Result<(), ValidateBlockIsSignedError> {
+	pub(crate) fn validate_is_signed(
+		&self,
+	) -> Result<(), ValidateBlockIsSignedError> {
 		if self.miner_signature.is_none() {
 			return Err(ValidateBlockIsSignedError::NotSigned);
 		}
 		Ok(())
 	}
 
+	/// Checks that the miner is authorized by the consensus and that it is
+	/// its turn by the consensus schedule at the `height` of the block.
+	fn validate_miner<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+		height: usize,
+	) -> Result<(), ValidateBlockMinerError>
+	where
+		S: crate::store::BlockStore,
+	{
+		let consensus = blockchain.consensus();
+		if consensus
+			.authorized_miners()
+			.is_some_and(|m| !m.contains(&*self.miner))
+		{
+			return Err(ValidateBlockMinerError::NotAuthorized);
+		}
+		if let Some(miner) = consensus.scheduled_miner(height) {
+			if miner != self.miner {
+				return Err(ValidateBlockMinerError::NotScheduled(
+					miner.to_owned(),
+				));
+			}
+		}
+		Ok(())
+	}
+
 	/// Checks the signature via `crate::user::User::validate_signature`.
 	///
 	/// # Panic
 	///
 	/// If block is not signed.
-	fn validate_miner_signature(
+	pub(crate) fn validate_miner_signature(
 		&self,
 	) -> Result<(), ValidateBlockMinerSignatureError> {
 		let signature = self.miner_signature.as_ref().unwrap();
@@ -190,10 +623,13 @@ impl<'a> Block<'a> {
 
 	/// Checks the validity of `self.created_at` in the whole plan, and then
 	/// relative to the previous one.
-	fn validate_created_at(
+	fn validate_created_at<S>(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
-	) -> Result<(), ValidateBlockCreatedAtError> {
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockCreatedAtError>
+	where
+		S: crate::store::BlockStore,
+	{
 		if crate::helpers::get_timestamp() - self.created_at < 0.0 {
 			return Err(ValidateBlockCreatedAtError::InFuture);
 		}
@@ -207,23 +643,57 @@ impl<'a> Block<'a> {
 
 	/// Validates the integrity of `transactions`. Also uses
 	/// [`validate_balance_state`](Block::validate_balance_state) and method
-	/// [`validate_integrity`](Transaction::validate_integrity).
-	fn validate_transactions(
+	/// [`validate_integrity`](Transaction::validate_integrity) against the
+	/// block with the `previous_hash`.
+	fn validate_transactions<S>(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
-	) -> Result<(), ValidateBlockTransactionsError> {
+		blockchain: &crate::blockchain::Blockchain<S>,
+		previous_hash: &str,
+	) -> Result<(), ValidateBlockTransactionsError>
+	where
+		S: crate::store::BlockStore,
+	{
+		// The blocks without the root hash the transactions themselves
+		if !self.merkle_root.is_empty()
+			&& self.merkle_root != self.compute_merkle_root()
+		{
+			return Err(ValidateBlockTransactionsError::MerkleRootNotEquals);
+		}
+
 		let count = self.transactions.len();
 		let storage_count = self
 			.transactions
 			.iter()
-			.filter(|t| t.sender() == crate::consts::STORAGE_ADDRESS)
+			.filter(|t| t.sender().is_storage())
 			.count();
 
-		// Validate counts
-		if count != (crate::consts::USER_TRANSACTIONS_PER_BLOCK + 1) as usize {
-			return Err(ValidateBlockTransactionsError::InvalidUserCount);
-		} else if storage_count != 1 {
+		// Validate counts. A block may be not full of user transactions, but
+		// not less than the consensus minimum
+		let user_counts =
+			blockchain.consensus().min_user_transactions_per_block() as usize
+				..=crate::consts::USER_TRANSACTIONS_PER_BLOCK as usize;
+		let rewards = blockchain.mining_rewards(&self.miner);
+		if storage_count != rewards.len() {
 			return Err(ValidateBlockTransactionsError::InvalidStorageCount);
+		} else if !user_counts.contains(&(count - storage_count)) {
+			return Err(ValidateBlockTransactionsError::InvalidUserCount);
+		}
+
+		// Validate the rewards of the miner and the treasury
+		let storage_transactions =
+			self.transactions.iter().filter(|t| t.sender().is_storage());
+		for (i, (transaction, (recipient, amount))) in
+			storage_transactions.zip(rewards).enumerate()
+		{
+			if transaction.recipient().as_str() != recipient {
+				return Err(if i == 0 && recipient == self.miner {
+					ValidateBlockTransactionsError::RewardedNotMiner
+				} else {
+					ValidateBlockTransactionsError::RewardedNotTreasury
+				});
+			} else if transaction.amount() != amount {
+				return Err(ValidateBlockTransactionsError::InvalidReward);
+			}
 		}
 
 		// Checking the uniqueness of `self.random_string'
@@ -239,68 +709,179 @@ impl<'a> Block<'a> {
 			}
 		}
 
-		for transaction in &self.transactions {
-			transaction.validate_integrity(blockchain)?;
-
-			// Validate reward
-			if transaction.sender() == crate::consts::STORAGE_ADDRESS {
-				if transaction.recipient() != self.miner {
-					return Err(
-						ValidateBlockTransactionsError::RewardedNotMiner,
-					);
-				} else if transaction.amount() != crate::consts::MINING_REWARD
-				{
-					return Err(ValidateBlockTransactionsError::InvalidReward);
-				}
+		// The balances are stored only in the way of the consensus
+		if blockchain.consensus().balance_state_deltas() {
+			if !self.balance_state.is_empty() {
+				return Err(
+					ValidateBlockTransactionsError::UnexpectedBalanceState,
+				);
 			}
+		} else if !self.balance_deltas.is_empty() {
+			return Err(
+				ValidateBlockTransactionsError::UnexpectedBalanceDeltas,
+			);
+		}
+
+		for transaction in &self.transactions {
+			transaction.validate_integrity_at(blockchain, previous_hash)?;
 
 			// Validate previous block hash
-			if !self
+			if self
 				.previous_hash
 				.as_ref()
-				.map_or(false, |h| h == transaction.previous_block_hash())
+				.is_none_or(|h| h != transaction.previous_block_hash())
 			{
 				return Err(
 					ValidateBlockTransactionsError::PreviousHashesNotEquals,
 				);
 			}
 			// Validate balance state for sender and recipient
-			self.validate_balance_state(transaction.sender(), blockchain)
-				.map_err(
-					ValidateBlockTransactionsError::ValidateSenderBalanceState,
-				)?;
-			self.validate_balance_state(transaction.recipient(), blockchain)
-				.map_err(
+			self.validate_balance_state(
+				transaction.sender().as_str(),
+				blockchain,
+			)
+			.map_err(
+				ValidateBlockTransactionsError::ValidateSenderBalanceState,
+			)?;
+			self.validate_balance_state(
+				transaction.recipient().as_str(),
+				blockchain,
+			)
+			.map_err(
 				ValidateBlockTransactionsError::ValidateRecipientBalanceState,
 			)?;
 		}
 
+		// The balances of the other addresses are not validated above, so
+		// they must not be in the balance state
+		if let Some(address) = self
+			.balance_state
+			.keys()
+			.chain(self.balance_deltas.keys())
+			.find(|a| !self.touches(a))
+		{
+			return Err(
+				ValidateBlockTransactionsError::UntouchedBalanceState(
+					address.clone(),
+				),
+			);
+		}
+
 		Ok(())
 	}
 
+	/// Whether the balance of the `address` is changed by the transactions
+	/// of the block.
+	fn touches(&self, address: &str) -> bool {
+		self.transactions.iter().any(|t| {
+			address == t.sender().as_str()
+				|| address == t.recipient().as_str()
+				|| (address == crate::consts::STORAGE_ADDRESS
+					&& t.amount_to_storage() != 0)
+				|| (address == self.miner && t.fee() != 0)
+		})
+	}
+
+	/// Calculates how much the `address` spent and received in the
+	/// transactions of the block. The miner receives the fees.
+	///
+	/// Returns `None` if a sum overflows. Each transaction bounds only its own
+	/// total cost, so the sums of several ones may not fit.
+	pub(crate) fn balance_change(&self, address: &str) -> Option<(u64, u64)> {
+		let (mut spent, mut received) = (0_u64, 0_u64);
+		for transaction in &self.transactions {
+			if address == transaction.sender().as_str() {
+				spent = spent.checked_add(transaction.total_cost()?.get())?;
+				// The self transaction returns the amount to the sender
+				if transaction.sender() == transaction.recipient() {
+					received =
+						received.checked_add(transaction.amount().get())?;
+				}
+			} else if address == transaction.recipient().as_str() {
+				received = received.checked_add(transaction.amount().get())?;
+			} else if address == crate::consts::STORAGE_ADDRESS {
+				received =
+					received.checked_add(transaction.amount_to_storage())?;
+			}
+			if address == self.miner {
+				received = received.checked_add(transaction.fee())?;
+			}
+		}
+		Some((spent, received))
+	}
+
+	/// Replaces the balance state with the changes of the balances. The
+	/// changes of the genesis block are its balances.
+	pub(crate) fn use_balance_deltas(&mut self) {
+		let balance_state = std::mem::take(&mut self.balance_state);
+		self.balance_deltas = if self.previous_hash.is_none() {
+			balance_state
+				.into_iter()
+				.map(|(a, b)| (a, i128::from(b)))
+				.collect()
+		} else {
+			balance_state
+				.into_keys()
+				.map(|a| {
+					// The overflowing change is rejected by the validation
+					let delta = self.balance_change(&a).map_or(
+						0,
+						|(spent, received)| {
+							i128::from(received) - i128::from(spent)
+						},
+					);
+					(a, delta)
+				})
+				.collect()
+		};
+	}
+
+	/// Replaces the balance state. The hash of the block is changed.
+	pub(crate) fn replace_balance_state(
+		&mut self,
+		balance_state: crate::helpers::BalanceState,
+	) {
+		self.balance_state = balance_state;
+	}
+
 	/// Calculates the balance using the data in the transactions and compares
-	/// the calculations to those specified in `self.balance_state`.
-	fn validate_balance_state(
+	/// the calculations to those specified in `self.balance_state` or in
+	/// `self.balance_deltas`.
+	fn validate_balance_state<S>(
 		&self,
 		address: &str,
-		blockchain: &crate::blockchain::Blockchain,
-	) -> Result<(), ValidateBlockBalanceStateError> {
-		if let Some(state_balance) = self.balance_state.get(address) {
-			let (mut spent, mut received) = (0, 0);
-			let balance =
-				blockchain.get_balance_from_database(address, Some(self))?;
-
-			// Calculation of costs and receipts
-			for transaction in &self.transactions {
-				if address == transaction.sender() {
-					spent += u64::from(transaction.amount())
-						+ transaction.amount_to_storage();
-				} else if address == transaction.recipient() {
-					received += u64::from(transaction.amount());
-				} else if address == crate::consts::STORAGE_ADDRESS {
-					received += transaction.amount_to_storage();
-				}
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateBlockBalanceStateError>
+	where
+		S: crate::store::BlockStore,
+	{
+		use std::convert::TryFrom as _;
+
+		if blockchain.consensus().balance_state_deltas() {
+			let delta = self
+				.balance_deltas
+				.get(address)
+				.ok_or(ValidateBlockBalanceStateError::NoBalanceInState)?;
+			let (spent, received) = self
+				.balance_change(address)
+				.ok_or(ValidateBlockBalanceStateError::ChangeOverflow)?;
+			if *delta != i128::from(received) - i128::from(spent) {
+				return Err(ValidateBlockBalanceStateError::BalancesNotEquals);
 			}
+			let balance = blockchain.get_balance_before(address, self)?;
+			return match u64::try_from(i128::from(balance) + delta) {
+				Ok(_) => Ok(()),
+				Err(_) if *delta < 0 => {
+					Err(ValidateBlockBalanceStateError::SubOverflow)
+				}
+				Err(_) => Err(ValidateBlockBalanceStateError::AddOverflow),
+			};
+		}
+		if let Some(state_balance) = self.balance_state.get(address) {
+			let balance = blockchain.get_balance_before(address, self)?;
+			let (spent, received) = self
+				.balance_change(address)
+				.ok_or(ValidateBlockBalanceStateError::ChangeOverflow)?;
 
 			// Comparison of balance and expectations
 			match balance.checked_add(received) {
@@ -329,14 +910,758 @@ mod tests {
 		let (user, mut block) = crate::test_helpers::create_test_block();
 		block.sign(&user).unwrap();
 		block.validate_miner_signature().unwrap();
-		Ok(())
 	}
 
 	#[test]
-	fn test_generate_proof_of_work() -> Result<()> {
+	fn test_canonical_bytes_after_round_trip() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..crate::consts::USER_TRANSACTIONS_PER_BLOCK {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 15);
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		let block = blockchain.mine_block().unwrap();
+
+		// The stored block is hashed and verified from the same bytes
+		let stored = blockchain.get_last_block().unwrap().unwrap();
+		assert_eq!(
+			stored.canonical_bytes().unwrap(),
+			block.canonical_bytes().unwrap()
+		);
+		assert_eq!(
+			blockchain.get_last_block_hash().unwrap(),
+			block.compute_hash().unwrap()
+		);
+		stored.validate_miner_signature().unwrap();
+
+		// Same after one more JSON round trip
+		let json = serde_json::to_string(&stored).unwrap();
+		let loaded: super::Block = serde_json::from_str(&json).unwrap();
+		assert_eq!(
+			loaded.canonical_bytes().unwrap(),
+			block.canonical_bytes().unwrap()
+		);
+		loaded.validate_miner_signature().unwrap();
+	}
+
+	#[test]
+	fn test_canonical_bytes_are_stable() {
+		// The bytes of the JSON object which was hashed before, so the stored
+		// blocks stay valid
+		let (_user, mut block) = crate::test_helpers::create_test_block();
+		block.merkle_root.clear();
+		let json = serde_json::json!({
+			"miner": block.miner,
+			"previous_hash": block.previous_hash,
+			"transactions": serde_json::to_string(&block.transactions).unwrap(),
+			"balance_state":
+				serde_json::to_string(&block.balance_state).unwrap(),
+			"nonce": block.nonce,
+			"created_at": block.created_at,
+		});
+		assert_eq!(
+			block.canonical_bytes().unwrap(),
+			json.to_string().into_bytes()
+		);
+	}
+
+	#[test]
+	fn test_load_without_chain_id() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		let block = blockchain.mine_block().unwrap();
+
+		// The block and the transactions, which were made before the chain
+		// id, are loaded with the default one and stay valid
+		let mut json = serde_json::to_value(&block).unwrap();
+		json.as_object_mut().unwrap().remove("chain_id").unwrap();
+		for transaction in json["transactions"].as_array_mut().unwrap() {
+			transaction.as_object_mut().unwrap().remove("chain_id").unwrap();
+		}
+		let loaded: super::Block = serde_json::from_value(json).unwrap();
+		assert_eq!(
+			loaded.compute_hash().unwrap(),
+			block.compute_hash().unwrap()
+		);
+		assert_eq!(loaded.compute_merkle_root(), block.merkle_root);
+		loaded.validate_standalone(&blockchain).unwrap();
+		for transaction in loaded.transactions() {
+			transaction.verify_signature_only().unwrap();
+		}
+	}
+
+	#[test]
+	fn test_merkle_root() {
+		use sha2::Digest as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let (_user, mut block) = crate::test_helpers::create_test_block();
+		assert_eq!(block.merkle_root, hex::encode(sha2::Sha256::digest(&[])));
+
+		let transactions: Vec<_> = (0..3)
+			.map(|_| {
+				crate::test_helpers::create_test_transaction(&blockchain, 15)
+			})
+			.collect();
+		let digests: Vec<_> = transactions
+			.iter()
+			.map(crate::transaction::Transaction::compute_digest)
+			.collect();
+		let node = |l: &[u8], r: &[u8]| -> [u8; 32] {
+			sha2::Sha256::new().chain(l).chain(r).finalize().into()
+		};
+		block.transactions.extend(transactions.iter().cloned());
+		assert_eq!(
+			block.compute_merkle_root(),
+			hex::encode(node(
+				&node(&digests[0], &digests[1]),
+				&node(&digests[2], &digests[2]),
+			))
+		);
+
+		// The transactions are hashed only by the root
+		block.merkle_root = block.compute_merkle_root();
+		let bytes = block.canonical_bytes().unwrap();
+		block.transactions.pop();
+		assert_eq!(block.canonical_bytes().unwrap(), bytes);
+		assert!(!block.verify_transaction_inclusion(&transactions[0]));
+		block.transactions.push(transactions[2].clone());
+		assert!(transactions
+			.iter()
+			.all(|t| block.verify_transaction_inclusion(t)));
+		assert!(!block.verify_transaction_inclusion(
+			&crate::test_helpers::create_test_transaction(&blockchain, 15)
+		));
+	}
+
+	#[test]
+	fn test_balance_state_json() {
+		const BALANCE: u64 = u64::MAX - 1;
+
+		let mut state = crate::helpers::BalanceState::new();
+		state.insert("address".to_owned(), BALANCE);
+		let block = super::Block::new(
+			"miner",
+			None::<&str>,
+			crate::block::Transactions::new(),
+			state,
+			0,
+		);
+
+		let json = serde_json::to_string(&block).unwrap();
+		assert!(json.contains(&format!("\"{BALANCE}\"")));
+		let block: super::Block = serde_json::from_str(&json).unwrap();
+		assert_eq!(block.balance_state()["address"], BALANCE);
+	}
+
+	#[test]
+	fn test_age() {
+		let (_user, mut block) = crate::test_helpers::create_test_block();
+		assert!((0.0..1.0).contains(&block.age()));
+		block.created_at -= 60.0;
+		assert!((60.0..61.0).contains(&block.age()));
+	}
+
+	#[test]
+	fn test_deserialize_unknown_field() {
+		let (_user, block) = crate::test_helpers::create_test_block();
+		let mut json = serde_json::to_value(&block).unwrap();
+		json["hash"] = serde_json::json!("0000");
+		assert!(serde_json::from_value::<super::Block>(json).is_err());
+	}
+
+	#[test]
+	fn test_generate_proof_of_work() {
+		use std::sync::atomic::Ordering;
+
+		let _lock = crate::test_helpers::lock_mining();
 		let (_user, mut block) = crate::test_helpers::create_test_block();
+		crate::blockchain::IS_MINING.store(true, Ordering::SeqCst);
 		block.generate_proof_of_work().unwrap();
+		crate::blockchain::IS_MINING.store(false, Ordering::SeqCst);
 		block.validate_proof_of_work().unwrap();
-		Ok(())
+	}
+
+	#[test]
+	fn test_generate_proof_of_work_threads() {
+		use std::sync::atomic::Ordering;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (_user, block) = crate::test_helpers::create_test_block();
+		crate::blockchain::IS_MINING.store(true, Ordering::SeqCst);
+		for _ in 0..16 {
+			let mut mined = block.clone();
+			mined.generate_proof_of_work().unwrap();
+			mined.validate_proof_of_work().unwrap();
+			// Only the nonce is searched
+			assert_eq!(mined.created_at.to_bits(), block.created_at.to_bits());
+			assert_eq!(mined.miner, block.miner);
+		}
+		crate::blockchain::IS_MINING.store(false, Ordering::SeqCst);
+	}
+
+	#[test]
+	fn test_generate_proof_of_work_nonce_overflow() {
+		use std::sync::atomic::Ordering;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (_user, mut block) = crate::test_helpers::create_test_block();
+		// Start from the last nonce, which does not give a valid hash
+		block.nonce = u64::MAX;
+		block.created_at = 0.0;
+		while block.validate_proof_of_work().is_ok() {
+			block.created_at += 1.0;
+		}
+		let created_at = block.created_at;
+
+		crate::blockchain::IS_MINING.store(true, Ordering::SeqCst);
+		block.generate_proof_of_work().unwrap();
+		crate::blockchain::IS_MINING.store(false, Ordering::SeqCst);
+		block.validate_proof_of_work().unwrap();
+		assert!(block.nonce < u64::MAX);
+		assert!(block.created_at > created_at);
+	}
+
+	#[test]
+	fn test_validate_user_transactions_count() {
+		use crate::error::{
+			AddBlockError, MineBlockError, ValidateBlockIntegrityError,
+			ValidateBlockTransactionsError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let consensus = crate::test_helpers::create_test_consensus(
+			serde_json::json!({"min_user_transactions_per_block": 2}),
+		);
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(consensus);
+
+		// Less than minimum
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(matches!(
+			blockchain.mine_block(),
+			Err(MineBlockError::AddBlock(AddBlockError::ValidateIntegrity(
+				ValidateBlockIntegrityError::ValidateTransactions(
+					ValidateBlockTransactionsError::InvalidUserCount
+				)
+			)))
+		));
+
+		// Full
+		for _ in 0..crate::consts::USER_TRANSACTIONS_PER_BLOCK {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		blockchain.mine_block().unwrap();
+	}
+
+	#[test]
+	fn test_validate_unauthorized_miner() {
+		use crate::error::{
+			AddBlockError, MineBlockError, ValidateBlockIntegrityError,
+			ValidateBlockMinerError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let consensus = crate::test_helpers::create_test_consensus(
+			serde_json::json!({"authorized_miners": ["authorized"]}),
+		);
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(consensus);
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(matches!(
+			blockchain.mine_block(),
+			Err(MineBlockError::AddBlock(AddBlockError::ValidateIntegrity(
+				ValidateBlockIntegrityError::ValidateMiner(
+					ValidateBlockMinerError::NotAuthorized
+				)
+			)))
+		));
+	}
+
+	#[test]
+	fn test_validate_scheduled_miner() {
+		use crate::error::{
+			AddBlockError, MineBlockError, ValidateBlockIntegrityError,
+			ValidateBlockMinerError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let user = crate::test_helpers::create_test_user();
+		let other = crate::test_helpers::create_test_user();
+		let consensus = crate::test_helpers::create_test_consensus(
+			serde_json::json!({"miner_schedule": [other.address(), user.address()]}),
+		);
+		let mut blockchain = crate::blockchain::Blockchain::new(
+			user,
+			consensus,
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+
+		// The turn of the user is at the odd heights
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(matches!(
+			blockchain.mine_block(),
+			Err(MineBlockError::AddBlock(AddBlockError::ValidateIntegrity(
+				ValidateBlockIntegrityError::ValidateMiner(
+					ValidateBlockMinerError::NotScheduled(m)
+				)
+			))) if m == other.address().as_str()
+		));
+	}
+
+	#[test]
+	fn test_validate_under_full_block() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(!blockchain.minable());
+		blockchain.mine_block().unwrap();
+		assert_eq!(blockchain.len().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_validate_untouched_balance_state() {
+		use crate::{
+			error::ValidateBlockTransactionsError, store::BlockStore as _,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let user = crate::test_helpers::create_test_user();
+		let mut blockchain = crate::blockchain::Blockchain::new(
+			user.clone(),
+			common::config::Consensus::default(),
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+		// The block is validated by the chain without it
+		let store = crate::store::MemoryStore::default();
+		let genesis = serde_json::to_string(
+			&blockchain.get_last_block().unwrap().unwrap(),
+		)
+		.unwrap();
+		store
+			.insert_block(blockchain.get_last_block_hash().unwrap(), genesis)
+			.unwrap();
+		let before = crate::blockchain::Blockchain::new(
+			user,
+			common::config::Consensus::default(),
+			store,
+		)
+		.unwrap();
+
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		let mut block = blockchain.mine_block().unwrap();
+		let previous_hash = before.get_last_block_hash().unwrap();
+		block.validate_transactions(&before, &previous_hash).unwrap();
+
+		block.balance_state.insert("bogus".to_owned(), 1000);
+		assert!(matches!(
+			block.validate_transactions(&before, &previous_hash),
+			Err(ValidateBlockTransactionsError::UntouchedBalanceState(a))
+				if a == "bogus"
+		));
+	}
+
+	#[test]
+	fn test_validate_fees() {
+		use crate::error::{
+			ValidateBlockBalanceStateError, ValidateBlockIntegrityError,
+			ValidateBlockTransactionsError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (miner, mut block, before) = mine_test_block(&[(20, 3)]);
+		block.validate_integrity(&before).unwrap();
+		let recipient = block
+			.transactions
+			.iter()
+			.find(|t| !t.sender().is_storage())
+			.unwrap()
+			.recipient()
+			.to_string();
+
+		// The miner paid 20, 1 to storage and 3 of fee, and got the fee back
+		// with the reward
+		let balance = |a: &str| block.balance_state()[a];
+		assert_eq!(balance(miner.address().as_str()), 100 - 24 + 3 + 1);
+		assert_eq!(balance(&recipient), 20);
+
+		// More than the fees and the reward
+		*block.balance_state.get_mut(miner.address().as_str()).unwrap() += 1;
+		reseal(&mut block, &miner);
+		assert!(matches!(
+			block.validate_integrity(&before),
+			Err(ValidateBlockIntegrityError::ValidateTransactions(
+				ValidateBlockTransactionsError::ValidateSenderBalanceState(
+					ValidateBlockBalanceStateError::BalancesNotEquals
+				)
+			))
+		));
+	}
+
+	#[test]
+	fn test_validate_balance_change_overflow() {
+		use crate::error::ValidateBlockBalanceStateError;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (miner, mut block, before) = mine_test_block(&[(20, 3), (20, 3)]);
+		let miner = miner.address().as_str();
+
+		// Each fee fits, but their sum exceeds `u64::MAX`
+		for transaction in
+			block.transactions.iter_mut().filter(|t| !t.sender().is_storage())
+		{
+			*transaction = transaction.clone().with_fee(u64::MAX / 2 + 1);
+		}
+		assert!(block.transactions.iter().all(|t| t.total_cost().is_some()));
+		assert_eq!(block.balance_change(miner), None);
+		assert!(matches!(
+			block.validate_balance_state(miner, &before),
+			Err(ValidateBlockBalanceStateError::ChangeOverflow)
+		));
+	}
+
+	/// A change of a single field of a valid block.
+	#[derive(Clone, Copy, Debug)]
+	enum Mutation {
+		ChainId,
+		PreviousHash,
+		Nonce,
+		Unsigned,
+		ForeignSignature,
+		NoUserTransactions,
+		DuplicateTransaction,
+		UntouchedBalance,
+		WrongBalance,
+		CreatedAtInFuture,
+		CreatedAtEqualsPrevious,
+		MerkleRoot,
+	}
+
+	/// The amounts and the fees of the user transactions of a block. The
+	/// amounts around `consts::STORAGE_REWARD_STARTING_FROM` are more likely.
+	fn transactions(
+	) -> impl proptest::strategy::Strategy<Value = Vec<(u64, u64)>> {
+		use proptest::prelude::*;
+
+		let from = crate::consts::STORAGE_REWARD_STARTING_FROM;
+		proptest::collection::vec(
+			(prop_oneof![from - 1..=from, 1..=40_u64], 0..=5_u64),
+			1..=crate::consts::USER_TRANSACTIONS_PER_BLOCK as usize,
+		)
+	}
+
+	fn mutation() -> impl proptest::strategy::Strategy<Value = Mutation> {
+		proptest::sample::select(vec![
+			Mutation::ChainId,
+			Mutation::PreviousHash,
+			Mutation::Nonce,
+			Mutation::Unsigned,
+			Mutation::ForeignSignature,
+			Mutation::NoUserTransactions,
+			Mutation::DuplicateTransaction,
+			Mutation::UntouchedBalance,
+			Mutation::WrongBalance,
+			Mutation::CreatedAtInFuture,
+			Mutation::CreatedAtEqualsPrevious,
+			Mutation::MerkleRoot,
+		])
+	}
+
+	/// Mines a block with the transactions of the amounts and the fees of
+	/// the `transactions` and returns it with its miner and the blockchain
+	/// before it, against which it is validated as a new block.
+	fn mine_test_block<'a>(
+		transactions: &[(u64, u64)],
+	) -> (
+		crate::user::User,
+		super::Block<'a>,
+		crate::blockchain::Blockchain<'a, crate::store::MemoryStore>,
+	) {
+		use crate::store::BlockStore as _;
+
+		let user = crate::test_helpers::create_test_user();
+		let mut blockchain = crate::blockchain::Blockchain::new(
+			user.clone(),
+			common::config::Consensus::default(),
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+		let store = crate::store::MemoryStore::default();
+		let genesis = serde_json::to_string(
+			&blockchain.get_last_block().unwrap().unwrap(),
+		)
+		.unwrap();
+		store
+			.insert_block(blockchain.get_last_block_hash().unwrap(), genesis)
+			.unwrap();
+		let before = crate::blockchain::Blockchain::new(
+			user.clone(),
+			common::config::Consensus::default(),
+			store,
+		)
+		.unwrap();
+
+		for &(amount, fee) in transactions {
+			let transaction =
+				crate::test_helpers::create_test_transaction_with_fee(
+					&blockchain,
+					amount,
+					fee,
+				);
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		let block = blockchain.mine_block().unwrap();
+		(user, block, before)
+	}
+
+	/// Generates the proof of work of the changed `block` again and signs it
+	/// by the `miner`, so only the changed field is invalid.
+	fn reseal(block: &mut super::Block, miner: &crate::user::User) {
+		use std::sync::atomic::Ordering;
+
+		crate::blockchain::IS_MINING.store(true, Ordering::SeqCst);
+		block.generate_proof_of_work().unwrap();
+		crate::blockchain::IS_MINING.store(false, Ordering::SeqCst);
+		block.sign(miner).unwrap();
+	}
+
+	/// Changes the field of the valid `block`, which is chosen by the
+	/// `mutation`. The block is resealed by the `miner`, unless the proof of
+	/// work or the signature is changed.
+	fn apply_mutation<S>(
+		mutation: Mutation,
+		miner: &crate::user::User,
+		block: &mut super::Block,
+		before: &crate::blockchain::Blockchain<S>,
+	) where
+		S: crate::store::BlockStore,
+	{
+		match mutation {
+			Mutation::ChainId => {
+				block.chain_id = block.chain_id.wrapping_add(1);
+				reseal(block, miner);
+			}
+			Mutation::PreviousHash => {
+				block.previous_hash = Some("0".repeat(64).into());
+				reseal(block, miner);
+			}
+			Mutation::Nonce => {
+				while block.validate_proof_of_work().is_ok() {
+					block.nonce = block.nonce.wrapping_add(1);
+				}
+			}
+			Mutation::Unsigned => block.miner_signature = None,
+			Mutation::ForeignSignature => {
+				block.sign(&crate::test_helpers::create_test_user()).unwrap();
+			}
+			Mutation::NoUserTransactions => {
+				block.transactions.retain(|t| t.sender().is_storage());
+				block.merkle_root = block.compute_merkle_root();
+				reseal(block, miner);
+			}
+			Mutation::DuplicateTransaction => {
+				let transaction = block
+					.transactions
+					.iter()
+					.find(|t| !t.sender().is_storage())
+					.unwrap()
+					.clone();
+				block.transactions.push(transaction);
+				block.merkle_root = block.compute_merkle_root();
+				reseal(block, miner);
+			}
+			Mutation::UntouchedBalance => {
+				let address = crate::test_helpers::create_test_user()
+					.address()
+					.to_string();
+				block.balance_state.insert(address, 1);
+				reseal(block, miner);
+			}
+			Mutation::WrongBalance => {
+				*block
+					.balance_state
+					.get_mut(miner.address().as_str())
+					.unwrap() += 1;
+				reseal(block, miner);
+			}
+			Mutation::CreatedAtInFuture => {
+				block.created_at = crate::helpers::get_timestamp() + 3600.0;
+				reseal(block, miner);
+			}
+			Mutation::CreatedAtEqualsPrevious => {
+				block.created_at =
+					before.get_last_block().unwrap().unwrap().created_at;
+				reseal(block, miner);
+			}
+			Mutation::MerkleRoot => {
+				block.merkle_root = "0".repeat(64);
+				reseal(block, miner);
+			}
+		}
+	}
+
+	/// Whether the `result` of the validation of the block, changed by the
+	/// `mutation`, is the expected error. The block was `full` of user
+	/// transactions before the change.
+	fn is_expected_error(
+		mutation: Mutation,
+		full: bool,
+		result: &Result<(), crate::error::ValidateBlockIntegrityError>,
+	) -> bool {
+		use crate::error::{
+			ValidateBlockBalanceStateError, ValidateBlockChainIdError,
+			ValidateBlockCreatedAtError, ValidateBlockIntegrityError as E,
+			ValidateBlockIsSignedError, ValidateBlockMinerSignatureError,
+			ValidateBlockPreviousHashError, ValidateBlockProofOfWorkError,
+			ValidateBlockTransactionsError,
+		};
+
+		match mutation {
+			Mutation::ChainId => matches!(
+				result,
+				Err(E::ValidateChainId(ValidateBlockChainIdError::NotEquals))
+			),
+			Mutation::PreviousHash => matches!(
+				result,
+				Err(E::ValidatePreviousHash(
+					ValidateBlockPreviousHashError::HashesNotEquals
+				))
+			),
+			Mutation::Nonce => matches!(
+				result,
+				Err(E::ValidateProofOfWork(
+					ValidateBlockProofOfWorkError::Invalid
+				))
+			),
+			Mutation::Unsigned => matches!(
+				result,
+				Err(E::ValidateIsSigned(
+					ValidateBlockIsSignedError::NotSigned
+				))
+			),
+			Mutation::ForeignSignature => matches!(
+				result,
+				Err(E::ValidateMinerSignature(
+					ValidateBlockMinerSignatureError::ValidateUserSignature(_)
+				))
+			),
+			Mutation::NoUserTransactions => matches!(
+				result,
+				Err(E::ValidateTransactions(
+					ValidateBlockTransactionsError::InvalidUserCount
+				))
+			),
+			// The block may be full already
+			Mutation::DuplicateTransaction if full => {
+				matches!(
+					result,
+					Err(E::ValidateTransactions(
+						ValidateBlockTransactionsError::InvalidUserCount
+					))
+				)
+			}
+			Mutation::DuplicateTransaction => matches!(
+				result,
+				Err(E::ValidateTransactions(
+					ValidateBlockTransactionsError::RandomStringNotUnique
+				))
+			),
+			Mutation::UntouchedBalance => matches!(
+				result,
+				Err(E::ValidateTransactions(
+					ValidateBlockTransactionsError::UntouchedBalanceState(_)
+				))
+			),
+			Mutation::WrongBalance => matches!(
+				result,
+				Err(E::ValidateTransactions(
+					ValidateBlockTransactionsError::ValidateSenderBalanceState(
+						ValidateBlockBalanceStateError::BalancesNotEquals
+					) | ValidateBlockTransactionsError::ValidateRecipientBalanceState(
+						ValidateBlockBalanceStateError::BalancesNotEquals
+					)
+				))
+			),
+			Mutation::CreatedAtInFuture => matches!(
+				result,
+				Err(E::ValidateCreatedAt(
+					ValidateBlockCreatedAtError::InFuture
+				))
+			),
+			Mutation::CreatedAtEqualsPrevious => matches!(
+				result,
+				Err(E::ValidateCreatedAt(
+					ValidateBlockCreatedAtError::PreviousInFuture
+				))
+			),
+			Mutation::MerkleRoot => matches!(
+				result,
+				Err(E::ValidateTransactions(
+					ValidateBlockTransactionsError::MerkleRootNotEquals
+				))
+			),
+		}
+	}
+
+	proptest::proptest! {
+		#![proptest_config(crate::test_helpers::create_test_proptest_config(32))]
+
+		#[test]
+		fn test_validate_mined_block(transactions in transactions()) {
+			let _lock = crate::test_helpers::lock_mining();
+			let (_miner, block, before) = mine_test_block(&transactions);
+			proptest::prop_assert!(block.validate_integrity(&before).is_ok());
+		}
+
+		#[test]
+		fn test_validate_mutated_block(
+			transactions in transactions(),
+			mutation in mutation(),
+		) {
+			let _lock = crate::test_helpers::lock_mining();
+			let (miner, mut block, before) = mine_test_block(&transactions);
+			let full = transactions.len()
+				== crate::consts::USER_TRANSACTIONS_PER_BLOCK as usize;
+			apply_mutation(mutation, &miner, &mut block, &before);
+			proptest::prop_assert!(
+				is_expected_error(
+					mutation,
+					full,
+					&block.validate_integrity(&before)
+				),
+				"{:?} is not rejected as expected",
+				mutation
+			);
+		}
 	}
 }
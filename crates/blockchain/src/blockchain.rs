@@ -1,11 +1,16 @@
 use crate::error::{
-	AddBlockError, AddBlockToDatabaseError, AddToBalanceError,
+	AddBlockError, AddBlockToDatabaseError, AddBlocksError, AddToBalanceError,
 	AddTransactionError, BlockchainFromStrError, BlockchainToStringError,
 	GenerateBlockProofOfWorkError, GetBalanceError,
 	GetBalanceFromDatabaseError, GetBlockBeforeBlockError,
-	GetBlocksCountError, GetBlocksError, GetLastBlockHashError,
+	GetBlockByHashError, GetBlockByHeightError, GetBlocksCountError,
+	GetBlocksError, GetGenesisBlockError, GetGenesisBlockHashError,
+	GetLastBlockError, GetLastBlockHashError, LoadBlockchainError,
 	LoadOrCreateBlockchainError, MakeStorageTransactionError, MineBlockError,
-	MineGenesisBlockError, NewBlockchainError, RemoveFromBalanceError,
+	MineGenesisBlockError, NewBlockchainError, PruneStaleTransactionsError,
+	RecomputeBalanceStatesError, RemoveFromBalanceError, RestoreFromFileError,
+	SnapshotToFileError, TieBreakError, TryReorgError, ValidateBlockAtError,
+	ValidateStoredChainError,
 };
 
 pub static IS_MINING: std::sync::atomic::AtomicBool =
@@ -13,126 +18,390 @@ pub static IS_MINING: std::sync::atomic::AtomicBool =
 static DB_IO_LOCKED: std::sync::atomic::AtomicBool =
 	std::sync::atomic::AtomicBool::new(false);
 
-type DbPool = r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>;
+/// Runs `f` with `DB_IO_LOCKED` taken, so the databases are not replaced
+/// concurrently.
+fn with_io_lock<T, F>(f: F) -> T
+where
+	F: FnOnce() -> T,
+{
+	use std::sync::atomic::Ordering;
 
-/// Stores the state of the blockchain.
+	while DB_IO_LOCKED
+		.compare_exchange_weak(
+			false,
+			true,
+			Ordering::Acquire,
+			Ordering::Relaxed,
+		)
+		.is_err()
+	{
+		std::hint::spin_loop();
+	}
+	let rv = f();
+	DB_IO_LOCKED.store(false, Ordering::Release);
+	rv
+}
+
+/// The result of [`Blockchain::tie_break`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TieBreak {
+	/// The block does not compete with the last one.
+	NotCompeting,
+	/// The block has a lower hash than the last one, so the chain was
+	/// switched to it.
+	Won,
+	/// The block has a higher hash than the last one. It is kept as the fork
+	/// candidate, so its branch can be switched to when it grows.
+	Lost,
+}
+
+/// The result of [`Blockchain::try_reorg`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Reorg {
+	/// The branch does not have more work than the replaced blocks, so the
+	/// current chain is kept.
+	NotHeavier,
+	/// The chain was switched to the branch.
+	Switched,
+}
+
+/// Stores the state of the blockchain. The blocks are kept in the `S` store,
+/// which is the `SQLite` database by default.
 ///
-/// To load or create a `Blockchain` object, use `Self::load_or_create`.
+/// To load or create a `Blockchain` object, use `Self::load_or_create`. To use
+/// another store, use `Self::new`.
 #[derive(Clone)]
-pub struct Blockchain<'a> {
+pub struct Blockchain<'a, S = crate::store::SqliteStore> {
 	preparing_block_state:
 		crate::preparing_block_state::PreparingBlockState<'a>,
 	miner: crate::user::User,
-	db_pool: DbPool,
+	consensus: common::config::Consensus,
+	store: S,
+	/// The last block, which lost the tie-break with the last stored one.
+	fork_candidate: Option<crate::block::Block<'a>>,
+	/// Whether the pending transactions are being mined on the clone made by
+	/// [`seal`](Blockchain::seal).
+	sealed: bool,
+	/// The number of stored blocks, so it is not counted on each
+	/// [`len`](Blockchain::len). `None` if it is unknown. It is shared with
+	/// the clones, because they share the store.
+	blocks_count: std::sync::Arc<std::sync::Mutex<Option<usize>>>,
+	/// The hash of the last stored block, so it is not computed on each
+	/// [`get_last_block_hash`](Blockchain::get_last_block_hash). `None` if
+	/// it is unknown. It is shared with the clones like `blocks_count`.
+	last_block_hash: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+	/// The balances as of the last stored block, so the hot addresses are
+	/// not scanned for on each [`get_balance`](Blockchain::get_balance). It
+	/// is shared with the clones like `blocks_count`.
+	balances: std::sync::Arc<
+		std::sync::Mutex<std::collections::HashMap<String, u64>>,
+	>,
 }
 
-impl<'a> Blockchain<'a> {
-	common::accessor!(& miner -> &crate::user::User);
-
+impl Blockchain<'_> {
 	/// Loads or creates a blockchain depending on the state of the database
 	/// file in the `consts::DB_PATH` path.
-	#[tracing::instrument]
+	#[inline]
 	pub fn load_or_create(
 		miner: crate::user::User,
+		consensus: common::config::Consensus,
+	) -> Result<Self, LoadOrCreateBlockchainError> {
+		Self::load_or_create_at(miner, consensus, &crate::consts::DB_PATH)
+	}
+
+	/// Same as [`load_or_create`](Blockchain::load_or_create), but uses the
+	/// database file in the `path`.
+	#[tracing::instrument]
+	pub fn load_or_create_at(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		path: &std::path::Path,
 	) -> Result<Self, LoadOrCreateBlockchainError> {
-		let path = crate::consts::DB_PATH.as_path();
 		if path.exists() {
 			tracing::info!("Loading an existing database...");
 		} else {
 			tracing::info!("Initializing a new database...");
 		}
 
-		let pool =
-			r2d2::Pool::new(r2d2_sqlite::SqliteConnectionManager::file(path))?;
-		Ok(Self::new(miner, pool)?)
+		let store = crate::store::SqliteStore::open(path)?;
+		Ok(Self::new(miner, consensus, store)?)
+	}
+
+	/// Loads the blockchain from the existing database file in the `path`.
+	/// Unlike [`load_or_create_at`](Blockchain::load_or_create_at), a missing
+	/// database is not created, so it is for the offline tools, which must
+	/// not start a new chain.
+	#[tracing::instrument]
+	pub fn load_at(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		path: &std::path::Path,
+	) -> Result<Self, LoadBlockchainError> {
+		if !path.exists() {
+			return Err(LoadBlockchainError::NotFound);
+		}
+		let store = crate::store::SqliteStore::open(path)?;
+		let rv = Self::new(miner, consensus, store)?;
+		if rv.is_empty()? {
+			return Err(LoadBlockchainError::Empty);
+		}
+		Ok(rv)
 	}
 
 	/// Accepts a string that contains block JSONs, from which it reconstructs
-	/// the blockchain.
+	/// the blockchain in the `consts::DB_PATH` database.
 	///
-	/// Loads blocks from `s` into the temporary database at
-	/// `consts::TEMP_DB_PATH`. If the integrity of the received data is
-	/// confirmed, moves the temporary database to `consts::DB_PATH` and
-	/// returns a new `Blockchain` object.
-	#[tracing::instrument]
+	/// See [`from_str_into`](Blockchain::from_str_into).
+	#[inline]
 	pub fn from_str(
 		miner: crate::user::User,
+		consensus: common::config::Consensus,
 		s: &str,
 	) -> Result<Self, BlockchainFromStrError> {
-		use std::sync::atomic::Ordering;
+		Self::from_str_into(miner, consensus, s, &crate::consts::DB_PATH)
+	}
+
+	/// Accepts a string that contains block JSONs, from which it reconstructs
+	/// the blockchain in the database at `path`.
+	///
+	/// Loads blocks from `s` into the temporary database next to `path`, with
+	/// the `temp-` prefix. If the integrity of the received data is
+	/// confirmed, moves the temporary database to `path` and returns a new
+	/// `Blockchain` object.
+	///
+	/// The blocks are inserted via [`add_blocks`](Blockchain::add_blocks),
+	/// so the temporary database is committed only once.
+	#[inline]
+	pub fn from_str_into(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		s: &str,
+		path: &std::path::Path,
+	) -> Result<Self, BlockchainFromStrError> {
+		Self::from_str_into_with_progress(miner, consensus, s, path, None)
+	}
 
+	/// Same as [`from_str_into`](Blockchain::from_str_into), but reports the
+	/// number of added blocks to the `progress`.
+	#[tracing::instrument(skip(progress))]
+	pub fn from_str_into_with_progress(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		s: &str,
+		path: &std::path::Path,
+		progress: Option<&mut crate::progress::Progress<'_>>,
+	) -> Result<Self, BlockchainFromStrError> {
 		// Convert block JSONs into objects
 		let blocks: Vec<crate::block::Block> = serde_json::from_str(s)?;
-		// Take IO lock
-		while DB_IO_LOCKED
-			.compare_exchange_weak(
-				false,
-				true,
-				Ordering::Acquire,
-				Ordering::Relaxed,
-			)
-			.is_err()
-		{
-			std::hint::spin_loop();
+		with_io_lock(|| {
+			Self::import(&miner, &consensus, path, |temp| {
+				Ok(temp.add_blocks(&blocks, progress)?)
+			})
+		})?;
+
+		let rv = Self::load_or_create_at(miner, consensus, path)?;
+		Ok(rv)
+	}
+
+	/// Restores the blockchain in the `consts::DB_PATH` database from the
+	/// snapshot file at `snapshot_path`.
+	///
+	/// See [`restore_from_file_into`](Blockchain::restore_from_file_into).
+	#[inline]
+	pub fn restore_from_file(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		snapshot_path: &std::path::Path,
+	) -> Result<Self, RestoreFromFileError> {
+		Self::restore_from_file_into(
+			miner,
+			consensus,
+			snapshot_path,
+			&crate::consts::DB_PATH,
+		)
+	}
+
+	/// Restores the blockchain in the database at `path` from the snapshot
+	/// file at `snapshot_path`, made with
+	/// [`snapshot_to_file`](Blockchain::snapshot_to_file).
+	///
+	/// **The restoring is trust-based.** The checksum only detects a damaged
+	/// file, and the blocks are not validated, so restore only the snapshots
+	/// from a trusted operator. The chain can be checked later with
+	/// [`validate_stored_chain`](Blockchain::validate_stored_chain).
+	#[tracing::instrument]
+	pub fn restore_from_file_into(
+		miner: crate::user::User,
+		consensus: common::config::Consensus,
+		snapshot_path: &std::path::Path,
+		path: &std::path::Path,
+	) -> Result<Self, RestoreFromFileError> {
+		use {crate::store::BlockStore as _, sha2::Digest as _};
+
+		let bytes = std::fs::read(snapshot_path)
+			.map_err(RestoreFromFileError::Read)?;
+		let payload = bytes
+			.strip_prefix(crate::consts::SNAPSHOT_MAGIC)
+			.ok_or(RestoreFromFileError::InvalidMagic)?;
+		if payload.len() < crate::consts::SNAPSHOT_CHECKSUM_LEN {
+			return Err(RestoreFromFileError::ChecksumNotEquals);
+		}
+		let (checksum, payload) =
+			payload.split_at(crate::consts::SNAPSHOT_CHECKSUM_LEN);
+		if sha2::Sha256::digest(payload).as_slice() != checksum {
+			return Err(RestoreFromFileError::ChecksumNotEquals);
 		}
-		// Creating an empty blockchain with a temporary database
-		let path = crate::consts::TEMP_DB_PATH.as_path();
-		let pool =
-			r2d2::Pool::new(r2d2_sqlite::SqliteConnectionManager::file(path))?;
-		let mut rv = Self::new(miner.clone(), pool)?;
+		let blocks: Vec<crate::block::Block> = bincode::deserialize(payload)?;
+
+		// Insert the blocks as they are, without validation
+		with_io_lock(|| {
+			Self::import(&miner, &consensus, path, |temp| {
+				temp.store.execute_batch(crate::consts::DB_BEGIN_QUERY)?;
+				for block in &blocks {
+					temp.store.insert_block(
+						block.compute_hash()?,
+						serde_json::to_string(block)?,
+					)?;
+				}
+				temp.store.execute_batch(crate::consts::DB_COMMIT_QUERY)?;
+				Ok(())
+			})
+		})?;
+
+		let rv = Self::load_or_create_at(miner, consensus, path)?;
+		Ok(rv)
+	}
+
+	/// Fills a temporary database next to `path` with `fill` and moves it to
+	/// `path`. Hold `DB_IO_LOCKED` while using it.
+	fn import<F>(
+		miner: &crate::user::User,
+		consensus: &common::config::Consensus,
+		path: &std::path::Path,
+		fill: F,
+	) -> Result<(), BlockchainFromStrError>
+	where
+		F: FnOnce(&mut Self) -> Result<(), BlockchainFromStrError>,
+	{
+		let mut temp_name = std::ffi::OsString::from("temp-");
+		temp_name.push(path.file_name().unwrap_or_default());
+		let temp_path = path.with_file_name(temp_name);
+
+		// Creating an empty blockchain with a temporary database. The pool
+		// has one connection for `Self::add_blocks`
+		let pool = r2d2::Pool::builder()
+			.max_size(1)
+			.build(r2d2_sqlite::SqliteConnectionManager::file(&temp_path))?;
+		let store = crate::store::SqliteStore::new(pool)?;
+		let mut temp = Self::new(miner.clone(), consensus.clone(), store)?;
 		// Transferring all blocks to the new blockchain
+		if let Err(e) = fill(&mut temp) {
+			drop(temp);
+			std::fs::remove_file(&temp_path)
+				.map_err(BlockchainFromStrError::RemoveTempDb)?;
+			return Err(e)?;
+		}
+		drop(temp);
+		// Replacing the database with the temporary one
+		std::fs::rename(&temp_path, path)
+			.map_err(BlockchainFromStrError::ReplaceDb)?;
+		Ok(())
+	}
+
+	/// Adds the `blocks` to an empty blockchain, starting from the genesis
+	/// one, within a single database transaction. If any block can not be
+	/// added, the transaction is rolled back.
+	///
+	/// The validation of each block reads the previous uncommitted ones, so
+	/// the database pool of `self.store` must have only one connection.
+	///
+	/// The number of added blocks is reported to the `progress`.
+	#[tracing::instrument(skip(self, blocks, progress))]
+	pub(crate) fn add_blocks(
+		&mut self,
+		blocks: &[crate::block::Block],
+		mut progress: Option<&mut crate::progress::Progress<'_>>,
+	) -> Result<(), AddBlocksError> {
+		self.store
+			.execute_batch(crate::consts::DB_BEGIN_QUERY)
+			.map_err(AddBlocksError::BeginDbTransaction)?;
 		for (i, block) in blocks.iter().enumerate() {
-			if let Err(e) = rv.add_block(block, i == 0) {
-				std::fs::remove_file(path)
-					.map_err(BlockchainFromStrError::RemoveTempDb)?;
+			if let Err(e) = self.add_block(block, i == 0) {
+				// The rolled back blocks were counted and hashed
+				*self.lock_blocks_count() = None;
+				*self.lock_last_block_hash() = None;
+				self.lock_balances().clear();
+				// The failed block is the cause, so it is returned anyway
+				if let Err(rollback) =
+					self.store.execute_batch(crate::consts::DB_ROLLBACK_QUERY)
+				{
+					tracing::error!(
+						"Failed to rollback a db transaction: {rollback:?}"
+					);
+				}
 				return Err(e)?;
 			}
+			if let Some(p) = progress.as_deref_mut() {
+				p.report(i + 1, blocks.len());
+			}
 		}
-		// Replacing the temporary database with the current one
-		std::fs::rename(path, crate::consts::DB_PATH.as_path())
-			.map_err(BlockchainFromStrError::ReplaceDb)?;
-		// Drop IO lock
-		DB_IO_LOCKED.store(false, Ordering::Release);
-
-		let rv = Self::load_or_create(miner)?;
-		Ok(rv)
+		self.store
+			.execute_batch(crate::consts::DB_COMMIT_QUERY)
+			.map_err(AddBlocksError::CommitDbTransaction)?;
+		Ok(())
 	}
+}
+
+impl<'a, S> Blockchain<'a, S>
+where
+	S: crate::store::BlockStore,
+{
+	common::accessor!(& miner -> &crate::user::User);
+
+	common::accessor!(& consensus -> &common::config::Consensus);
 
-	#[tracing::instrument(skip(db_pool))]
-	fn new(
+	common::accessor!(as_ref fork_candidate -> Option<&crate::block::Block<'a>>);
+
+	/// Creates a blockchain with the blocks of the `store`.
+	#[tracing::instrument(skip(store))]
+	pub fn new(
 		miner: crate::user::User,
-		db_pool: DbPool,
+		consensus: common::config::Consensus,
+		store: S,
 	) -> Result<Self, NewBlockchainError> {
-		db_pool
-			.get()?
-			.execute(crate::consts::DB_CREATE_TABLE_IF_NOT_EXISTS_QUERY, [])?;
+		if consensus.min_user_transactions_per_block()
+			> crate::consts::USER_TRANSACTIONS_PER_BLOCK
+		{
+			return Err(NewBlockchainError::MinUserTransactionsPerBlockTooBig);
+		} else if let Some(treasury) = consensus.treasury() {
+			// Otherwise each block would fail to be mined
+			crate::address::Address::parse(treasury)
+				.map_err(NewBlockchainError::ParseTreasury)?;
+		}
 		let preparing_block_state =
 			crate::preparing_block_state::PreparingBlockState::new();
-		Ok(Self { preparing_block_state, miner, db_pool })
+		Ok(Self {
+			preparing_block_state,
+			miner,
+			consensus,
+			store,
+			fork_candidate: None,
+			sealed: false,
+			blocks_count: std::sync::Arc::default(),
+			last_block_hash: std::sync::Arc::default(),
+			balances: std::sync::Arc::default(),
+		})
 	}
 
 	#[tracing::instrument(level = tracing::Level::DEBUG, ret, skip(self))]
 	pub fn get_block_before_block(
 		&self,
 		before_block: &crate::block::Block,
-	) -> Result<Option<crate::block::Block>, GetBlockBeforeBlockError> {
-		// Getting connection and results with jsons
-		let connection = self.db_pool.get()?;
-		let mut statement = connection
-			.prepare(crate::consts::DB_GET_ALL_QUERY)
-			.map_err(GetBlockBeforeBlockError::PrepareDbStatement)?;
-		let json_results = statement
-			.query_map([], |row| row.get(0))
-			.map_err(GetBlockBeforeBlockError::QueryDb)?;
-		// Unwrap results and find previous block
-		for json_result in json_results {
-			let json: String = json_result
-				.map_err(GetBlockBeforeBlockError::UnwrapDbResult)?;
+	) -> Result<Option<crate::block::Block<'_>>, GetBlockBeforeBlockError> {
+		for json in self.store.get_all()? {
 			let block: crate::block::Block = serde_json::from_str(&json)?;
 			let block_hash = block.compute_hash()?;
-			if before_block
-				.previous_hash()
-				.map_or(false, |ph| ph == block_hash)
+			if before_block.previous_hash().is_some_and(|ph| ph == block_hash)
 			{
 				return Ok(Some(block));
 			}
@@ -140,6 +409,171 @@ impl<'a> Blockchain<'a> {
 		Ok(None)
 	}
 
+	/// Validates the stored blocks without their transactions: blocks must be
+	/// linked by hashes, belong to the consensus network, have a proof of work
+	/// (if the consensus requires it) and be signed by their miners.
+	///
+	/// Use it to make sure that the database is not corrupted.
+	#[inline]
+	pub fn validate_stored_chain(
+		&self,
+	) -> Result<(), ValidateStoredChainError> {
+		self.validate_stored_chain_with_progress(None)
+	}
+
+	/// Same as [`validate_stored_chain`](Blockchain::validate_stored_chain),
+	/// but reports the number of validated blocks to the `progress`.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self, progress))]
+	pub fn validate_stored_chain_with_progress(
+		&self,
+		mut progress: Option<&mut crate::progress::Progress<'_>>,
+	) -> Result<(), ValidateStoredChainError> {
+		let mut previous_hash: Option<String> = None;
+		let blocks = self.get_blocks(None)?;
+		let total = blocks.len();
+		for (i, block) in blocks.into_iter().enumerate() {
+			if block.previous_hash() != previous_hash.as_deref() {
+				return Err(ValidateStoredChainError::NotLinked);
+			}
+			block.validate_chain_id(self)?;
+			if self.consensus.require_proof_of_work() {
+				block.validate_proof_of_work()?;
+			}
+			block.validate_is_signed()?;
+			block.validate_miner_signature()?;
+			previous_hash = Some(block.compute_hash()?);
+			if let Some(p) = progress.as_deref_mut() {
+				p.report(i + 1, total);
+			}
+		}
+		Ok(())
+	}
+
+	/// Validates the stored block at the `index` against the stored block
+	/// before it, not against the last block, so a block in the middle of
+	/// the chain can be audited in place. The genesis block is validated
+	/// with [`validate_genesis`](crate::block::Block::validate_genesis).
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn validate_block_at(
+		&self,
+		index: usize,
+	) -> Result<(), ValidateBlockAtError> {
+		let block = self
+			.get_block_by_height(index)?
+			.ok_or(ValidateBlockAtError::NotFound(index))?;
+		match index.checked_sub(1) {
+			Some(i) => {
+				let previous = self
+					.get_block_by_height(i)?
+					.ok_or(ValidateBlockAtError::NotFound(i))?;
+				block.validate_integrity_at(
+					self,
+					index,
+					&previous.compute_hash()?,
+				)?;
+			}
+			None => block.validate_genesis(self)?,
+		}
+		Ok(())
+	}
+
+	/// Recomputes the balance state of each stored block from its
+	/// transactions and the balances before it, and reports the stored
+	/// balances which differ. The balances of the genesis block are compared
+	/// with [`Self::genesis_balance_state`]. The blocks with balance deltas
+	/// have no balance state to recompute.
+	///
+	/// If `rewrite` is `true`, the stored balance states are replaced with
+	/// the recomputed ones. It is a migration: the hashes of the rewritten
+	/// blocks change, so the next blocks no longer link to them and the chain
+	/// has to be mined again to be valid for other nodes.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn recompute_balance_states(
+		&self,
+		rewrite: bool,
+	) -> Result<
+		Vec<crate::block::BalanceStateDiscrepancy>,
+		RecomputeBalanceStatesError,
+	> {
+		let mut rv = Vec::new();
+		let mut balances = crate::helpers::BalanceState::new();
+		for (height, mut block) in
+			self.get_blocks(None)?.into_iter().enumerate()
+		{
+			let genesis_balance_state =
+				self.genesis_balance_state(block.miner());
+			let mut computed_state = crate::helpers::BalanceState::new();
+			for (address, stored) in block.balance_state() {
+				let computed = if height == 0 {
+					genesis_balance_state.get(address).copied().unwrap_or(0)
+				} else {
+					let (spent, received) = block
+						.balance_change(address)
+						.ok_or(RecomputeBalanceStatesError::AddOverflow)?;
+					balances
+						.get(address)
+						.copied()
+						.unwrap_or(0)
+						.checked_add(received)
+						.ok_or(RecomputeBalanceStatesError::AddOverflow)?
+						.checked_sub(spent)
+						.ok_or(RecomputeBalanceStatesError::SubOverflow)?
+				};
+				if computed != *stored {
+					rv.push(crate::block::BalanceStateDiscrepancy::new(
+						height,
+						address.clone(),
+						*stored,
+						computed,
+					));
+				}
+				computed_state.insert(address.clone(), computed);
+			}
+
+			// Replace the balance state if it differs
+			let differs = computed_state != *block.balance_state();
+			balances
+				.extend(computed_state.iter().map(|(a, b)| (a.clone(), *b)));
+			if rewrite && differs {
+				block.replace_balance_state(computed_state);
+				self.replace_stored_block(
+					height,
+					block.compute_hash()?,
+					serde_json::to_string(&block)?,
+				)?;
+				tracing::warn!(
+					"Balance state of block {height} is rewritten."
+				);
+			}
+		}
+		Ok(rv)
+	}
+
+	/// Dumps all blocks into the compact binary snapshot file at `path`, from
+	/// which the blockchain is restored with
+	/// [`restore_from_file`](Blockchain::restore_from_file).
+	///
+	/// The file consists of `consts::SNAPSHOT_MAGIC`, the SHA-256 checksum of
+	/// the payload and the payload, which is the blocks encoded with
+	/// `bincode`.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn snapshot_to_file(
+		&self,
+		path: &std::path::Path,
+	) -> Result<(), SnapshotToFileError> {
+		use sha2::Digest as _;
+
+		let payload = bincode::serialize(&self.get_blocks(None)?)?;
+		let checksum = sha2::Sha256::digest(&payload);
+		std::fs::write(
+			path,
+			[crate::consts::SNAPSHOT_MAGIC, checksum.as_slice(), &payload]
+				.concat(),
+		)
+		.map_err(SnapshotToFileError::Write)?;
+		Ok(())
+	}
+
 	/// Gets a list of all blocks and dumps them into JSONs string format.
 	pub fn to_string(&self) -> Result<String, BlockchainToStringError> {
 		let blocks = self.get_blocks(None)?;
@@ -147,18 +581,187 @@ impl<'a> Blockchain<'a> {
 		Ok(rv)
 	}
 
-	///  Gets the hash of the last block from the database.
+	///  Gets the hash of the last block from the store. It is computed
+	/// only if `self.last_block_hash` is unknown.
 	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
 	pub fn get_last_block_hash(
 		&self,
 	) -> Result<String, GetLastBlockHashError> {
-		let json: String = self.db_pool.get()?.query_row(
-			crate::consts::DB_GET_LAST_QUERY,
-			[],
-			|row| row.get(0),
-		)?;
+		let mut cached = self.lock_last_block_hash();
+		if let Some(hash) = cached.as_ref() {
+			return Ok(hash.clone());
+		}
+		let json =
+			self.store.get_last()?.ok_or(GetLastBlockHashError::Empty)?;
 		let hash = serde_json::from_str::<crate::block::Block>(&json)?
 			.compute_hash()?;
+		*cached = Some(hash.clone());
+		Ok(hash)
+	}
+
+	/// Gets the block with the `hash` from the store or `None` if there is
+	/// no such block.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_block_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<crate::block::Block<'_>>, GetBlockByHashError> {
+		let json = self.store.get_by_hash(hash)?;
+		Ok(json.map(|j| serde_json::from_str(&j)).transpose()?)
+	}
+
+	/// Gets the block at `height` from the store or `None` if there is no
+	/// such block. The height of the genesis block is zero.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_block_by_height(
+		&self,
+		height: usize,
+	) -> Result<Option<crate::block::Block<'_>>, GetBlockByHeightError> {
+		let json = self.store.get_by_height(height)?;
+		Ok(json.map(|j| serde_json::from_str(&j)).transpose()?)
+	}
+
+	/// Gets the last block from the store or `None` if it is empty.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_last_block(
+		&self,
+	) -> Result<Option<crate::block::Block<'_>>, GetLastBlockError> {
+		let json = self.store.get_last()?;
+		Ok(json.map(|j| serde_json::from_str(&j)).transpose()?)
+	}
+
+	/// Collects every address which appears in the balance state or deltas
+	/// of any block. The storage address is excluded.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn all_known_addresses(
+		&self,
+	) -> Result<std::collections::BTreeSet<String>, GetBlocksError> {
+		let mut rv = std::collections::BTreeSet::new();
+		for block in self.get_blocks(None)? {
+			rv.extend(
+				block
+					.balance_state()
+					.keys()
+					.chain(block.balance_deltas().keys())
+					.filter(|a| *a != crate::consts::STORAGE_ADDRESS)
+					.cloned(),
+			);
+		}
+		Ok(rv)
+	}
+
+	/// Collects the latest confirmed balance of every address, including the
+	/// storage one. The pending transactions are not taken into account.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn snapshot_balances(
+		&self,
+	) -> Result<crate::helpers::BalanceState, GetBlocksError> {
+		use std::convert::TryFrom as _;
+
+		let mut rv = crate::helpers::BalanceState::new();
+		for block in self.get_blocks(None)? {
+			rv.extend(
+				block.balance_state().iter().map(|(a, b)| (a.clone(), *b)),
+			);
+			// The deltas are validated, so the balances stay in range
+			for (address, delta) in block.balance_deltas() {
+				let balance = rv.entry(address.clone()).or_insert(0);
+				*balance = u64::try_from(i128::from(*balance) + delta)
+					.unwrap_or_default();
+			}
+		}
+		Ok(rv)
+	}
+
+	/// Gets the stats of the last `n` blocks in chronological order. The
+	/// height of the genesis block is zero.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn recent_block_stats(
+		&self,
+		n: usize,
+	) -> Result<Vec<crate::block::BlockStat>, GetBlocksError> {
+		let mut rv = Vec::new();
+		for (height, json) in self.store.get_last_n(n)? {
+			let block: crate::block::Block = serde_json::from_str(&json)?;
+			rv.push(crate::block::BlockStat::new(height, &block));
+		}
+		rv.reverse();
+		Ok(rv)
+	}
+
+	/// Gets the hashes of the last `n` blocks, starting from the last one. A
+	/// syncing node can send them as a locator, so the serving node finds the
+	/// common block without transferring the whole chain.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn last_n_block_hashes(
+		&self,
+		n: usize,
+	) -> Result<Vec<String>, GetBlocksError> {
+		let mut rv = Vec::new();
+		for (_, json) in self.store.get_last_n(n)? {
+			let block: crate::block::Block = serde_json::from_str(&json)?;
+			rv.push(block.compute_hash()?);
+		}
+		Ok(rv)
+	}
+
+	/// Gets the blocks after the one with the `hash`, so a node, which is
+	/// slightly behind, gets only the blocks it lacks. The result is empty if
+	/// the `hash` is of the last block and `None` if there is no such block.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_blocks_since_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<Vec<crate::block::Block<'_>>>, GetBlocksError> {
+		let mut blocks = self.get_blocks(None)?;
+		for (i, block) in blocks.iter().enumerate() {
+			if block.compute_hash()? == hash {
+				return Ok(Some(blocks.split_off(i + 1)));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Finds the block, which contains the transaction with `random_string`,
+	/// and makes the proof of its inclusion. Returns `None` if there is no
+	/// such transaction in the stored blocks.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn inclusion_proof(
+		&self,
+		random_string: &str,
+	) -> Result<Option<crate::block::InclusionProof<'_>>, GetBlocksError> {
+		for (height, block) in self.get_blocks(None)?.into_iter().enumerate() {
+			if block
+				.transactions()
+				.iter()
+				.any(|t| t.random_string() == random_string)
+			{
+				return Ok(Some(crate::block::InclusionProof::new(
+					height, block,
+				)?));
+			}
+		}
+		Ok(None)
+	}
+
+	/// Gets the first block from the store.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_genesis_block(
+		&self,
+	) -> Result<crate::block::Block<'_>, GetGenesisBlockError> {
+		let json =
+			self.store.get_first()?.ok_or(GetGenesisBlockError::Empty)?;
+		let block = serde_json::from_str(&json)?;
+		Ok(block)
+	}
+
+	/// Gets the hash of the genesis block. Two blockchains with different
+	/// genesis hashes can not be the same chain.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn get_genesis_block_hash(
+		&self,
+	) -> Result<String, GetGenesisBlockHashError> {
+		let hash = self.get_genesis_block()?.compute_hash()?;
 		Ok(hash)
 	}
 
@@ -179,24 +782,69 @@ impl<'a> Blockchain<'a> {
 	/// `IS_MINING`. This can be done, for example, in another
 	/// thread.
 	///
+	/// # Errors
+	///
+	/// `MineBlockError::StorageInsolvent` if the storage can not pay the
+	/// mining reward. In this case the pending transactions are kept.
+	///
+	/// `MineBlockError::NoPendingTransactions` if all pending transactions
+	/// are stale, see
+	/// [`prune_stale_transactions`](Blockchain::prune_stale_transactions).
+	///
+	/// `MineBlockError::GenerateBlockProofOfWork` if the mining is stopped.
+	/// In this case the pending transactions are kept, so it can be retried.
+	///
 	/// # Panics
 	///
 	/// If `IS_MINING` is `true` or there is no pending transactions.
 	#[tracing::instrument(skip(self))]
 	pub fn mine_block(
 		&mut self,
-	) -> Result<crate::block::Block, MineBlockError> {
+	) -> Result<crate::block::Block<'a>, MineBlockError> {
 		use std::sync::atomic::Ordering;
 
 		debug_assert!(!self.is_empty()?, "Mine the genesis block first.");
 		debug_assert!(!self.preparing_block_state.transactions.is_empty());
 		assert!(!IS_MINING.load(Ordering::Acquire));
 
+		// The stale transactions would fail the validation of the block
+		self.prune_stale_transactions()?;
+		if self.preparing_block_state.transactions.is_empty() {
+			return Err(MineBlockError::NoPendingTransactions);
+		}
+
+		// Check that the storage can pay the reward
+		if self.get_balance(crate::consts::STORAGE_ADDRESS)?
+			< self.consensus.mining_reward()
+		{
+			return Err(MineBlockError::StorageInsolvent);
+		}
+
+		// Restored if the mining is stopped
+		let pending = self.preparing_block_state.clone();
+
 		// To avoid immutable and mutable accesses in one moment
-		self.make_storage_transaction(
-			self.miner.address().to_owned(),
-			crate::consts::MINING_REWARD,
-		)?;
+		let rewards = self
+			.mining_rewards(self.miner.address().as_str())
+			.into_iter()
+			.map(|(r, a)| Ok((crate::address::Address::parse(r)?, a)))
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(MineBlockError::ParseRewardRecipient)?;
+		for (recipient, amount) in rewards {
+			self.make_storage_transaction(recipient, amount)?;
+		}
+		// The fees of the user transactions are paid to the miner
+		let fees = self
+			.preparing_block_state
+			.transactions
+			.iter()
+			.try_fold(0_u64, |sum, t| sum.checked_add(t.fee()))
+			.ok_or(MineBlockError::FeesOverflow)?;
+		if let Some(fees) = std::num::NonZeroU64::new(fees) {
+			let miner = self.miner.address().to_string();
+			self.add_to_balance(&miner, fees)
+				.map_err(MineBlockError::AddFeesToMinerBalance)?;
+		}
 
 		// Getting the necessary fields
 		let transactions =
@@ -207,20 +855,27 @@ impl<'a> Blockchain<'a> {
 
 		// Creating the base of the block and mine
 		let mut block = crate::block::Block::new(
-			self.miner.address().to_owned(),
+			self.miner.address().to_string(),
 			Some(previous_hash),
 			transactions,
 			balance_state,
+			self.consensus.chain_id(),
 		);
-		IS_MINING.store(true, Ordering::SeqCst);
-		if let Err(e) = block.generate_proof_of_work() {
-			// Because can't to implement `PartialEq` for `Error`
-			if matches!(e, GenerateBlockProofOfWorkError::Stopped) {
-				tracing::info!("Mining has been stopped.");
+		if self.consensus.balance_state_deltas() {
+			block.use_balance_deltas();
+		}
+		if self.consensus.require_proof_of_work() {
+			IS_MINING.store(true, Ordering::SeqCst);
+			if let Err(e) = block.generate_proof_of_work() {
+				// Because can't to implement `PartialEq` for `Error`
+				if matches!(e, GenerateBlockProofOfWorkError::Stopped) {
+					tracing::info!("Mining has been stopped.");
+					self.preparing_block_state = pending;
+				}
+				return Err(e)?;
 			}
-			return Err(e)?;
+			IS_MINING.store(false, Ordering::SeqCst);
 		}
-		IS_MINING.store(false, Ordering::SeqCst);
 
 		// Signing and adding a block
 		block.sign(&self.miner)?;
@@ -230,9 +885,24 @@ impl<'a> Blockchain<'a> {
 		Ok(block)
 	}
 
-	/// Mines a genesis block by setting the initial balance to the
-	/// miner in `consts::GENESIS_BLOCK_REWARD`, and the storage in
-	/// `consts::STORAGE_START_BALANCE`.
+	/// Same as [`mine_block`](Blockchain::mine_block), but takes the
+	/// blockchain and returns it with the new block instead of changing it in
+	/// place. It is pure in the sense that it knows nothing about the network,
+	/// so it is up to the caller to spread the block.
+	///
+	/// # Panics
+	///
+	/// Same as [`mine_block`](Blockchain::mine_block).
+	#[inline]
+	pub fn mine_pending(
+		mut self,
+	) -> Result<(crate::block::Block<'a>, Self), MineBlockError> {
+		let block = self.mine_block()?;
+		Ok((block, self))
+	}
+
+	/// Mines a genesis block with the
+	/// [`genesis_balance_state`](Blockchain::genesis_balance_state).
 	///
 	/// # Panics
 	///
@@ -240,33 +910,28 @@ impl<'a> Blockchain<'a> {
 	#[tracing::instrument(skip(self))]
 	pub fn mine_genesis_block(
 		&mut self,
-	) -> Result<crate::block::Block, MineGenesisBlockError> {
+	) -> Result<crate::block::Block<'_>, MineGenesisBlockError> {
 		use std::sync::atomic::Ordering;
 
 		debug_assert!(self.is_empty()?);
 		assert!(!IS_MINING.load(Ordering::Acquire));
 
-		// Creating the base balance state
-		let mut state = crate::helpers::BalanceState::new();
-		state.insert(
-			self.miner.address().to_owned(),
-			crate::consts::GENESIS_BLOCK_REWARD,
-		);
-		state.insert(
-			crate::consts::STORAGE_ADDRESS.to_owned(),
-			crate::consts::STORAGE_START_BALANCE,
-		);
-
 		// Create, mine, sign and add the block
 		let mut block = crate::block::Block::new(
-			self.miner.address().to_owned(),
+			self.miner.address().to_string(),
 			None::<&str>,
 			crate::block::Transactions::new(),
-			state,
+			self.genesis_balance_state(self.miner.address().as_str()),
+			self.consensus.chain_id(),
 		);
-		IS_MINING.store(true, Ordering::SeqCst);
-		block.generate_proof_of_work()?;
-		IS_MINING.store(false, Ordering::SeqCst);
+		if self.consensus.balance_state_deltas() {
+			block.use_balance_deltas();
+		}
+		if self.consensus.require_proof_of_work() {
+			IS_MINING.store(true, Ordering::SeqCst);
+			block.generate_proof_of_work()?;
+			IS_MINING.store(false, Ordering::SeqCst);
+		}
 
 		// Signing and adding a block
 		block.sign(&self.miner)?;
@@ -276,140 +941,564 @@ impl<'a> Blockchain<'a> {
 		Ok(block)
 	}
 
-	#[inline]
+	/// The balance state of the genesis block mined by the `miner`. It gives
+	/// `consensus.genesis_block_reward()` to the miner and
+	/// `consensus.storage_start_balance()` to the storage.
 	#[must_use]
-	pub fn minable(&self) -> bool {
-		self.preparing_block_state.filled()
+	pub fn genesis_balance_state(
+		&self,
+		miner: &str,
+	) -> crate::helpers::BalanceState {
+		let mut rv = crate::helpers::BalanceState::new();
+		rv.insert(miner.to_owned(), self.consensus.genesis_block_reward());
+		rv.insert(
+			crate::consts::STORAGE_ADDRESS.to_owned(),
+			self.consensus.storage_start_balance(),
+		);
+		rv
 	}
 
-	/// Checks the integrity of the block and enters it into the database.
-	#[tracing::instrument(skip(self))]
-	pub fn add_block(
-		&mut self,
-		block: &crate::block::Block,
-		is_genesis: bool,
-	) -> Result<(), AddBlockError> {
-		// Remove state
-		self.preparing_block_state.clear();
-
-		// Stop mining
-		if IS_MINING.load(std::sync::atomic::Ordering::Relaxed) {
-			IS_MINING.store(false, std::sync::atomic::Ordering::Relaxed);
+	/// Counts the pending user transactions by their fee, which decides what
+	/// is replaced first when the block is full. The buckets are sorted by
+	/// fee.
+	#[must_use]
+	pub fn mempool_fee_histogram(&self) -> Vec<(u64, usize)> {
+		let mut rv = std::collections::BTreeMap::new();
+		for transaction in &self.preparing_block_state.transactions {
+			if !transaction.sender().is_storage() {
+				*rv.entry(transaction.fee()).or_insert(0) += 1;
+			}
 		}
+		rv.into_iter().collect()
+	}
 
-		if is_genesis {
-			debug_assert!(self.is_empty()?);
-		} else {
-			block.validate_integrity(self)?;
+	/// The storage transactions of a block mined by the `miner`, in their
+	/// order: the miner and the treasury shares of
+	/// [`Consensus::split_mining_reward`](common::config::Consensus::split_mining_reward).
+	/// Zero shares are not paid.
+	#[must_use]
+	pub(crate) fn mining_rewards<'m>(
+		&'m self,
+		miner: &'m str,
+	) -> Vec<(&'m str, std::num::NonZeroU64)> {
+		let (miner_share, treasury_share) =
+			self.consensus.split_mining_reward();
+		let mut rv = Vec::new();
+		if let Some(a) = std::num::NonZeroU64::new(miner_share) {
+			rv.push((miner, a));
 		}
-		self.add_block_to_database(block)?;
-		Ok(())
+		if let (Some(t), Some(a)) = (
+			self.consensus.treasury(),
+			std::num::NonZeroU64::new(treasury_share),
+		) {
+			rv.push((t, a));
+		}
+		rv
 	}
 
-	/// Adds a new pending transaction to `self.preparing_block_state`.
-	#[tracing::instrument(skip(self))]
-	pub fn add_transaction(
-		&mut self,
+	/// Whether the pending transactions fill a block. They are not minable
+	/// again while they are being mined on a [`seal`](Blockchain::seal)ed
+	/// clone.
+	#[inline]
+	#[must_use]
+	pub fn minable(&self) -> bool {
+		!self.sealed && self.preparing_block_state.filled()
+	}
+
+	/// Whether the pending transactions are enough to mine a partial block,
+	/// which has at least `self.consensus.min_user_transactions_per_block()`
+	/// user transactions.
+	#[inline]
+	#[must_use]
+	pub fn partially_minable(&self) -> bool {
+		!self.sealed
+			&& self.preparing_block_state.transactions.len()
+				>= self.consensus.min_user_transactions_per_block() as usize
+	}
+
+	common::accessor!(copy sealed -> bool);
+
+	/// The transactions which go into the next block.
+	#[inline]
+	#[must_use]
+	pub fn pending_transactions(
+		&self,
+	) -> &[crate::transaction::Transaction<'a>] {
+		&self.preparing_block_state.transactions
+	}
+
+	/// Makes a clone to mine the pending transactions on, so `self` keeps
+	/// serving meanwhile. Until the mined clone replaces `self`, or
+	/// [`unseal`](Blockchain::unseal) is called if the mining fails, `self`
+	/// rejects new transactions: they would point to the last block, which
+	/// is replaced, so they would be lost with `self`.
+	#[must_use = "Mine the pending transactions on the clone."]
+	pub fn seal(&mut self) -> Self {
+		let rv = self.clone();
+		self.sealed = true;
+		rv
+	}
+
+	/// Accepts new transactions again after [`seal`](Blockchain::seal). The
+	/// pending transactions are kept.
+	#[inline]
+	pub fn unseal(&mut self) {
+		self.sealed = false;
+	}
+
+	/// Whether a block should be mined: it is full, or it is partial and the
+	/// oldest pending transaction has waited for the `grace`, so the pending
+	/// transactions are not stuck in quiet periods. A zero `grace` disables
+	/// the partial blocks.
+	#[must_use]
+	pub fn minable_with_grace(&self, grace: std::time::Duration) -> bool {
+		self.minable()
+			|| (!grace.is_zero()
+				&& self.partially_minable()
+				&& self
+					.preparing_block_state
+					.pending_since
+					.is_some_and(|t| t.elapsed() >= grace))
+	}
+
+	/// Checks the integrity of the block and enters it into the database.
+	#[tracing::instrument(skip(self))]
+	pub fn add_block(
+		&mut self,
+		block: &crate::block::Block,
+		is_genesis: bool,
+	) -> Result<(), AddBlockError> {
+		// Remove state. The sealed transactions are removed too
+		self.preparing_block_state.clear();
+		self.sealed = false;
+
+		// Stop mining
+		if IS_MINING.load(std::sync::atomic::Ordering::Relaxed) {
+			IS_MINING.store(false, std::sync::atomic::Ordering::Relaxed);
+		}
+
+		self.append_block(block, is_genesis)?;
+		self.fork_candidate = None;
+		Ok(())
+	}
+
+	/// Switches the chain to the branch of the `candidate_blocks`, if it has
+	/// strictly more cumulative work than the blocks it replaces and is fully
+	/// valid. With the equal work, the current chain is kept.
+	///
+	/// The candidate blocks, which are already stored, are skipped, so the
+	/// whole chain of another node may be passed. The rest of them are the
+	/// branch, which must continue a stored block or start with the genesis
+	/// one. The branch is validated on a copy of the chain in memory before
+	/// the store is changed, and the store replaces the blocks at once, so
+	/// the current chain is untouched if the branch is rejected.
+	#[tracing::instrument(skip(self, candidate_blocks))]
+	pub fn try_reorg(
+		&mut self,
+		candidate_blocks: &[crate::block::Block],
+	) -> Result<Reorg, TryReorgError> {
+		// Skip the common blocks
+		let mut start = 0;
+		while let Some(block) = candidate_blocks.get(start) {
+			if self.store.get_height_by_hash(&block.compute_hash()?)?.is_none()
+			{
+				break;
+			}
+			start += 1;
+		}
+		let branch = &candidate_blocks[start..];
+		let fork_height = match branch.first().map(|b| b.previous_hash()) {
+			None => return Ok(Reorg::NotHeavier),
+			Some(None) => 0,
+			Some(Some(previous_hash)) => {
+				self.store
+					.get_height_by_hash(previous_hash)?
+					.ok_or(TryReorgError::UnknownForkPoint)?
+					+ 1
+			}
+		};
+		let replaced = self.len()?.saturating_sub(fork_height);
+		if self.work(branch.len()) <= self.work(replaced) {
+			return Ok(Reorg::NotHeavier);
+		}
+		self.switch_to_branch(fork_height, branch)?;
+		Ok(Reorg::Switched)
+	}
+
+	/// Replaces the blocks from the `fork_height` with the `branch`, if it is
+	/// valid on top of the blocks before it. The branch is validated on a copy
+	/// of the chain in memory before the store is changed.
+	fn switch_to_branch(
+		&mut self,
+		fork_height: usize,
+		branch: &[crate::block::Block],
+	) -> Result<(), TryReorgError> {
+		use crate::store::BlockStore as _;
+
+		// Validate the branch on top of the copy of the common blocks
+		let temp = Blockchain::new(
+			self.miner.clone(),
+			self.consensus.clone(),
+			crate::store::MemoryStore::default(),
+		)?;
+		for json in self.store.get_all()?.into_iter().take(fork_height) {
+			let hash = serde_json::from_str::<crate::block::Block>(&json)?
+				.compute_hash()?;
+			temp.store.insert_block(hash, json)?;
+		}
+		let mut blocks = Vec::with_capacity(branch.len());
+		for block in branch {
+			temp.append_block(block, temp.is_empty()?)
+				.map_err(TryReorgError::InvalidBranch)?;
+			blocks
+				.push((block.compute_hash()?, serde_json::to_string(block)?));
+		}
+
+		// Switch to the branch. The caches are locked like in
+		// `Self::add_block_to_database`
+		{
+			let mut count = self.lock_blocks_count();
+			let mut last_hash = self.lock_last_block_hash();
+			let mut balances = self.lock_balances();
+			self.store.replace_from(fork_height, blocks)?;
+			*count = None;
+			*last_hash = None;
+			balances.clear();
+		}
+		self.preparing_block_state.clear();
+		self.sealed = false;
+		self.fork_candidate = None;
+		IS_MINING.store(false, std::sync::atomic::Ordering::Relaxed);
+		tracing::info!(
+			"Switched to the branch of {} blocks from height {fork_height}.",
+			branch.len()
+		);
+		Ok(())
+	}
+
+	/// The cumulative work of the `blocks` number of blocks, that is, the
+	/// expected number of hashes to mine them. The difficulty is constant, so
+	/// each block has the same work.
+	fn work(&self, blocks: usize) -> u128 {
+		let block_work = if self.consensus.require_proof_of_work() {
+			16_u128.pow(u32::from(crate::consts::PROOF_OF_WORK_DIFFICULTY))
+		} else {
+			1
+		};
+		block_work.saturating_mul(blocks as u128)
+	}
+
+	/// Decides between the `block` and the last block, if they compete for
+	/// the same height, that is, have the same previous block. The block with
+	/// the lower hash wins, so all nodes make the same choice, and the chain is
+	/// switched to it.
+	///
+	/// Only the parts of the `block` which do not depend on the chain are
+	/// validated before. Its transactions are validated with its branch, when
+	/// it is switched to.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self, block))]
+	pub fn tie_break(
+		&mut self,
+		block: &crate::block::Block<'a>,
+	) -> Result<TieBreak, TieBreakError> {
+		let (hash, last_hash, same_previous) = {
+			let last = self.get_last_block()?.ok_or(TieBreakError::Empty)?;
+			(
+				block.compute_hash()?,
+				last.compute_hash()?,
+				block.previous_hash() == last.previous_hash(),
+			)
+		};
+		if block.previous_hash().is_none()
+			|| !same_previous
+			|| hash == last_hash
+		{
+			return Ok(TieBreak::NotCompeting);
+		}
+
+		block.validate_standalone(self)?;
+		if hash < last_hash {
+			let fork_height = self.len()? - 1;
+			self.switch_to_branch(fork_height, std::slice::from_ref(block))
+				.map_err(TieBreakError::Switch)?;
+			return Ok(TieBreak::Won);
+		}
+		self.fork_candidate = Some(block.clone());
+		Ok(TieBreak::Lost)
+	}
+
+	/// Adds a new pending transaction to `self.preparing_block_state`.
+	#[tracing::instrument(skip(self))]
+	pub fn add_transaction(
+		&mut self,
 		transaction: crate::transaction::Transaction<'a>,
 	) -> Result<(), AddTransactionError> {
 		debug_assert!(!self.is_empty()?, "Mine the genesis block first.");
 
 		// Validate transaction
-		if transaction.sender() != crate::consts::STORAGE_ADDRESS
+		if self.sealed {
+			return Err(AddTransactionError::Sealed);
+		}
+		self.prune_stale_transactions()?;
+		if self
+			.preparing_block_state
+			.transactions
+			.iter()
+			.any(|t| t.random_string() == transaction.random_string())
+		{
+			// It would fail the uniqueness check of the mined block
+			return Err(AddTransactionError::Duplicate);
+		} else if !transaction.sender().is_storage()
 			&& self.preparing_block_state.transactions.len()
 				== crate::consts::USER_TRANSACTIONS_PER_BLOCK as usize
 		{
-			return Err(AddTransactionError::LimitReached);
+			return self.replace_cheapest_transaction(transaction);
 		}
+		// The costs must be checked before anything else, because with an
+		// amount near `u64::MAX` they wrap to a tiny debit
+		let sender_costs = transaction
+			.total_cost()
+			.ok_or(AddTransactionError::TotalCostOverflow)?;
 		transaction.validate_integrity(self)?;
 
+		// The pending costs of the sender must be covered by the confirmed
+		// balance, not by the money received in the same block
+		let sender = transaction.sender().as_str();
+		let outgoing = self
+			.preparing_block_state
+			.outgoing
+			.get(sender)
+			.copied()
+			.unwrap_or(0)
+			.checked_add(u64::from(sender_costs))
+			.ok_or(AddTransactionError::TotalCostOverflow)?;
+		if !transaction.sender().is_storage()
+			&& outgoing > self.get_stored_balance(sender)?
+		{
+			return Err(AddTransactionError::PendingExceedsBalance);
+		}
+
 		// Withdrawal of sender costs
-		let sender_costs = unsafe {
-			std::num::NonZeroU64::new_unchecked(
-				u64::from(transaction.amount())
-					+ transaction.amount_to_storage(),
-			)
-		};
-		self.remove_from_balance(transaction.sender(), sender_costs)?;
+		self.remove_from_balance(sender, sender_costs)?;
 
 		// Adding profit to the recipient and the storage
-		self.add_to_balance(transaction.recipient(), transaction.amount())
-			.map_err(AddTransactionError::AddToRecipientBalance)?;
-		if transaction.amount_to_storage() != 0 {
-			let amount = unsafe {
-				std::num::NonZeroU64::new_unchecked(
-					transaction.amount_to_storage(),
-				)
-			};
+		self.add_to_balance(
+			transaction.recipient().as_str(),
+			transaction.amount(),
+		)
+		.map_err(AddTransactionError::AddToRecipientBalance)?;
+		if let Some(amount) =
+			std::num::NonZeroU64::new(transaction.amount_to_storage())
+		{
 			self.add_to_balance(crate::consts::STORAGE_ADDRESS, amount)
 				.map_err(AddTransactionError::AddToStorageBalance)?;
 		}
 
+		self.preparing_block_state
+			.outgoing
+			.insert(transaction.sender().to_string(), outgoing);
 		self.preparing_block_state.transactions.push(transaction);
+		self.preparing_block_state
+			.pending_since
+			.get_or_insert_with(std::time::Instant::now);
+		Ok(())
+	}
+
+	/// Drops the pending transactions, which point to a block other than the
+	/// last one, because the last block was replaced before they were mined.
+	/// The others are added again, since the balances may change with the
+	/// new last block, and dropped too if they fail.
+	///
+	/// Returns the dropped transactions.
+	#[tracing::instrument(level = tracing::Level::DEBUG, skip(self))]
+	pub fn prune_stale_transactions(
+		&mut self,
+	) -> Result<
+		Vec<crate::transaction::Transaction<'a>>,
+		PruneStaleTransactionsError,
+	> {
+		let last_hash = self.get_last_block_hash()?;
+		if self
+			.preparing_block_state
+			.transactions
+			.iter()
+			.all(|t| t.previous_block_hash() == last_hash)
+		{
+			return Ok(Vec::new());
+		}
+
+		let pending_since = self.preparing_block_state.pending_since;
+		let (fresh, mut stale): (Vec<_>, Vec<_>) =
+			std::mem::take(&mut self.preparing_block_state.transactions)
+				.into_iter()
+				.partition(|t| t.previous_block_hash() == last_hash);
+		self.preparing_block_state.clear();
+		for t in fresh {
+			if let Err(e) = self.add_transaction(t.clone()) {
+				tracing::debug!("Failed to add the transaction again: {e}");
+				stale.push(t);
+			}
+		}
+		if !self.preparing_block_state.transactions.is_empty() {
+			self.preparing_block_state.pending_since = pending_since;
+		}
+		for t in &stale {
+			tracing::warn!(
+				"The stale pending transaction {} was dropped.",
+				t.random_string()
+			);
+		}
+		Ok(stale)
+	}
+
+	/// Replaces the pending transaction with the lowest fee and then amount
+	/// to storage, the latest of them, with the `transaction`, if they are
+	/// higher. So the last slot of a full block goes to the better
+	/// transaction.
+	///
+	/// The other pending transactions are added again, because they may
+	/// spend the replaced one. If they fail, nothing is replaced.
+	fn replace_cheapest_transaction(
+		&mut self,
+		transaction: crate::transaction::Transaction<'a>,
+	) -> Result<(), AddTransactionError> {
+		let Some(index) = self
+			.preparing_block_state
+			.transactions
+			.iter()
+			.enumerate()
+			.rev()
+			.min_by_key(|(_, t)| (t.fee(), t.amount_to_storage()))
+			.filter(|(_, t)| {
+				(t.fee(), t.amount_to_storage())
+					< (transaction.fee(), transaction.amount_to_storage())
+			})
+			.map(|(i, _)| i)
+		else {
+			return Err(AddTransactionError::LimitReached);
+		};
+
+		let backup = self.preparing_block_state.clone();
+		let mut transactions =
+			std::mem::take(&mut self.preparing_block_state.transactions);
+		let replaced = transactions.remove(index);
+		self.preparing_block_state.clear();
+		// The replayed transactions keep waiting since the oldest one
+		self.preparing_block_state.pending_since = backup.pending_since;
+		for t in transactions.into_iter().chain([transaction]) {
+			if let Err(e) = self.add_transaction(t) {
+				self.preparing_block_state = backup;
+				return Err(e);
+			}
+		}
+		tracing::info!(
+			"The pending transaction {} was replaced by a higher fee one.",
+			replaced.random_string()
+		);
 		Ok(())
 	}
 
 	/// Tries to get the user's balance from the `self.balance_state`. If it
-	/// fails, it tries to get it with `self.get_balance_from_database`.
+	/// fails, it tries to get it from the `self.balances` cache.
+	///
+	/// On a cache miss, it tries to get it from the balance state of the
+	/// last block, which is a single row. Otherwise it scans all blocks with
+	/// `self.get_balance_from_database`. The found balance is cached.
 	#[tracing::instrument(level = tracing::Level::DEBUG, ret, skip(self))]
 	pub fn get_balance(&self, address: &str) -> Result<u64, GetBalanceError> {
-		let rv = match self.preparing_block_state.balance_state.get(address) {
-			Some(b) => *b,
+		if let Some(b) = self.preparing_block_state.balance_state.get(address)
+		{
+			return Ok(*b);
+		}
+		self.get_stored_balance(address)
+	}
+
+	/// Gets the balance before the `block`. It is the cached one if the
+	/// `block` follows the last stored block, otherwise the blocks are
+	/// scanned with `self.get_balance_from_database`.
+	pub(crate) fn get_balance_before(
+		&self,
+		address: &str,
+		block: &crate::block::Block,
+	) -> Result<u64, GetBalanceError> {
+		let follows_last = match block.previous_hash() {
+			Some(previous_hash) => match self.get_last_block_hash() {
+				Ok(h) => h == previous_hash,
+				Err(GetLastBlockHashError::Empty) => false,
+				Err(e) => return Err(e.into()),
+			},
+			None => false,
+		};
+		if follows_last {
+			return self.get_stored_balance(address);
+		}
+		Ok(self.get_balance_from_database(address, Some(block))?)
+	}
+
+	/// Gets the balance as of the last stored block from the
+	/// `self.balances` cache or finds and caches it.
+	fn get_stored_balance(
+		&self,
+		address: &str,
+	) -> Result<u64, GetBalanceError> {
+		let mut balances = self.lock_balances();
+		if let Some(b) = balances.get(address) {
+			return Ok(*b);
+		}
+		let balance = match self
+			.get_last_block()?
+			.and_then(|b| b.balance_state().get(address).copied())
+		{
+			Some(b) => b,
 			None => self.get_balance_from_database(address, None)?,
 		};
-		Ok(rv)
+		balances.insert(address.to_owned(), balance);
+		Ok(balance)
 	}
 
-	/// Gets the balance from the database.
+	/// Gets the balance from the database. The balance deltas of the blocks
+	/// are summed up to the newest full balance.
 	pub(crate) fn get_balance_from_database(
 		&self,
 		address: &str,
 		before_block: Option<&crate::block::Block>,
 	) -> Result<u64, GetBalanceFromDatabaseError> {
-		let mut rv = 0;
+		use std::convert::TryFrom as _;
+
+		let (mut balance, mut delta) = (0, 0_i128);
 		// Obtaining blocks and reversing them (Looking for a fresh balance)
 		let mut blocks = self.get_blocks(before_block)?;
 		blocks.reverse();
-		// Looking for balance
+		// Looking for balance, summing the deltas of the newer blocks
 		for block in blocks {
 			if let Some(b) = block.balance_state().get(address) {
-				rv = *b;
+				balance = *b;
 				break;
 			}
+			delta += block.balance_deltas().get(address).copied().unwrap_or(0);
 		}
-		Ok(rv)
+		u64::try_from(i128::from(balance) + delta)
+			.map_err(|_| GetBalanceFromDatabaseError::InvalidDeltas)
 	}
 
-	/// Gets all existing blocks using the query `consts::DB_GET_ALL_QUERY`.
+	/// Gets all existing blocks, or the blocks up to the one before the
+	/// `before_block`.
 	#[tracing::instrument(level = tracing::Level::TRACE, ret, skip(self))]
 	fn get_blocks(
 		&self,
 		before_block: Option<&crate::block::Block>,
-	) -> Result<Vec<crate::block::Block>, GetBlocksError> {
+	) -> Result<Vec<crate::block::Block<'_>>, GetBlocksError> {
 		let mut rv = Vec::<crate::block::Block>::new();
-
-		// Getting connection and results with jsons
-		let connection = self.db_pool.get()?;
-		let mut statement = connection
-			.prepare(crate::consts::DB_GET_ALL_QUERY)
-			.map_err(GetBlocksError::PrepareDbStatement)?;
-		let json_results = statement
-			.query_map([], |row| row.get(0))
-			.map_err(GetBlocksError::QueryDb)?;
-
-		// Unwrap results and push to vector
-		for json_result in json_results {
+		for json in self.store.get_all()? {
 			let mut break_ = false;
 			// Convert JSON into a object
-			let json: String =
-				json_result.map_err(GetBlocksError::UnwrapDbResult)?;
 			let block: crate::block::Block = serde_json::from_str(&json)?;
 			// Check if the current block was the last one
 			if let Some(before_block) = before_block {
 				let block_hash = block.compute_hash()?;
 				if before_block
 					.previous_hash()
-					.map_or(false, |ph| ph == block_hash)
+					.is_some_and(|ph| ph == block_hash)
 				{
 					break_ = true;
 				}
@@ -426,45 +1515,121 @@ impl<'a> Blockchain<'a> {
 	///
 	/// # Arguments
 	///
-	/// `recipient` is a `String` to avoid immutable (`self.miner.address()`)
-	/// and mutable (`&mut self`) accesses.
+	/// `recipient` is owned to avoid immutable (`self.miner.address()`) and
+	/// mutable (`&mut self`) accesses.
 	#[tracing::instrument(ret, skip(self))]
 	fn make_storage_transaction(
 		&mut self,
-		recipient: String,
+		recipient: crate::address::Address,
 		amount: std::num::NonZeroU64,
 	) -> Result<crate::transaction::Transaction<'a>, MakeStorageTransactionError>
 	{
 		let transaction = crate::transaction::Transaction::new(
-			crate::consts::STORAGE_ADDRESS,
+			crate::address::Address::storage(),
 			recipient,
 			amount,
 			self.get_last_block_hash()?,
+			self.consensus.chain_id(),
 		);
 		self.add_transaction(transaction.clone())?;
 		Ok(transaction)
 	}
 
-	/// Gives us the number of existing blocks in the database.
+	/// Gives us the number of existing blocks in the store. It is counted
+	/// only if `self.blocks_count` is unknown.
 	#[tracing::instrument(level = tracing::Level::DEBUG, ret, skip(self))]
 	fn get_blocks_count(&self) -> Result<usize, GetBlocksCountError> {
-		let count = self.db_pool.get()?.query_row(
-			crate::consts::DB_GET_COUNT_QUERY,
-			[],
-			|row| row.get(0),
-		)?;
-		Ok(count)
+		let mut count = self.lock_blocks_count();
+		if let Some(c) = *count {
+			return Ok(c);
+		}
+		let c = self.store.count()?;
+		*count = Some(c);
+		Ok(c)
 	}
 
-	/// Adds a block to the database in JSON format.
+	/// Adds a block to the store in JSON format.
+	///
+	/// The `self.blocks_count`, `self.last_block_hash` and `self.balances`
+	/// are locked while the block is inserted, so they are not computed in
+	/// between. The cached balances in the balance state of the block are
+	/// updated, and the ones with balance deltas are dropped.
 	fn add_block_to_database(
 		&self,
 		block: &crate::block::Block,
 	) -> Result<(), AddBlockToDatabaseError> {
 		let json = serde_json::to_string(block)?;
-		self.db_pool
-			.get()?
-			.execute(crate::consts::DB_INSERT_QUERY_TEMPLATE, [json])?;
+		let hash = block.compute_hash()?;
+		let mut count = self.lock_blocks_count();
+		let mut last_hash = self.lock_last_block_hash();
+		let mut balances = self.lock_balances();
+		self.store.insert_block(hash.clone(), json)?;
+		if let Some(c) = count.as_mut() {
+			*c += 1;
+		}
+		*last_hash = Some(hash);
+		balances.extend(
+			block.balance_state().iter().map(|(a, b)| (a.clone(), *b)),
+		);
+		for address in block.balance_deltas().keys() {
+			balances.remove(address);
+		}
+		Ok(())
+	}
+
+	/// Validates the `block` against the last stored one, or as the genesis
+	/// block if `is_genesis`, and stores it.
+	fn append_block(
+		&self,
+		block: &crate::block::Block,
+		is_genesis: bool,
+	) -> Result<(), AddBlockError> {
+		if is_genesis {
+			debug_assert!(self.is_empty()?);
+			block.validate_genesis(self)?;
+		} else {
+			block.validate_integrity(self)?;
+		}
+		self.add_block_to_database(block)?;
+		Ok(())
+	}
+
+	fn lock_blocks_count(&self) -> std::sync::MutexGuard<'_, Option<usize>> {
+		self.blocks_count
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner)
+	}
+
+	fn lock_last_block_hash(
+		&self,
+	) -> std::sync::MutexGuard<'_, Option<String>> {
+		self.last_block_hash
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner)
+	}
+
+	fn lock_balances(
+		&self,
+	) -> std::sync::MutexGuard<'_, std::collections::HashMap<String, u64>> {
+		self.balances.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+	}
+
+	/// Replaces the stored block at `height`, see
+	/// [`BlockStore::replace_block`](crate::store::BlockStore::replace_block).
+	/// The caches are forgotten, because the block may be the last one and
+	/// its balance state may differ. They are locked like in
+	/// `Self::add_block_to_database`.
+	fn replace_stored_block(
+		&self,
+		height: usize,
+		hash: String,
+		json: String,
+	) -> Result<(), crate::error::BlockStoreError> {
+		let mut last_hash = self.lock_last_block_hash();
+		let mut balances = self.lock_balances();
+		self.store.replace_block(height, hash, json)?;
+		*last_hash = None;
+		balances.clear();
 		Ok(())
 	}
 
@@ -501,3 +1666,1321 @@ impl<'a> Blockchain<'a> {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_get_genesis_block() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let genesis = blockchain.get_genesis_block().unwrap();
+		assert!(genesis.previous_hash().is_none());
+		assert_eq!(
+			blockchain.get_genesis_block_hash().unwrap(),
+			genesis.compute_hash().unwrap()
+		);
+	}
+
+	#[test]
+	fn test_mine_pending() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+
+		let (block, blockchain) = blockchain.mine_pending().unwrap();
+		assert_eq!(
+			blockchain.get_last_block_hash().unwrap(),
+			block.compute_hash().unwrap()
+		);
+		assert_eq!(blockchain.len().unwrap(), 2);
+		blockchain.validate_stored_chain().unwrap();
+	}
+
+	#[test]
+	fn test_add_blocks() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+		let blocks = blockchain.get_blocks(None).unwrap();
+
+		// All blocks are committed at once
+		let (mut imported, _dir) =
+			crate::test_helpers::create_test_single_connection_blockchain();
+		imported.add_blocks(&blocks, None).unwrap();
+		assert_eq!(
+			imported.to_string().unwrap(),
+			blockchain.to_string().unwrap()
+		);
+		imported.validate_stored_chain().unwrap();
+
+		// The second block is not linked to the last one, so the genesis
+		// block is rolled back too
+		let (mut imported, _dir) =
+			crate::test_helpers::create_test_single_connection_blockchain();
+		assert!(imported
+			.add_blocks(&[&blocks[..], &blocks[1..]].concat(), None)
+			.is_err());
+		assert!(imported.is_empty().unwrap());
+	}
+
+	#[test]
+	fn test_blocks_count_cache() {
+		use crate::store::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		assert_eq!(blockchain.len().unwrap(), 1);
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+		assert_eq!(blockchain.len().unwrap(), 2);
+		assert_eq!(blockchain.store.count().unwrap(), 2);
+
+		// The clone shares the store, so it shares the count too
+		let mut clone = blockchain.clone();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&clone, 5);
+		clone.add_transaction(transaction).unwrap();
+		clone.mine_block().unwrap();
+		assert_eq!(blockchain.len().unwrap(), 3);
+
+		// The rolled back blocks of the import are not counted
+		let blocks = blockchain.get_blocks(None).unwrap();
+		let (mut imported, _dir) =
+			crate::test_helpers::create_test_single_connection_blockchain();
+		imported.add_blocks(&blocks, None).unwrap();
+		assert_eq!(imported.len().unwrap(), 3);
+		let (mut imported, _dir) =
+			crate::test_helpers::create_test_single_connection_blockchain();
+		assert!(imported
+			.add_blocks(&[&blocks[..2], &blocks[..1]].concat(), None)
+			.is_err());
+		assert_eq!(imported.len().unwrap(), 0);
+		assert_eq!(imported.store.count().unwrap(), 0);
+	}
+
+	#[test]
+	fn test_last_block_hash_cache() {
+		use crate::store::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let compute = |b: &crate::Blockchain| {
+			let json = b.store.get_last().unwrap().unwrap();
+			serde_json::from_str::<crate::block::Block>(&json)
+				.unwrap()
+				.compute_hash()
+				.unwrap()
+		};
+		assert_eq!(
+			blockchain.get_last_block_hash().unwrap(),
+			compute(&blockchain)
+		);
+		for _ in 0..3 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+			assert_eq!(
+				blockchain.get_last_block_hash().unwrap(),
+				compute(&blockchain)
+			);
+		}
+
+		// The clone shares the store, so it shares the hash too
+		let mut clone = blockchain.clone();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&clone, 5);
+		clone.add_transaction(transaction).unwrap();
+		clone.mine_block().unwrap();
+		assert_eq!(blockchain.get_last_block_hash().unwrap(), compute(&clone));
+
+		// The rolled back blocks of the import are not hashed
+		let blocks = blockchain.get_blocks(None).unwrap();
+		let (mut imported, _dir) =
+			crate::test_helpers::create_test_single_connection_blockchain();
+		assert!(imported
+			.add_blocks(&[&blocks[..2], &blocks[..1]].concat(), None)
+			.is_err());
+		assert!(matches!(
+			imported.get_last_block_hash(),
+			Err(crate::error::GetLastBlockHashError::Empty)
+		));
+	}
+
+	#[test]
+	fn test_get_balance_from_last_block() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		let recipient = transaction.recipient().to_string();
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+
+		let last_block = blockchain.get_last_block().unwrap().unwrap();
+		assert!(last_block.balance_state().contains_key(&recipient));
+		assert_eq!(
+			blockchain.get_balance(&recipient).unwrap(),
+			blockchain.get_balance_from_database(&recipient, None).unwrap(),
+		);
+	}
+
+	#[test]
+	fn test_cached_balances() {
+		let _lock = crate::test_helpers::lock_mining();
+		for deltas in [false, true] {
+			let consensus = serde_json::from_value(
+				serde_json::json!({"balance_state_deltas": deltas}),
+			)
+			.unwrap();
+			let (mut blockchain, _dir) =
+				crate::test_helpers::create_test_blockchain_with(consensus);
+			let miner = blockchain.miner().address().to_string();
+			let recipient = crate::test_helpers::create_test_user();
+			let addresses = [miner.as_str(), recipient.address().as_ref()];
+
+			for _ in 0..3 {
+				let mut transaction = crate::transaction::Transaction::new(
+					blockchain.miner().address().clone(),
+					recipient.address().clone(),
+					std::num::NonZeroU64::new(5).unwrap(),
+					blockchain.get_last_block_hash().unwrap(),
+					blockchain.consensus().chain_id(),
+				);
+				transaction.sign(blockchain.miner()).unwrap();
+				blockchain.add_transaction(transaction).unwrap();
+				blockchain.mine_block().unwrap();
+
+				// The cached balances match a fresh scan
+				for address in addresses {
+					assert_eq!(
+						blockchain.get_balance(address).unwrap(),
+						blockchain
+							.get_balance_from_database(address, None)
+							.unwrap(),
+					);
+					assert!(blockchain.lock_balances().contains_key(address));
+				}
+			}
+			assert_eq!(blockchain.get_balance(addresses[1]).unwrap(), 15);
+		}
+	}
+
+	#[test]
+	fn test_all_known_addresses() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		let recipient = transaction.recipient().to_string();
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+
+		let expected: std::collections::BTreeSet<String> =
+			vec![blockchain.miner().address().to_string(), recipient]
+				.into_iter()
+				.collect();
+		assert_eq!(blockchain.all_known_addresses().unwrap(), expected);
+	}
+
+	#[test]
+	fn test_get_block_by_hash() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let genesis_hash = blockchain.get_last_block_hash().unwrap();
+		let mut hashes = Vec::new();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			hashes.push(
+				blockchain.mine_block().unwrap().compute_hash().unwrap(),
+			);
+		}
+		let middle_hash = &hashes[0];
+
+		let found =
+			blockchain.get_block_by_hash(middle_hash).unwrap().unwrap();
+		assert_eq!(&found.compute_hash().unwrap(), middle_hash);
+		assert_eq!(found.previous_hash(), Some(genesis_hash.as_str()));
+		assert!(blockchain.get_block_by_hash("unknown").unwrap().is_none());
+	}
+
+	#[test]
+	fn test_get_block_by_height() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..3 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		// The heights are contiguous and follow the order of the blocks
+		let blocks = blockchain.get_blocks(None).unwrap();
+		assert_eq!(blockchain.get_blocks_count().unwrap(), blocks.len());
+		for (height, block) in blocks.iter().enumerate() {
+			let found =
+				blockchain.get_block_by_height(height).unwrap().unwrap();
+			assert_eq!(
+				found.compute_hash().unwrap(),
+				block.compute_hash().unwrap()
+			);
+		}
+		assert!(blockchain
+			.get_block_by_height(blocks.len())
+			.unwrap()
+			.is_none());
+	}
+
+	#[test]
+	fn test_from_str_into() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+		let main_db = std::fs::read(&*crate::consts::DB_PATH).ok();
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("imported.db");
+		let imported = super::Blockchain::from_str_into(
+			blockchain.miner().clone(),
+			blockchain.consensus().clone(),
+			&blockchain.to_string().unwrap(),
+			&path,
+		)
+		.unwrap();
+		assert_eq!(imported.len().unwrap(), 2);
+		assert_eq!(
+			imported.get_last_block_hash().unwrap(),
+			blockchain.get_last_block_hash().unwrap()
+		);
+		assert!(path.exists());
+		assert_eq!(std::fs::read(&*crate::consts::DB_PATH).ok(), main_db);
+	}
+
+	#[test]
+	fn test_load_at() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("loaded.db");
+		let load = |path: &std::path::Path| {
+			super::Blockchain::load_at(
+				blockchain.miner().clone(),
+				blockchain.consensus().clone(),
+				path,
+			)
+		};
+
+		// Nothing is created for a missing or an empty database
+		assert!(matches!(
+			load(&path),
+			Err(crate::error::LoadBlockchainError::NotFound)
+		));
+		assert!(!path.exists());
+		std::fs::write(&path, "").unwrap();
+		assert!(matches!(
+			load(&path),
+			Err(crate::error::LoadBlockchainError::Empty)
+		));
+
+		let imported = super::Blockchain::from_str_into(
+			blockchain.miner().clone(),
+			blockchain.consensus().clone(),
+			&blockchain.to_string().unwrap(),
+			&path,
+		)
+		.unwrap();
+		assert_eq!(
+			load(&path).unwrap().get_last_block_hash().unwrap(),
+			imported.get_last_block_hash().unwrap()
+		);
+	}
+
+	#[test]
+	fn test_progress() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		// Every block and every second block with the last one
+		for (every, expected) in
+			[(1, vec![(1, 3), (2, 3), (3, 3)]), (2, vec![(2, 3), (3, 3)])]
+		{
+			let every = std::num::NonZeroUsize::new(every).unwrap();
+			let mut calls = Vec::new();
+			let mut callback =
+				|processed, total| calls.push((processed, total));
+			blockchain
+				.validate_stored_chain_with_progress(Some(
+					&mut crate::progress::Progress::new(every, &mut callback),
+				))
+				.unwrap();
+			assert_eq!(calls, expected);
+
+			calls.clear();
+			let mut callback =
+				|processed, total| calls.push((processed, total));
+			let dir = tempfile::tempdir().unwrap();
+			let path = dir.path().join("imported.db");
+			super::Blockchain::from_str_into_with_progress(
+				blockchain.miner().clone(),
+				blockchain.consensus().clone(),
+				&blockchain.to_string().unwrap(),
+				&path,
+				Some(&mut crate::progress::Progress::new(
+					every,
+					&mut callback,
+				)),
+			)
+			.unwrap();
+			assert_eq!(calls, expected);
+		}
+	}
+
+	#[test]
+	fn test_snapshot() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+
+		let dir = tempfile::tempdir().unwrap();
+		let snapshot_path = dir.path().join("blockchain.snapshot");
+		let path = dir.path().join("restored.db");
+		blockchain.snapshot_to_file(&snapshot_path).unwrap();
+		let restored = super::Blockchain::restore_from_file_into(
+			blockchain.miner().clone(),
+			blockchain.consensus().clone(),
+			&snapshot_path,
+			&path,
+		)
+		.unwrap();
+		assert_eq!(
+			restored.to_string().unwrap(),
+			blockchain.to_string().unwrap()
+		);
+		restored.validate_stored_chain().unwrap();
+
+		// A damaged snapshot is rejected
+		let mut bytes = std::fs::read(&snapshot_path).unwrap();
+		*bytes.last_mut().unwrap() ^= 1;
+		std::fs::write(&snapshot_path, bytes).unwrap();
+		assert!(matches!(
+			super::Blockchain::restore_from_file_into(
+				blockchain.miner().clone(),
+				blockchain.consensus().clone(),
+				&snapshot_path,
+				&path,
+			),
+			Err(crate::error::RestoreFromFileError::ChecksumNotEquals)
+		));
+	}
+
+	#[test]
+	fn test_from_str_with_mismatched_genesis() {
+		use crate::error::{
+			AddBlockError, AddBlocksError, BlockchainFromStrError,
+			ValidateBlockGenesisError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				crate::test_helpers::create_test_consensus(
+					serde_json::json!({"genesis_block_reward": 200}),
+				),
+			);
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("imported.db");
+		let result = super::Blockchain::from_str_into(
+			blockchain.miner().clone(),
+			common::config::Consensus::default(),
+			&blockchain.to_string().unwrap(),
+			&path,
+		);
+		assert!(matches!(
+			result,
+			Err(BlockchainFromStrError::AddBlocks(AddBlocksError::AddBlock(
+				AddBlockError::ValidateGenesis(
+					ValidateBlockGenesisError::BalanceStateNotEquals
+				)
+			)))
+		));
+		assert!(!path.exists());
+	}
+
+	#[test]
+	fn test_add_duplicate_transaction() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let miner = blockchain.miner().address().to_string();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction.clone()).unwrap();
+
+		assert!(matches!(
+			blockchain.add_transaction(transaction),
+			Err(super::AddTransactionError::Duplicate)
+		));
+		assert_eq!(blockchain.preparing_block_state.transactions.len(), 1);
+		assert_eq!(blockchain.get_balance(&miner).unwrap(), 95);
+	}
+
+	#[test]
+	fn test_add_overflowing_transaction() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let miner = blockchain.miner().address().to_string();
+		let transaction = crate::test_helpers::create_test_transaction(
+			&blockchain,
+			u64::MAX,
+		);
+		assert_ne!(transaction.amount_to_storage(), 0);
+
+		assert!(matches!(
+			blockchain.add_transaction(transaction),
+			Err(super::AddTransactionError::TotalCostOverflow)
+		));
+		assert!(blockchain.preparing_block_state.transactions.is_empty());
+		assert_eq!(blockchain.get_balance(&miner).unwrap(), 100);
+	}
+
+	#[test]
+	fn test_get_blocks_since_hash() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let genesis_hash = blockchain.get_last_block_hash().unwrap();
+		let mut hashes = Vec::new();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			hashes.push(
+				blockchain.mine_block().unwrap().compute_hash().unwrap(),
+			);
+		}
+
+		let since_hashes = |hash: &str| -> Option<Vec<String>> {
+			blockchain.get_blocks_since_hash(hash).unwrap().map(|blocks| {
+				blocks.iter().map(|b| b.compute_hash().unwrap()).collect()
+			})
+		};
+		assert_eq!(since_hashes(&genesis_hash), Some(hashes.clone()));
+		assert_eq!(since_hashes(&hashes[0]), Some(hashes[1..].to_vec()));
+		assert_eq!(since_hashes(&hashes[1]), Some(Vec::new()));
+		assert_eq!(since_hashes("unknown"), None);
+	}
+
+	#[test]
+	fn test_inclusion_proof() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		let random_string = transaction.random_string().to_owned();
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(blockchain.inclusion_proof(&random_string).unwrap().is_none());
+
+		blockchain.mine_block().unwrap();
+		let proof =
+			blockchain.inclusion_proof(&random_string).unwrap().unwrap();
+		assert_eq!(proof.height(), 1);
+		assert_eq!(
+			proof.block_hash(),
+			blockchain.get_last_block_hash().unwrap()
+		);
+		assert!(proof.verify(&random_string).unwrap());
+		assert!(!proof.verify("unknown").unwrap());
+	}
+
+	#[test]
+	fn test_recompute_balance_states() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let miner = blockchain.miner().address().to_string();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+		let hash = blockchain.get_last_block_hash().unwrap();
+		assert!(blockchain.recompute_balance_states(true).unwrap().is_empty());
+		assert_eq!(blockchain.get_last_block_hash().unwrap(), hash);
+
+		// Inject a wrong balance of the miner
+		let mut block = blockchain.get_last_block().unwrap().unwrap();
+		let mut state = block.balance_state().clone();
+		let right = state.insert(miner.clone(), 1000).unwrap();
+		block.replace_balance_state(state);
+		blockchain
+			.replace_stored_block(
+				1,
+				block.compute_hash().unwrap(),
+				serde_json::to_string(&block).unwrap(),
+			)
+			.unwrap();
+
+		// Only reported without rewriting
+		let expected = [crate::block::BalanceStateDiscrepancy::new(
+			1,
+			miner.clone(),
+			1000,
+			right,
+		)];
+		assert_eq!(
+			blockchain.recompute_balance_states(false).unwrap(),
+			expected
+		);
+		assert_eq!(blockchain.get_balance(&miner).unwrap(), 1000);
+
+		assert_eq!(
+			blockchain.recompute_balance_states(true).unwrap(),
+			expected
+		);
+		assert_eq!(blockchain.get_balance(&miner).unwrap(), right);
+		assert_eq!(blockchain.get_last_block_hash().unwrap(), hash);
+		assert!(blockchain
+			.recompute_balance_states(false)
+			.unwrap()
+			.is_empty());
+	}
+
+	#[test]
+	fn test_try_reorg() {
+		use crate::store::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut first, _dir) = crate::test_helpers::create_test_blockchain();
+		// The second chain has the same genesis block
+		let store = crate::store::MemoryStore::default();
+		store
+			.insert_block(
+				first.get_last_block_hash().unwrap(),
+				first.store.get_first().unwrap().unwrap(),
+			)
+			.unwrap();
+		let mut second = super::Blockchain::new(
+			first.miner().clone(),
+			first.consensus().clone(),
+			store,
+		)
+		.unwrap();
+
+		// The second chain becomes longer
+		let transaction =
+			crate::test_helpers::create_test_transaction(&first, 5);
+		first.add_transaction(transaction).unwrap();
+		first.mine_block().unwrap();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&second, 5);
+			second.add_transaction(transaction).unwrap();
+			second.mine_block().unwrap();
+		}
+		let branch: Vec<crate::block::Block> =
+			serde_json::from_str(&second.to_string().unwrap()).unwrap();
+		let hash = first.get_last_block_hash().unwrap();
+
+		// The equal length keeps the current chain
+		assert_eq!(
+			first.try_reorg(&branch[..2]).unwrap(),
+			super::Reorg::NotHeavier
+		);
+		assert!(matches!(
+			first.try_reorg(&branch[2..]),
+			Err(crate::error::TryReorgError::UnknownForkPoint)
+		));
+
+		// The longer branch with an invalid block is rejected at once
+		let mut tampered = branch.clone();
+		let mut state = tampered[2].balance_state().clone();
+		*state.values_mut().next().unwrap() += 1;
+		tampered[2].replace_balance_state(state);
+		assert!(matches!(
+			first.try_reorg(&tampered),
+			Err(crate::error::TryReorgError::InvalidBranch(_))
+		));
+		assert_eq!(first.len().unwrap(), 2);
+		assert_eq!(first.get_last_block_hash().unwrap(), hash);
+		first.validate_stored_chain().unwrap();
+
+		// The longer valid branch is switched to
+		assert_eq!(first.try_reorg(&branch).unwrap(), super::Reorg::Switched);
+		assert_eq!(first.len().unwrap(), 3);
+		assert_eq!(
+			first.get_last_block_hash().unwrap(),
+			second.get_last_block_hash().unwrap()
+		);
+		first.validate_stored_chain().unwrap();
+		let miner = first.miner().address().to_string();
+		assert_eq!(
+			first.get_balance(&miner).unwrap(),
+			second.get_balance(&miner).unwrap()
+		);
+	}
+
+	#[test]
+	fn test_try_reorg_equal_length() {
+		use crate::store::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let user = crate::test_helpers::create_test_user();
+		let mut first = super::Blockchain::new(
+			user.clone(),
+			common::config::Consensus::default(),
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		first.mine_genesis_block().unwrap();
+		// The second chain has the same genesis block
+		let store = crate::store::MemoryStore::default();
+		store
+			.insert_block(
+				first.get_last_block_hash().unwrap(),
+				first.store.get_first().unwrap().unwrap(),
+			)
+			.unwrap();
+		let mut second = super::Blockchain::new(
+			user,
+			common::config::Consensus::default(),
+			store,
+		)
+		.unwrap();
+		for blockchain in [&mut first, &mut second] {
+			let transaction =
+				crate::test_helpers::create_test_transaction(blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		// Whichever hash is lower, the valid branch of the equal length keeps
+		// the incumbent chain
+		let first_blocks = first.to_string().unwrap();
+		let second_blocks = second.to_string().unwrap();
+		let branch: Vec<crate::block::Block> =
+			serde_json::from_str(&second_blocks).unwrap();
+		assert_eq!(
+			first.try_reorg(&branch).unwrap(),
+			super::Reorg::NotHeavier
+		);
+		let branch: Vec<crate::block::Block> =
+			serde_json::from_str(&first_blocks).unwrap();
+		assert_eq!(
+			second.try_reorg(&branch).unwrap(),
+			super::Reorg::NotHeavier
+		);
+		assert_eq!(first.to_string().unwrap(), first_blocks);
+		assert_eq!(second.to_string().unwrap(), second_blocks);
+		first.validate_stored_chain().unwrap();
+		second.validate_stored_chain().unwrap();
+	}
+
+	#[test]
+	fn test_tie_break() {
+		use crate::store::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let user = crate::test_helpers::create_test_user();
+		let mut first = super::Blockchain::new(
+			user.clone(),
+			common::config::Consensus::default(),
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		first.mine_genesis_block().unwrap();
+		// The second chain has the same genesis block
+		let store = crate::store::MemoryStore::default();
+		store
+			.insert_block(
+				first.get_last_block_hash().unwrap(),
+				first.store.get_first().unwrap().unwrap(),
+			)
+			.unwrap();
+		let mut second = super::Blockchain::new(
+			user,
+			common::config::Consensus::default(),
+			store,
+		)
+		.unwrap();
+
+		// Mine the competing blocks at the same height
+		let mut blocks = Vec::new();
+		for blockchain in [&mut first, &mut second] {
+			let transaction =
+				crate::test_helpers::create_test_transaction(blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blocks.push(blockchain.mine_block().unwrap());
+		}
+		let first_won = blocks[1].compute_hash().unwrap()
+			> blocks[0].compute_hash().unwrap();
+
+		// Both chains pick the same block
+		let (first_result, second_result) = (
+			first.tie_break(&blocks[1]).unwrap(),
+			second.tie_break(&blocks[0]).unwrap(),
+		);
+		let (winner, loser, lost_block) = if first_won {
+			assert_eq!(first_result, super::TieBreak::Lost);
+			assert_eq!(second_result, super::TieBreak::Won);
+			(&second, &first, &blocks[1])
+		} else {
+			assert_eq!(first_result, super::TieBreak::Won);
+			assert_eq!(second_result, super::TieBreak::Lost);
+			(&first, &second, &blocks[0])
+		};
+		// The won block is switched to and the lost one is kept
+		assert_eq!(
+			loser.fork_candidate().map(|b| b.compute_hash().unwrap()),
+			Some(lost_block.compute_hash().unwrap())
+		);
+		assert_eq!(winner.len().unwrap(), 2);
+		winner.validate_stored_chain().unwrap();
+		assert_eq!(
+			winner.get_last_block_hash().unwrap(),
+			loser.get_last_block_hash().unwrap()
+		);
+		assert!(winner.fork_candidate().is_none());
+
+		// Neither the same block, nor the genesis block compete
+		let last = std::cmp::min_by_key(&blocks[0], &blocks[1], |b| {
+			b.compute_hash().unwrap()
+		})
+		.clone();
+		assert_eq!(
+			first.tie_break(&last).unwrap(),
+			super::TieBreak::NotCompeting
+		);
+		let genesis: crate::block::Block =
+			serde_json::from_str(&first.store.get_first().unwrap().unwrap())
+				.unwrap();
+		assert_eq!(
+			first.tie_break(&genesis).unwrap(),
+			super::TieBreak::NotCompeting
+		);
+	}
+
+	#[test]
+	fn test_mempool_fee_histogram() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		assert!(blockchain.mempool_fee_histogram().is_empty());
+
+		// The amount to storage is not the fee
+		for (amount, fee) in [(10, 0), (5, 3)] {
+			let transaction =
+				crate::test_helpers::create_test_transaction_with_fee(
+					&blockchain,
+					amount,
+					fee,
+				);
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		assert_eq!(blockchain.mempool_fee_histogram(), [(0, 1), (3, 1)]);
+	}
+
+	#[test]
+	fn test_replace_cheapest_transaction() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let miner = blockchain.miner().address().to_string();
+		let mut cheap = Vec::new();
+		for _ in 0..crate::consts::USER_TRANSACTIONS_PER_BLOCK {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			cheap.push(transaction.recipient().to_string());
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		assert!(blockchain.minable());
+
+		// The same fee does not get the last slot
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 6);
+		assert!(matches!(
+			blockchain.add_transaction(transaction),
+			Err(super::AddTransactionError::LimitReached)
+		));
+
+		// The higher fee replaces the latest cheap transaction
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 10);
+		let recipient = transaction.recipient().to_string();
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(blockchain.minable());
+		assert_eq!(blockchain.mempool_fee_histogram(), [(0, 2)]);
+		assert_eq!(blockchain.get_balance(&cheap[0]).unwrap(), 5);
+		assert_eq!(blockchain.get_balance(cheap.last().unwrap()).unwrap(), 0);
+		assert_eq!(blockchain.get_balance(&recipient).unwrap(), 10);
+		assert_eq!(
+			blockchain.get_balance(&miner).unwrap(),
+			100 - 5 - 10 - crate::consts::STORAGE_REWARD
+		);
+	}
+
+	#[test]
+	fn test_snapshot_balances() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		let recipient = transaction.recipient().to_string();
+		blockchain.add_transaction(transaction).unwrap();
+		blockchain.mine_block().unwrap();
+
+		let snapshot = blockchain.snapshot_balances().unwrap();
+		let mut addresses = blockchain.all_known_addresses().unwrap();
+		addresses.insert(crate::consts::STORAGE_ADDRESS.to_owned());
+		assert!(snapshot.keys().eq(addresses.iter()));
+		for (address, balance) in &snapshot {
+			assert_eq!(blockchain.get_balance(address).unwrap(), *balance);
+		}
+		assert_eq!(snapshot[&recipient], 5);
+	}
+
+	#[test]
+	fn test_validate_block_at() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..3 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		// A mid-chain block is not valid against the last block
+		let mut block = blockchain.get_blocks(None).unwrap().swap_remove(2);
+		assert!(block.validate_integrity(&blockchain).is_err());
+		for index in 0..4 {
+			blockchain.validate_block_at(index).unwrap();
+		}
+		assert!(matches!(
+			blockchain.validate_block_at(4),
+			Err(crate::error::ValidateBlockAtError::NotFound(4))
+		));
+
+		// The tampered block fails, and so does the next one
+		let mut state = block.balance_state().clone();
+		*state.values_mut().next().unwrap() += 1;
+		block.replace_balance_state(state);
+		blockchain
+			.replace_stored_block(
+				2,
+				block.compute_hash().unwrap(),
+				serde_json::to_string(&block).unwrap(),
+			)
+			.unwrap();
+		assert!(blockchain.validate_block_at(1).is_ok());
+		assert!(blockchain.validate_block_at(2).is_err());
+		assert!(blockchain.validate_block_at(3).is_err());
+	}
+
+	#[test]
+	fn test_pending_exceeds_balance() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let miner = blockchain.miner().clone();
+		let recipient = crate::test_helpers::create_test_user();
+		let transfer = |blockchain: &super::Blockchain,
+		                sender: &crate::user::User,
+		                amount: u64| {
+			let mut transaction = crate::transaction::Transaction::new(
+				sender.address().clone(),
+				recipient.address().clone(),
+				std::num::NonZeroU64::new(amount).unwrap(),
+				blockchain.get_last_block_hash().unwrap(),
+				blockchain.consensus().chain_id(),
+			);
+			transaction.sign(sender).unwrap();
+			transaction
+		};
+		let user_transactions = |block: &crate::block::Block| {
+			block
+				.transactions()
+				.iter()
+				.filter(|t| !t.sender().is_storage())
+				.count()
+		};
+
+		// Each transaction fits the balance, but both do not
+		let half =
+			blockchain.get_balance(miner.address().as_str()).unwrap() / 2;
+		blockchain
+			.add_transaction(transfer(&blockchain, &miner, half + 1))
+			.unwrap();
+		assert!(matches!(
+			blockchain.add_transaction(transfer(
+				&blockchain,
+				&miner,
+				half + 1
+			)),
+			Err(crate::error::AddTransactionError::PendingExceedsBalance)
+		));
+		assert_eq!(user_transactions(&blockchain.mine_block().unwrap()), 1);
+
+		// The money received in the same block is not confirmed
+		let sender = crate::test_helpers::create_test_user();
+		let mut transaction = crate::transaction::Transaction::new(
+			miner.address().clone(),
+			sender.address().clone(),
+			std::num::NonZeroU64::new(10).unwrap(),
+			blockchain.get_last_block_hash().unwrap(),
+			blockchain.consensus().chain_id(),
+		);
+		transaction.sign(&miner).unwrap();
+		blockchain.add_transaction(transaction).unwrap();
+		assert!(matches!(
+			blockchain.add_transaction(transfer(&blockchain, &sender, 5)),
+			Err(crate::error::AddTransactionError::PendingExceedsBalance)
+		));
+
+		assert_eq!(user_transactions(&blockchain.mine_block().unwrap()), 1);
+	}
+
+	#[test]
+	fn test_add_transaction_while_mining() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut live, _dir) = crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&live, 5);
+		live.add_transaction(transaction).unwrap();
+
+		// The transaction which comes during the mining points to the block
+		// before the mined one, so it is rejected instead of being lost
+		let clone = live.seal();
+		assert!(!live.partially_minable());
+		let during = crate::test_helpers::create_test_transaction(&live, 7);
+		assert!(matches!(
+			live.add_transaction(during.clone()),
+			Err(crate::error::AddTransactionError::Sealed)
+		));
+		let (block, mined) = clone.mine_pending().unwrap();
+		assert!(block
+			.transactions()
+			.iter()
+			.all(|t| t.random_string() != during.random_string()));
+		let balance = mined.get_balance(live.miner().address().as_str());
+		live = mined;
+		assert!(!live.sealed());
+		assert_eq!(
+			live.get_balance(live.miner().address().as_str()).unwrap(),
+			balance.unwrap()
+		);
+
+		// It is accepted again against the new last block
+		let after = crate::test_helpers::create_test_transaction(&live, 7);
+		live.add_transaction(after).unwrap();
+
+		// The failed mining gives the transactions back
+		let _clone = live.seal();
+		live.unseal();
+		assert!(live.partially_minable());
+		let another = crate::test_helpers::create_test_transaction(&live, 7);
+		live.add_transaction(another).unwrap();
+	}
+
+	#[test]
+	fn test_prune_stale_transactions() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let stale =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(stale.clone()).unwrap();
+
+		// Another blockchain with the same database replaces the last block
+		let mut other = blockchain.clone();
+		other.mine_block().unwrap();
+		assert_eq!(blockchain.len().unwrap(), 2);
+
+		// The stale transaction is not mined into a doomed block
+		let dropped = blockchain.clone().prune_stale_transactions().unwrap();
+		assert_eq!(dropped.len(), 1);
+		assert_eq!(dropped[0].random_string(), stale.random_string());
+		assert!(matches!(
+			blockchain.mine_block(),
+			Err(crate::error::MineBlockError::NoPendingTransactions)
+		));
+		assert!(blockchain.preparing_block_state.transactions.is_empty());
+		assert!(blockchain.preparing_block_state.pending_since.is_none());
+
+		// The stale transaction is dropped before a fresh one is added
+		blockchain.add_transaction(stale.clone()).unwrap_err();
+		let fresh =
+			crate::test_helpers::create_test_transaction(&blockchain, 7);
+		blockchain.add_transaction(fresh.clone()).unwrap();
+		let block = blockchain.mine_block().unwrap();
+		assert_eq!(blockchain.len().unwrap(), 3);
+		assert!(block
+			.transactions()
+			.iter()
+			.any(|t| t.random_string() == fresh.random_string()));
+		assert!(block
+			.transactions()
+			.iter()
+			.all(|t| t.random_string() != stale.random_string()));
+	}
+
+	#[test]
+	fn test_minable_with_grace() {
+		use std::time::Duration;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		let grace = Duration::from_millis(100);
+		assert!(!blockchain.minable_with_grace(grace));
+
+		std::thread::sleep(grace);
+		assert!(blockchain.minable_with_grace(grace));
+		assert!(!blockchain.minable_with_grace(Duration::ZERO));
+		blockchain.mine_block().unwrap();
+		assert!(!blockchain.minable_with_grace(grace));
+	}
+
+	#[test]
+	fn test_balance_deltas() {
+		let _lock = crate::test_helpers::lock_mining();
+		let user = crate::test_helpers::create_test_user();
+		let recipients = [
+			crate::test_helpers::create_test_user().address().clone(),
+			crate::test_helpers::create_test_user().address().clone(),
+		];
+		let mut chains = [false, true].iter().map(|&deltas| {
+			let consensus = crate::test_helpers::create_test_consensus(
+				serde_json::json!({"balance_state_deltas": deltas}),
+			);
+			let mut blockchain = super::Blockchain::new(
+				user.clone(),
+				consensus,
+				crate::store::MemoryStore::default(),
+			)
+			.unwrap();
+			blockchain.mine_genesis_block().unwrap();
+			blockchain
+		});
+		let (mut full, mut deltas) =
+			(chains.next().unwrap(), chains.next().unwrap());
+
+		// The same transactions on both chains
+		for (i, amount) in [5, 10, 15].iter().enumerate() {
+			for blockchain in &mut [&mut full, &mut deltas] {
+				let mut transaction = crate::transaction::Transaction::new(
+					user.address().clone(),
+					recipients[i % 2].clone(),
+					std::num::NonZeroU64::new(*amount).unwrap(),
+					blockchain.get_last_block_hash().unwrap(),
+					blockchain.consensus().chain_id(),
+				);
+				transaction.sign(&user).unwrap();
+				blockchain.add_transaction(transaction).unwrap();
+				blockchain.mine_block().unwrap();
+			}
+		}
+
+		for block in deltas.get_blocks(None).unwrap() {
+			assert!(block.balance_state().is_empty());
+			assert!(!block.balance_deltas().is_empty());
+		}
+		let snapshot = full.snapshot_balances().unwrap();
+		assert_eq!(deltas.snapshot_balances().unwrap(), snapshot);
+		for address in snapshot.keys() {
+			assert_eq!(
+				deltas.get_balance(address).unwrap(),
+				full.get_balance(address).unwrap()
+			);
+		}
+		assert_eq!(snapshot[recipients[0].as_str()], 20);
+		assert_eq!(snapshot[recipients[1].as_str()], 10);
+	}
+
+	#[test]
+	fn test_recent_block_stats() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		let stats = blockchain.recent_block_stats(2).unwrap();
+		let blocks = blockchain.get_blocks(None).unwrap();
+		assert_eq!(stats.len(), 2);
+		for (stat, (height, block)) in
+			stats.iter().zip(blocks.iter().enumerate().skip(1))
+		{
+			assert_eq!(stat.height(), height);
+			assert_eq!(
+				stat.created_at().to_bits(),
+				block.created_at().to_bits()
+			);
+			assert_eq!(stat.transactions_count(), 2);
+			assert_eq!(stat.miner(), block.miner());
+		}
+		assert_eq!(blockchain.recent_block_stats(10).unwrap().len(), 3);
+	}
+
+	#[test]
+	fn test_last_n_block_hashes() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		for _ in 0..2 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&blockchain, 5);
+			blockchain.add_transaction(transaction).unwrap();
+			blockchain.mine_block().unwrap();
+		}
+
+		let hashes: Vec<String> = blockchain
+			.get_blocks(None)
+			.unwrap()
+			.iter()
+			.rev()
+			.map(|b| b.compute_hash().unwrap())
+			.collect();
+		assert_eq!(blockchain.last_n_block_hashes(2).unwrap(), hashes[..2]);
+		assert_eq!(
+			blockchain.last_n_block_hashes(2).unwrap()[0],
+			blockchain.get_last_block_hash().unwrap()
+		);
+		assert_eq!(blockchain.last_n_block_hashes(10).unwrap(), hashes);
+	}
+
+	#[test]
+	fn test_mine_block_with_treasury() {
+		let _lock = crate::test_helpers::lock_mining();
+		let treasury = crate::test_helpers::create_test_user();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				crate::test_helpers::create_test_consensus(
+					serde_json::json!({
+						"mining_reward": 10,
+						"treasury": treasury.address(),
+						"treasury_percent": 25,
+					}),
+				),
+			);
+		let miner = blockchain.miner().address().to_string();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		let block = blockchain.mine_block().unwrap();
+
+		// 2.5 is rounded down, the remainder goes to the miner
+		let rewards: Vec<(&str, u64)> = block
+			.transactions()
+			.iter()
+			.filter(|t| t.sender().is_storage())
+			.map(|t| (t.recipient().as_str(), u64::from(t.amount())))
+			.collect();
+		assert_eq!(
+			rewards,
+			[(miner.as_str(), 8), (treasury.address().as_str(), 2)]
+		);
+		assert_eq!(
+			blockchain.get_balance(treasury.address().as_str()).unwrap(),
+			2
+		);
+		assert_eq!(blockchain.get_balance(&miner).unwrap(), 100 - 5 + 8);
+		blockchain.validate_stored_chain().unwrap();
+
+		// The invalid treasury is rejected before the mining
+		assert!(matches!(
+			super::Blockchain::new(
+				crate::test_helpers::create_test_user(),
+				crate::test_helpers::create_test_consensus(
+					serde_json::json!({
+						"treasury": &treasury.address().as_str()[1..],
+						"treasury_percent": 25,
+					})
+				),
+				crate::store::MemoryStore::default(),
+			),
+			Err(crate::error::NewBlockchainError::ParseTreasury(_))
+		));
+	}
+
+	#[test]
+	fn test_mine_block_with_insolvent_storage() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		// Drain the storage
+		blockchain
+			.preparing_block_state
+			.balance_state
+			.insert(crate::consts::STORAGE_ADDRESS.to_owned(), 0);
+
+		assert!(matches!(
+			blockchain.mine_block(),
+			Err(super::MineBlockError::StorageInsolvent)
+		));
+		assert_eq!(blockchain.preparing_block_state.transactions.len(), 1);
+		assert_eq!(blockchain.len().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_mine_block_without_proof_of_work() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				crate::test_helpers::create_test_consensus(
+					serde_json::json!({"require_proof_of_work": false}),
+				),
+			);
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+		assert_eq!(blockchain.mine_block().unwrap().nonce(), 0);
+		blockchain.validate_stored_chain().unwrap();
+	}
+}
@@ -1,40 +1,99 @@
 lazy_static::lazy_static! {
 	static ref BASE_DIR: std::path::PathBuf = std::env::current_dir().unwrap();
 	pub static ref RESOURCES_DIR: std::path::PathBuf = BASE_DIR.join("resources");
-	pub(crate) static ref DB_PATH: std::path::PathBuf
+	pub static ref DB_PATH: std::path::PathBuf
 		= RESOURCES_DIR.join("sqlite.db");
-	pub(crate) static ref TEMP_DB_PATH: std::path::PathBuf =
-		RESOURCES_DIR.join("temp-sqlite.db");
 	pub(crate) static ref PRIVATE_KEY_PATH: std::path::PathBuf =
 		RESOURCES_DIR.join("private-key");
 	pub(crate) static ref PROOF_OF_WORK_DIFFICULTY_STRING: String =
 		"0".repeat(PROOF_OF_WORK_DIFFICULTY as usize);
+	/// How many threads search the nonce of the proof of work. Defaults to
+	/// the number of CPUs.
+	pub static ref MINING_THREADS: std::num::NonZeroUsize =
+		std::thread::available_parallelism()
+			.unwrap_or(std::num::NonZeroUsize::MIN);
 }
 
 pub const USER_TRANSACTIONS_PER_BLOCK: u8 = 2;
-pub const TRANSACTIONS_PER_BLOCK: u8 = USER_TRANSACTIONS_PER_BLOCK + 1;
-pub(crate) const GENESIS_BLOCK_REWARD: u64 = 100;
+/// The user transactions and the rewards of the miner and the treasury.
+pub const TRANSACTIONS_PER_BLOCK: u8 = USER_TRANSACTIONS_PER_BLOCK + 2;
 #[cfg(not(test))]
-pub(crate) const PROOF_OF_WORK_DIFFICULTY: u8 = 4;
+pub const PROOF_OF_WORK_DIFFICULTY: u8 = 4;
 #[cfg(test)]
-pub(crate) const PROOF_OF_WORK_DIFFICULTY: u8 = 2;
-pub(crate) const MINING_REWARD: std::num::NonZeroU64 =
-	unsafe { std::num::NonZeroU64::new_unchecked(1) };
+pub const PROOF_OF_WORK_DIFFICULTY: u8 = 2;
 
-pub(crate) const STORAGE_ADDRESS: &str = "STORAGE";
-pub(crate) const STORAGE_START_BALANCE: u64 = 100;
-pub(crate) const STORAGE_REWARD: u64 = 1;
-pub(crate) const STORAGE_REWARD_STARTING_FROM: u64 = 10;
+pub(crate) const STORAGE_ADDRESS: &str = common::consts::STORAGE_ADDRESS;
+pub const STORAGE_REWARD: u64 = 1;
+pub const STORAGE_REWARD_STARTING_FROM: u64 = 10;
+
+/// The beginning of the encrypted private key files, followed by the salt,
+/// the nonce and the ciphertext. The version is bumped when the layout is
+/// changed.
+pub(crate) const ENCRYPTED_KEY_MAGIC: &[u8] = b"CRYPTOCURRENCY-KEY-1";
+pub(crate) const ENCRYPTED_KEY_SALT_LEN: usize = 16;
+/// The length of the nonce of XChaCha20-Poly1305.
+pub(crate) const ENCRYPTED_KEY_NONCE_LEN: usize = 24;
+/// The environment variable with the passphrase of the encrypted private
+/// key. If it is not set, the passphrase is prompted.
+pub const PASSPHRASE_ENV: &str = "CRYPTO_PASSPHRASE";
+
+/// The beginning of the snapshot files, followed by the checksum. The
+/// version is bumped when the layout of the payload is changed.
+pub(crate) const SNAPSHOT_MAGIC: &[u8] = b"CRYPTOCURRENCY-SNAPSHOT-2";
+/// The length of the SHA-256 checksum of the snapshot payload.
+pub(crate) const SNAPSHOT_CHECKSUM_LEN: usize = 32;
 
 pub(crate) const DB_CREATE_TABLE_IF_NOT_EXISTS_QUERY: &str = "
 CREATE TABLE IF NOT EXISTS block (
 	id INTEGER PRIMARY KEY,
-	json TEXT
+	json TEXT,
+	hash TEXT,
+	height INTEGER
 )
 ";
-pub(crate) const DB_GET_COUNT_QUERY: &str = "SELECT COUNT(*) FROM block";
+/// The databases, which were created before the `hash` or `height` column,
+/// get it with the queries below.
+pub(crate) const DB_HAS_COLUMN_QUERY_TEMPLATE: &str =
+	"SELECT COUNT(*) FROM pragma_table_info('block') WHERE name = ?";
+pub(crate) const DB_ADD_HASH_COLUMN_QUERY: &str =
+	"ALTER TABLE block ADD COLUMN hash TEXT";
+pub(crate) const DB_ADD_HEIGHT_COLUMN_QUERY: &str =
+	"ALTER TABLE block ADD COLUMN height INTEGER";
+pub(crate) const DB_FILL_HEIGHTS_QUERY: &str =
+	"UPDATE block SET height = id - 1 WHERE height IS NULL";
+pub(crate) const DB_CREATE_HASH_INDEX_IF_NOT_EXISTS_QUERY: &str =
+	"CREATE INDEX IF NOT EXISTS block_hash ON block (hash)";
+pub(crate) const DB_CREATE_HEIGHT_INDEX_IF_NOT_EXISTS_QUERY: &str =
+	"CREATE UNIQUE INDEX IF NOT EXISTS block_height ON block (height)";
+pub(crate) const DB_GET_UNHASHED_QUERY: &str =
+	"SELECT id, json FROM block WHERE hash IS NULL";
+pub(crate) const DB_SET_HASH_QUERY_TEMPLATE: &str =
+	"UPDATE block SET hash = ? WHERE id = ?";
+/// The count is the next height, so the index is used instead of a scan.
+pub(crate) const DB_GET_COUNT_QUERY: &str =
+	"SELECT COALESCE(MAX(height) + 1, 0) FROM block";
 pub(crate) const DB_GET_ALL_QUERY: &str = "SELECT json FROM block ORDER BY id";
+pub(crate) const DB_GET_FIRST_QUERY: &str =
+	"SELECT json FROM block ORDER BY id LIMIT 1";
 pub(crate) const DB_GET_LAST_QUERY: &str =
 	"SELECT json FROM block ORDER BY id DESC LIMIT 1";
-pub(crate) const DB_INSERT_QUERY_TEMPLATE: &str =
-	"INSERT INTO block (json) VALUES (?)";
+pub(crate) const DB_GET_LAST_N_QUERY_TEMPLATE: &str =
+	"SELECT height, json FROM block ORDER BY id DESC LIMIT ?";
+pub(crate) const DB_GET_BY_HASH_QUERY_TEMPLATE: &str =
+	"SELECT json FROM block WHERE hash = ? ORDER BY id LIMIT 1";
+pub(crate) const DB_GET_HEIGHT_BY_HASH_QUERY_TEMPLATE: &str =
+	"SELECT height FROM block WHERE hash = ? ORDER BY id LIMIT 1";
+pub(crate) const DB_GET_BY_HEIGHT_QUERY_TEMPLATE: &str =
+	"SELECT json FROM block WHERE height = ?";
+/// The height is the id of the inserted row minus one.
+pub(crate) const DB_INSERT_QUERY_TEMPLATE: &str = "
+INSERT INTO block (height, hash, json)
+VALUES ((SELECT COALESCE(MAX(id), 0) FROM block), ?, ?)
+";
+pub(crate) const DB_REPLACE_QUERY_TEMPLATE: &str =
+	"UPDATE block SET hash = ?, json = ? WHERE height = ?";
+pub(crate) const DB_DELETE_FROM_QUERY_TEMPLATE: &str =
+	"DELETE FROM block WHERE height >= ?";
+pub(crate) const DB_BEGIN_QUERY: &str = "BEGIN";
+pub(crate) const DB_COMMIT_QUERY: &str = "COMMIT";
+pub(crate) const DB_ROLLBACK_QUERY: &str = "ROLLBACK";
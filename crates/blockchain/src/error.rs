@@ -9,15 +9,28 @@ pub enum AddBlockError {
 	GetBlocksCount(#[from] GetBlocksCountError),
 	#[error("Failed to validate integrity.")]
 	ValidateIntegrity(#[from] ValidateBlockIntegrityError),
+	#[error("Failed to validate genesis.")]
+	ValidateGenesis(#[from] ValidateBlockGenesisError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum AddBlocksError {
+	#[error("Failed to add block.")]
+	AddBlock(#[from] AddBlockError),
+	#[error("Failed to begin a db transaction.")]
+	BeginDbTransaction(#[source] BlockStoreError),
+	#[error("Failed to commit a db transaction.")]
+	CommitDbTransaction(#[source] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum AddBlockToDatabaseError {
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
-	#[error("Failed to execute a query.")]
-	ExecuteQuery(#[from] rusqlite::Error),
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to insert block to the store.")]
+	Insert(#[from] BlockStoreError),
 	#[error("Failed to convert block to JSON.")]
 	ToJson(#[from] serde_json::Error),
 }
@@ -36,12 +49,26 @@ pub enum AddTransactionError {
 	AddToRecipientBalance(#[source] AddToBalanceError),
 	#[error("Failed to add to storage's balance.")]
 	AddToStorageBalance(#[source] AddToBalanceError),
+	#[error("Transaction with the same random string is already pending.")]
+	Duplicate,
+	#[error("Failed to get the confirmed balance of the sender.")]
+	GetBalance(#[from] GetBalanceError),
 	#[error("Failed to get blocks count.")]
 	GetBlocksCount(#[from] GetBlocksCountError),
 	#[error("Limit reached.")]
 	LimitReached,
+	#[error(
+		"The pending transactions of the sender exceed its confirmed balance."
+	)]
+	PendingExceedsBalance,
+	#[error("Failed to prune the stale transactions.")]
+	PruneStaleTransactions(#[from] PruneStaleTransactionsError),
 	#[error("Failed to remove from balance.")]
 	RemoveFromBalance(#[from] RemoveFromBalanceError),
+	#[error("The pending transactions are being mined.")]
+	Sealed,
+	#[error("The amount with the amount to storage overflows.")]
+	TotalCostOverflow,
 	#[error("Failed to validate integrity.")]
 	ValidateIntegrity(#[from] ValidateTransactionIntegrityError),
 }
@@ -49,8 +76,10 @@ pub enum AddTransactionError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum BlockchainFromStrError {
-	#[error("Failed to add block.")]
-	AddBlock(#[from] AddBlockError),
+	#[error("Failed to add blocks.")]
+	AddBlocks(#[from] AddBlocksError),
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
 	#[error("Failed to convert JSON to blocks.")]
 	FromJson(#[from] serde_json::Error),
 	#[error("Failed to load or create the blockchain.")]
@@ -59,12 +88,25 @@ pub enum BlockchainFromStrError {
 	NewBlockchain(#[from] NewBlockchainError),
 	#[error("Failed to create a new connections pool to db.")]
 	NewDbPool(#[from] r2d2::Error),
+	#[error("Failed to create a new store.")]
+	NewStore(#[from] BlockStoreError),
 	#[error("Failed to remove temp db.")]
 	RemoveTempDb(#[source] std::io::Error),
 	#[error("Failed to replace db.")]
 	ReplaceDb(#[source] std::io::Error),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum BlockStoreError {
+	#[error("Failed to get a connection to db.")]
+	GetConnection(#[from] r2d2::Error),
+	#[error("Failed to query db.")]
+	QueryDb(#[from] rusqlite::Error),
+	#[error("Failed to access the store.")]
+	Other(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum BlockchainToStringError {
@@ -77,10 +119,14 @@ pub enum BlockchainToStringError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ComputeBlockHashError {
+	#[error("Failed to convert balance deltas to json.")]
+	BalanceDeltasToJson(#[source] serde_json::Error),
 	#[error("Failed to convert transactions to json.")]
 	TransactionsToJson(#[source] serde_json::Error),
 	#[error("Failed to convert balance state to json.")]
 	BalanceStateToJson(#[source] serde_json::Error),
+	#[error("Failed to convert hashed body to json.")]
+	BodyToJson(#[source] serde_json::Error),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -104,6 +150,10 @@ pub enum GenerateBlockProofOfWorkError {
 pub enum GetBalanceError {
 	#[error("Failed to get balance from database.")]
 	FromDatabase(#[from] GetBalanceFromDatabaseError),
+	#[error("Failed to get last block.")]
+	GetLastBlock(#[from] GetLastBlockError),
+	#[error("Failed to get last block hash.")]
+	GetLastBlockHash(#[from] GetLastBlockHashError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -111,6 +161,26 @@ pub enum GetBalanceError {
 pub enum GetBalanceFromDatabaseError {
 	#[error("Failed to get blocks.")]
 	GetBlocks(#[from] GetBlocksError),
+	#[error("The balance deltas sum up to a balance out of range.")]
+	InvalidDeltas,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetBlockByHashError {
+	#[error("Failed to convert JSON to block.")]
+	FromJson(#[from] serde_json::Error),
+	#[error("Failed to get block from the store.")]
+	Store(#[from] BlockStoreError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetBlockByHeightError {
+	#[error("Failed to convert JSON to block.")]
+	FromJson(#[from] serde_json::Error),
+	#[error("Failed to get block from the store.")]
+	Store(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -118,16 +188,10 @@ pub enum GetBalanceFromDatabaseError {
 pub enum GetBlockBeforeBlockError {
 	#[error("Failed to compute block hash.")]
 	ComputeBlockHash(#[from] ComputeBlockHashError),
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
 	#[error("Failed to convert JSON to block.")]
 	FromJson(#[from] serde_json::Error),
-	#[error("Failed to prepare db statement.")]
-	PrepareDbStatement(#[source] rusqlite::Error),
-	#[error("Failed to query db.")]
-	QueryDb(#[source] rusqlite::Error),
-	#[error("Failed to unwrap db result.")]
-	UnwrapDbResult(#[source] rusqlite::Error),
+	#[error("Failed to get blocks from the store.")]
+	Store(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -137,23 +201,44 @@ pub enum GetBlocksError {
 	ComputeBlockHash(#[from] ComputeBlockHashError),
 	#[error("Failed to convert JSON to block.")]
 	FromJson(#[from] serde_json::Error),
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
-	#[error("Failed to prepare db statement.")]
-	PrepareDbStatement(#[source] rusqlite::Error),
-	#[error("Failed to query db.")]
-	QueryDb(#[source] rusqlite::Error),
-	#[error("Failed to unwrap db result.")]
-	UnwrapDbResult(#[source] rusqlite::Error),
+	#[error("Failed to get blocks from the store.")]
+	Store(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum GetBlocksCountError {
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
-	#[error("Failed to query db.")]
-	QueryDb(#[from] rusqlite::Error),
+	#[error("Failed to count blocks in the store.")]
+	Store(#[from] BlockStoreError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetGenesisBlockError {
+	#[error("The blockchain is empty.")]
+	Empty,
+	#[error("Failed to convert JSON to block.")]
+	FromJson(#[from] serde_json::Error),
+	#[error("Failed to get block from the store.")]
+	Store(#[from] BlockStoreError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetGenesisBlockHashError {
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to get genesis block.")]
+	GetGenesisBlock(#[from] GetGenesisBlockError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GetLastBlockError {
+	#[error("Failed to convert JSON to block.")]
+	FromJson(#[from] serde_json::Error),
+	#[error("Failed to get block from the store.")]
+	Store(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -161,12 +246,27 @@ pub enum GetBlocksCountError {
 pub enum GetLastBlockHashError {
 	#[error("Failed to compute block hash.")]
 	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("The blockchain is empty.")]
+	Empty,
 	#[error("Failed to convert JSON to block.")]
 	FromJson(#[from] serde_json::Error),
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
-	#[error("Failed to query db.")]
-	QueryDb(#[from] rusqlite::Error),
+	#[error("Failed to get block from the store.")]
+	Store(#[from] BlockStoreError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum LoadBlockchainError {
+	#[error("The database has no blocks.")]
+	Empty,
+	#[error("Failed to get blocks count.")]
+	GetBlocksCount(#[from] GetBlocksCountError),
+	#[error("Failed to make a new blockchain.")]
+	New(#[from] NewBlockchainError),
+	#[error("There is no database.")]
+	NotFound,
+	#[error("Failed to open the store.")]
+	OpenStore(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -174,19 +274,29 @@ pub enum GetLastBlockHashError {
 pub enum LoadOrCreateBlockchainError {
 	#[error("Failed to make a new blockchain.")]
 	New(#[from] NewBlockchainError),
-	#[error("Failed to create a new connections pool to db.")]
-	NewDbPool(#[from] r2d2::Error),
+	#[error("Failed to open the store.")]
+	OpenStore(#[from] BlockStoreError),
 }
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum LoadOrCreateUserError {
+	#[error("Wrong passphrase or the key file is corrupted.")]
+	BadPassphrase,
+	#[error("The encrypted key file is malformed.")]
+	Decrypt,
+	#[error("Failed to derive a key from the passphrase.")]
+	DeriveKey(#[from] argon2::Error),
+	#[error("Failed to encrypt the key.")]
+	Encrypt,
 	#[error("Failed to convert bytes to user.")]
 	FromBytes(#[from] k256::ecdsa::Error),
 	#[error("Failed to make new user.")]
 	New(#[from] NewUserError),
 	#[error("Failed to read.")]
 	Read(#[source] std::io::Error),
+	#[error("Failed to read the passphrase.")]
+	ReadPassphrase(#[source] std::io::Error),
 	#[error("Failed to write.")]
 	Write(#[source] std::io::Error),
 }
@@ -205,16 +315,30 @@ pub enum MakeStorageTransactionError {
 pub enum MineBlockError {
 	#[error("Failed to add a block.")]
 	AddBlock(#[from] AddBlockError),
+	#[error("Failed to add the fees to the miner balance.")]
+	AddFeesToMinerBalance(#[source] AddToBalanceError),
+	#[error("The sum of the fees overflows.")]
+	FeesOverflow,
 	#[error("Failed to generate block's proof of work.")]
 	GenerateBlockProofOfWork(#[from] GenerateBlockProofOfWorkError),
 	#[error("Failed to get blocks count.")]
 	GetBlocksCount(#[from] GetBlocksCountError),
 	#[error("Failed to get last block hash.")]
 	GetLastBlockHash(#[from] GetLastBlockHashError),
+	#[error("Failed to get balance.")]
+	GetBalance(#[from] GetBalanceError),
 	#[error("Failed to make storage transaction.")]
 	MakeStorageTransaction(#[from] MakeStorageTransactionError),
+	#[error("All pending transactions are stale.")]
+	NoPendingTransactions,
+	#[error("Failed to parse the reward recipient.")]
+	ParseRewardRecipient(#[source] ParseAddressError),
+	#[error("Failed to prune the stale transactions.")]
+	PruneStaleTransactions(#[from] PruneStaleTransactionsError),
 	#[error("Failed to sign a block.")]
 	SignBlock(#[from] SignBlockError),
+	#[error("Storage balance is not enough for the mining reward.")]
+	StorageInsolvent,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -235,10 +359,10 @@ pub enum MineGenesisBlockError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum NewBlockchainError {
-	#[error("Failed to get a connection to db.")]
-	GetConnection(#[from] r2d2::Error),
-	#[error("Failed to execute a query.")]
-	ExecuteQuery(#[from] rusqlite::Error),
+	#[error("Minimum user transactions per block is greater than the limit.")]
+	MinUserTransactionsPerBlockTooBig,
+	#[error("Failed to parse the treasury address.")]
+	ParseTreasury(#[source] ParseAddressError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -248,6 +372,45 @@ pub enum NewUserError {
 	ConvertPublicKeyToAdress(#[from] ConvertPublicKeyToAddressError),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ParseAddressError {
+	#[error("Failed to convert base58 to bytes.")]
+	FromBase58(base58::FromBase58Error),
+	#[error("Invalid checksum.")]
+	InvalidChecksum,
+	#[error("Invalid length of the Base58 string.")]
+	InvalidEncodedLen,
+	#[error("Invalid length.")]
+	InvalidLen,
+	#[error("Invalid network byte.")]
+	InvalidNetworkByte,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum PruneStaleTransactionsError {
+	#[error("Failed to get last block hash.")]
+	GetLastBlockHash(#[from] GetLastBlockHashError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RecomputeBalanceStatesError {
+	#[error("Add overflow.")]
+	AddOverflow,
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to get blocks.")]
+	GetBlocks(#[from] GetBlocksError),
+	#[error("Failed to replace block in the store.")]
+	ReplaceBlock(#[from] BlockStoreError),
+	#[error("Sub overflow.")]
+	SubOverflow,
+	#[error("Failed to convert block to JSON.")]
+	ToJson(#[from] serde_json::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum RemoveFromBalanceError {
@@ -257,6 +420,34 @@ pub enum RemoveFromBalanceError {
 	NotEnoughMoney,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RestoreFromFileError {
+	#[error("Snapshot checksum is not equal to the payload one.")]
+	ChecksumNotEquals,
+	#[error("Failed to decode blocks.")]
+	Decode(#[from] bincode::Error),
+	#[error("Failed to import blocks.")]
+	Import(#[from] BlockchainFromStrError),
+	#[error("File is not a snapshot.")]
+	InvalidMagic,
+	#[error("Failed to load or create the blockchain.")]
+	LoadOrCreateBlockchain(#[from] LoadOrCreateBlockchainError),
+	#[error("Failed to read snapshot.")]
+	Read(#[source] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SaveUserError {
+	#[error("Failed to encrypt the key.")]
+	Encrypt(#[source] LoadOrCreateUserError),
+	#[error("The key already exists.")]
+	Exists,
+	#[error("Failed to write.")]
+	Write(#[source] std::io::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum SignBlockError {
@@ -273,6 +464,68 @@ pub enum SignTransactionError {
 	UserSign(#[from] UserSignError),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum SnapshotToFileError {
+	#[error("Failed to encode blocks.")]
+	Encode(#[from] bincode::Error),
+	#[error("Failed to get blocks.")]
+	GetBlocks(#[from] GetBlocksError),
+	#[error("Failed to write snapshot.")]
+	Write(#[source] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TieBreakError {
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Blockchain is empty.")]
+	Empty,
+	#[error("Failed to get blocks count.")]
+	GetBlocksCount(#[from] GetBlocksCountError),
+	#[error("Failed to get last block.")]
+	GetLastBlock(#[from] GetLastBlockError),
+	#[error("Failed to switch to the block.")]
+	Switch(#[source] TryReorgError),
+	#[error("Failed to validate the block without the chain.")]
+	ValidateStandalone(#[from] ValidateBlockStandaloneError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum TryReorgError {
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to convert JSON to block.")]
+	FromJson(#[from] serde_json::Error),
+	#[error("Failed to get blocks count.")]
+	GetBlocksCount(#[from] GetBlocksCountError),
+	#[error("The branch is invalid.")]
+	InvalidBranch(#[source] AddBlockError),
+	#[error("Failed to create the blockchain to validate the branch.")]
+	NewBlockchain(#[from] NewBlockchainError),
+	#[error("Failed to access the store.")]
+	Store(#[from] BlockStoreError),
+	#[error("The branch does not start from a stored block.")]
+	UnknownForkPoint,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateBlockAtError {
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to get block by height.")]
+	GetBlockByHeight(#[from] GetBlockByHeightError),
+	#[error("There is no block at {0}.")]
+	NotFound(usize),
+	#[error("Failed to validate genesis block.")]
+	ValidateGenesis(#[from] ValidateBlockGenesisError),
+	#[error("Failed to validate block integrity.")]
+	ValidateIntegrity(#[from] ValidateBlockIntegrityError),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateBlockBalanceStateError {
@@ -280,14 +533,23 @@ pub enum ValidateBlockBalanceStateError {
 	AddOverflow,
 	#[error("Balances are not equals.")]
 	BalancesNotEquals,
-	#[error("Failed to get balance from database before block.")]
-	GetBalanceFromDatabase(#[from] GetBalanceFromDatabaseError),
+	#[error("The change of the balance overflows.")]
+	ChangeOverflow,
+	#[error("Failed to get balance before block.")]
+	GetBalance(#[from] GetBalanceError),
 	#[error("Not balance in state.")]
 	NoBalanceInState,
 	#[error("Sub overflow.")]
 	SubOverflow,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateBlockChainIdError {
+	#[error("Chain id is not equal to the consensus one.")]
+	NotEquals,
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateBlockCreatedAtError {
@@ -299,13 +561,26 @@ pub enum ValidateBlockCreatedAtError {
 	PreviousInFuture,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateBlockGenesisError {
+	#[error("Balance state is not equal to the consensus one.")]
+	BalanceStateNotEquals,
+	#[error("Failed to validate chain id.")]
+	ChainId(#[from] ValidateBlockChainIdError),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateBlockIntegrityError {
+	#[error("Failed to validate chain id.")]
+	ValidateChainId(#[from] ValidateBlockChainIdError),
 	#[error("Failed to validate timestamp.")]
 	ValidateCreatedAt(#[from] ValidateBlockCreatedAtError),
 	#[error("Failed to validate that is signed.")]
 	ValidateIsSigned(#[from] ValidateBlockIsSignedError),
+	#[error("Failed to validate miner.")]
+	ValidateMiner(#[from] ValidateBlockMinerError),
 	#[error("Failed to validate miner signature.")]
 	ValidateMinerSignature(#[from] ValidateBlockMinerSignatureError),
 	#[error("Failed to validate previous hash.")]
@@ -323,6 +598,17 @@ pub enum ValidateBlockIsSignedError {
 	NotSigned,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateBlockMinerError {
+	#[error("Failed to get blocks count.")]
+	GetBlocksCount(#[from] GetBlocksCountError),
+	#[error("Miner is not authorized.")]
+	NotAuthorized,
+	#[error("It is the turn of {0} to mine.")]
+	NotScheduled(String),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateBlockMinerSignatureError {
@@ -350,6 +636,19 @@ pub enum ValidateBlockProofOfWorkError {
 	Invalid,
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateBlockStandaloneError {
+	#[error("Failed to validate chain id.")]
+	ChainId(#[from] ValidateBlockChainIdError),
+	#[error("Failed to validate that is signed.")]
+	IsSigned(#[from] ValidateBlockIsSignedError),
+	#[error("Failed to validate miner signature.")]
+	MinerSignature(#[from] ValidateBlockMinerSignatureError),
+	#[error("Failed to validate proof of work.")]
+	ProofOfWork(#[from] ValidateBlockProofOfWorkError),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateBlockTransactionsError {
@@ -359,12 +658,24 @@ pub enum ValidateBlockTransactionsError {
 	InvalidStorageCount,
 	#[error("Invalid user transactions count.")]
 	InvalidUserCount,
+	#[error("Merkle root is not equals to the root of the transactions.")]
+	MerkleRootNotEquals,
 	#[error("Previous hashes are not equals.")]
 	PreviousHashesNotEquals,
 	#[error("Random string is not unique.")]
 	RandomStringNotUnique,
 	#[error("Rewarded user is not a miner.")]
 	RewardedNotMiner,
+	#[error("Rewarded user is not a treasury.")]
+	RewardedNotTreasury,
+	#[error("Balance deltas are stored, but the consensus has full states.")]
+	UnexpectedBalanceDeltas,
+	#[error("Balance state is stored, but the consensus has deltas.")]
+	UnexpectedBalanceState,
+	#[error(
+		"Balance state has the address {0}, which is not in transactions."
+	)]
+	UntouchedBalanceState(String),
 	#[error("Failed to validate recipient's balance state.")]
 	ValidateRecipientBalanceState(#[source] ValidateBlockBalanceStateError),
 	#[error("Failed to validate sender's balance state.")]
@@ -373,9 +684,50 @@ pub enum ValidateBlockTransactionsError {
 	ValidateTransactionIntegrity(#[from] ValidateTransactionIntegrityError),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateStoredChainError {
+	#[error("Failed to compute block hash.")]
+	ComputeBlockHash(#[from] ComputeBlockHashError),
+	#[error("Failed to get blocks.")]
+	GetBlocks(#[from] GetBlocksError),
+	#[error("Blocks are not linked by hashes.")]
+	NotLinked,
+	#[error("Failed to validate chain id.")]
+	ValidateChainId(#[from] ValidateBlockChainIdError),
+	#[error("Failed to validate that is signed.")]
+	ValidateIsSigned(#[from] ValidateBlockIsSignedError),
+	#[error("Failed to validate miner signature.")]
+	ValidateMinerSignature(#[from] ValidateBlockMinerSignatureError),
+	#[error("Failed to validate proof of work.")]
+	ValidateProofOfWork(#[from] ValidateBlockProofOfWorkError),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateTransactionAmountError {
+	#[error("Amount is less than the minimum.")]
+	LessThanMinimum,
+	#[error("Amount to storage is greater than the storage reward.")]
+	StorageAmountTooLarge,
+	#[error("Total cost overflows.")]
+	TotalCostOverflow,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidateTransactionChainIdError {
+	#[error("Chain id is not equal to the consensus one.")]
+	NotEquals,
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateTransactionIntegrityError {
+	#[error("Failed to vaidate amount.")]
+	Amount(#[from] ValidateTransactionAmountError),
+	#[error("Failed to validate chain id.")]
+	ChainId(#[from] ValidateTransactionChainIdError),
 	#[error("Failed to vaidate previous block hash.")]
 	PreviousBlockHash(#[from] ValidateTransactionPreviousBlockHashError),
 	#[error("Failed to vaidate recipient.")]
@@ -396,6 +748,8 @@ pub enum ValidateTransactionPreviousBlockHashError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateTransactionRecipientError {
+	#[error("Recipient is sender.")]
+	IsSender,
 	#[error("Recipient is storage.")]
 	IsStorage,
 }
@@ -426,9 +780,31 @@ pub enum ValidateUserSignatureError {
 	Verify(#[source] k256::ecdsa::Error),
 }
 
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum UserFromMnemonicError {
+	#[error("The seed does not give a valid private key.")]
+	InvalidKey(#[from] k256::ecdsa::Error),
+	#[error("The mnemonic has {0} words instead of 12 or 24.")]
+	InvalidWordCount(usize),
+	#[error("Failed to make new user.")]
+	New(#[from] NewUserError),
+	#[error("Failed to parse the mnemonic.")]
+	Parse(#[from] bip39::Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum UserSignError {
 	#[error("Failed to sign.")]
 	Sign(#[from] k256::ecdsa::Error),
 }
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum VerifyTransactionSignatureOnlyError {
+	#[error("Failed to validate recipient.")]
+	Recipient(#[from] ValidateTransactionRecipientError),
+	#[error("Failed to validate sender signature.")]
+	SenderSignature(#[from] ValidateTransactionSenderSignatureError),
+}
@@ -1,5 +1,105 @@
 pub type BalanceState = std::collections::BTreeMap<String, u64>;
 
+/// Serializes balances of a `BalanceState` as strings, so they do not lose
+/// precision in JSON parsers which read numbers as doubles. Balances as
+/// numbers are still accepted on deserialization.
+///
+/// The binary formats, like the snapshots, keep the balances as numbers.
+pub(crate) mod balance_state_serde {
+	pub(crate) fn serialize<S>(
+		state: &super::BalanceState,
+		serializer: S,
+	) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		use serde::Serialize as _;
+
+		if !serializer.is_human_readable() {
+			return state.serialize(serializer);
+		}
+		serializer.collect_map(state.iter().map(|(a, b)| (a, b.to_string())))
+	}
+
+	pub(crate) fn deserialize<'de, D>(
+		deserializer: D,
+	) -> Result<super::BalanceState, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		use serde::{de::Error as _, Deserialize as _};
+
+		#[derive(serde::Deserialize)]
+		#[serde(untagged)]
+		enum Balance {
+			Number(u64),
+			String(String),
+		}
+
+		if !deserializer.is_human_readable() {
+			return super::BalanceState::deserialize(deserializer);
+		}
+
+		std::collections::BTreeMap::<String, Balance>::deserialize(
+			deserializer,
+		)?
+		.into_iter()
+		.map(|(address, balance)| {
+			let balance = match balance {
+				Balance::Number(n) => n,
+				Balance::String(s) => s.parse().map_err(D::Error::custom)?,
+			};
+			Ok((address, balance))
+		})
+		.collect()
+	}
+}
+
+/// The changes of the balances in a block. They are signed, because a
+/// balance may decrease.
+pub type BalanceDeltas = std::collections::BTreeMap<String, i128>;
+
+/// Same as [`balance_state_serde`], but for the `BalanceDeltas`, which are
+/// always strings in the human readable formats.
+pub(crate) mod balance_deltas_serde {
+	pub(crate) fn serialize<S>(
+		deltas: &super::BalanceDeltas,
+		serializer: S,
+	) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		use serde::Serialize as _;
+
+		if !serializer.is_human_readable() {
+			return deltas.serialize(serializer);
+		}
+		serializer.collect_map(deltas.iter().map(|(a, d)| (a, d.to_string())))
+	}
+
+	pub(crate) fn deserialize<'de, D>(
+		deserializer: D,
+	) -> Result<super::BalanceDeltas, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		use serde::{de::Error as _, Deserialize as _};
+
+		if !deserializer.is_human_readable() {
+			return super::BalanceDeltas::deserialize(deserializer);
+		}
+
+		std::collections::BTreeMap::<String, String>::deserialize(
+			deserializer,
+		)?
+		.into_iter()
+		.map(|(address, delta)| {
+			Ok((address, delta.parse().map_err(D::Error::custom)?))
+		})
+		.collect()
+	}
+}
+
 /// Gets the checksum of the `data`: Hashes SHA-256 twice, then gets the hex
 /// and returns the first 8 characters.
 #[must_use]
@@ -22,10 +122,19 @@ pub(crate) fn generate_random_string() -> String {
 /// Returns Unix timestamp.
 #[must_use]
 pub(crate) fn get_timestamp() -> f64 {
-	std::time::SystemTime::now()
-		.duration_since(std::time::SystemTime::UNIX_EPOCH)
-		.expect("`std::time::SystemTime` before the Unix epoch!")
-		.as_secs_f64()
+	get_timestamp_of(std::time::SystemTime::now())
+}
+
+/// Returns Unix timestamp of the `time`. A time before the Unix epoch is
+/// saturated to `0.0`, because a misconfigured clock must not crash a node.
+#[must_use]
+fn get_timestamp_of(time: std::time::SystemTime) -> f64 {
+	if let Ok(d) = time.duration_since(std::time::SystemTime::UNIX_EPOCH) {
+		d.as_secs_f64()
+	} else {
+		tracing::warn!("`std::time::SystemTime` before the Unix epoch!");
+		0.0
+	}
 }
 
 #[cfg(test)]
@@ -35,9 +144,16 @@ mod tests {
 		assert_eq!(super::get_checksum(b"1"), "9c2e4d8f");
 	}
 
+	#[test]
+	fn test_get_timestamp_of_before_epoch() {
+		let time = std::time::SystemTime::UNIX_EPOCH
+			- std::time::Duration::from_secs(1);
+		assert!(super::get_timestamp_of(time) == 0.0);
+	}
+
 	#[test]
 	fn test_generate_random_string() {
-		let mut results = std::collectionsHashSet::new();
+		let mut results = std::collections::HashSet::new();
 		for _ in 0..50 {
 			results.insert(super::generate_random_string());
 		}
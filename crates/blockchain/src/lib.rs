@@ -10,15 +10,19 @@
 	clippy::as_conversions,
 	clippy::implicit_return,
 	clippy::missing_docs_in_private_items,
-	clippy::missing_errors_doc
+	clippy::missing_errors_doc,
+	clippy::non_std_lazy_statics
 )]
 
+pub mod address;
 pub mod block;
 pub mod blockchain;
 pub mod consts;
 pub mod error;
 mod helpers;
 mod preparing_block_state;
+pub mod progress;
+pub mod store;
 #[cfg(test)]
 mod test_helpers;
 pub mod transaction;
@@ -4,6 +4,10 @@
 pub struct PreparingBlockState<'a> {
 	pub(crate) transactions: crate::block::Transactions<'a>,
 	pub(crate) balance_state: crate::helpers::BalanceState,
+	/// The sums of the costs of the `self.transactions` by their senders.
+	pub(crate) outgoing: std::collections::HashMap<String, u64>,
+	/// When the oldest of the `self.transactions` was added.
+	pub(crate) pending_since: Option<std::time::Instant>,
 }
 
 impl PreparingBlockState<'_> {
@@ -13,12 +17,16 @@ impl PreparingBlockState<'_> {
 		Self {
 			transactions: crate::block::Transactions::new(),
 			balance_state: crate::helpers::BalanceState::new(),
+			outgoing: std::collections::HashMap::new(),
+			pending_since: None,
 		}
 	}
 
 	pub fn clear(&mut self) {
 		self.transactions.clear();
 		self.balance_state.clear();
+		self.outgoing.clear();
+		self.pending_since = None;
 	}
 
 	#[must_use]
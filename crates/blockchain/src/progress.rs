@@ -0,0 +1,35 @@
+/// Reports the progress of a long operation over the blocks, like an import
+/// or a validation, so it can be rendered as a progress bar.
+///
+/// The callback is called with the numbers of the processed blocks and of all
+/// blocks every `every` blocks and after the last one. It may be called while
+/// the database IO lock is held, so it must not take any locks itself.
+pub struct Progress<'f> {
+	every: std::num::NonZeroUsize,
+	callback: &'f mut dyn FnMut(usize, usize),
+}
+
+impl<'f> Progress<'f> {
+	#[inline]
+	#[must_use]
+	pub fn new(
+		every: std::num::NonZeroUsize,
+		callback: &'f mut dyn FnMut(usize, usize),
+	) -> Self {
+		Self { every, callback }
+	}
+
+	/// Calls the callback if `processed` is a multiple of `self.every` or is
+	/// the `total`.
+	pub(crate) fn report(&mut self, processed: usize, total: usize) {
+		if processed.is_multiple_of(self.every.get()) || processed == total {
+			(self.callback)(processed, total);
+		}
+	}
+}
+
+impl std::fmt::Debug for Progress<'_> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Progress").field("every", &self.every).finish()
+	}
+}
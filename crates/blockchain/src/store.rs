@@ -0,0 +1,484 @@
+use crate::error::BlockStoreError;
+
+type DbPool = r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>;
+
+/// Storage of the blocks of a [`Blockchain`](crate::Blockchain). The blocks
+/// are stored as JSONs in the order of their addition, with their hashes to
+/// be found by them.
+///
+/// The clones of a store must share its blocks, because a blockchain is
+/// cloned to be mined without blocking it.
+pub trait BlockStore: Clone {
+	/// Appends the block `json` with the `hash` to the end.
+	fn insert_block(
+		&self,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError>;
+
+	/// Gets the first block JSON with the `hash` or `None` if there is no
+	/// such block.
+	fn get_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<String>, BlockStoreError>;
+
+	/// Gets the height of the first block with the `hash` or `None` if there
+	/// is no such block.
+	fn get_height_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<usize>, BlockStoreError>;
+
+	/// Gets the block JSON at `height` or `None` if there is no such block.
+	/// The height of the genesis block is zero.
+	fn get_by_height(
+		&self,
+		height: usize,
+	) -> Result<Option<String>, BlockStoreError> {
+		Ok(self.get_all()?.into_iter().nth(height))
+	}
+
+	/// Gets all block JSONs, starting from the genesis one.
+	fn get_all(&self) -> Result<Vec<String>, BlockStoreError>;
+
+	/// Gets the last block JSON or `None` if the store is empty.
+	fn get_last(&self) -> Result<Option<String>, BlockStoreError>;
+
+	fn count(&self) -> Result<usize, BlockStoreError>;
+
+	/// Replaces the block JSON and its `hash` at `height`. The height of the
+	/// genesis block is zero.
+	fn replace_block(
+		&self,
+		height: usize,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError>;
+
+	/// Removes the blocks from `height` on and appends the `blocks`, which
+	/// are pairs of a hash and a JSON. Nothing is changed if it fails.
+	fn replace_from(
+		&self,
+		height: usize,
+		blocks: Vec<(String, String)>,
+	) -> Result<(), BlockStoreError>;
+
+	/// Gets the genesis block JSON or `None` if the store is empty.
+	fn get_first(&self) -> Result<Option<String>, BlockStoreError> {
+		Ok(self.get_all()?.into_iter().next())
+	}
+
+	/// Gets the last `n` block JSONs with their heights, starting from the
+	/// last one. The height of the genesis block is zero.
+	fn get_last_n(
+		&self,
+		n: usize,
+	) -> Result<Vec<(usize, String)>, BlockStoreError> {
+		Ok(self.get_all()?.into_iter().enumerate().rev().take(n).collect())
+	}
+}
+
+/// The default store, which keeps the blocks in the `SQLite` database.
+#[derive(Clone)]
+pub struct SqliteStore {
+	db_pool: DbPool,
+}
+
+impl SqliteStore {
+	/// Opens the database file in the `path` or creates it.
+	#[inline]
+	pub fn open(path: &std::path::Path) -> Result<Self, BlockStoreError> {
+		Self::new(r2d2::Pool::new(
+			r2d2_sqlite::SqliteConnectionManager::file(path),
+		)?)
+	}
+
+	/// Creates the blocks table in the `db_pool` database if it does not
+	/// exist. The `hash` and `height` columns are added to the older table
+	/// and filled.
+	pub(crate) fn new(db_pool: DbPool) -> Result<Self, BlockStoreError> {
+		let connection = db_pool.get()?;
+		connection
+			.execute(crate::consts::DB_CREATE_TABLE_IF_NOT_EXISTS_QUERY, [])?;
+		for (column, query) in [
+			("hash", crate::consts::DB_ADD_HASH_COLUMN_QUERY),
+			("height", crate::consts::DB_ADD_HEIGHT_COLUMN_QUERY),
+		] {
+			let has_column: bool = connection.query_row(
+				crate::consts::DB_HAS_COLUMN_QUERY_TEMPLATE,
+				[column],
+				|row| row.get(0),
+			)?;
+			if !has_column {
+				connection.execute(query, [])?;
+			}
+		}
+		connection.execute(crate::consts::DB_FILL_HEIGHTS_QUERY, [])?;
+		connection.execute(
+			crate::consts::DB_CREATE_HASH_INDEX_IF_NOT_EXISTS_QUERY,
+			[],
+		)?;
+		connection.execute(
+			crate::consts::DB_CREATE_HEIGHT_INDEX_IF_NOT_EXISTS_QUERY,
+			[],
+		)?;
+		Self::fill_hashes(&connection)?;
+		drop(connection);
+		Ok(Self { db_pool })
+	}
+
+	/// Computes the hashes of the blocks which have none.
+	fn fill_hashes(
+		connection: &rusqlite::Connection,
+	) -> Result<(), BlockStoreError> {
+		let unhashed: Vec<(i64, String)> = connection
+			.prepare(crate::consts::DB_GET_UNHASHED_QUERY)?
+			.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+			.collect::<Result<_, _>>()?;
+		for (id, json) in unhashed {
+			let hash = serde_json::from_str::<crate::block::Block>(&json)
+				.map_err(|e| BlockStoreError::Other(e.into()))?
+				.compute_hash()
+				.map_err(|e| BlockStoreError::Other(e.into()))?;
+			connection.execute(
+				crate::consts::DB_SET_HASH_QUERY_TEMPLATE,
+				rusqlite::params![hash, id],
+			)?;
+		}
+		Ok(())
+	}
+
+	/// Executes the `query` which begins, commits or rolls back a database
+	/// transaction. Only makes sense if `self.db_pool` has one connection.
+	pub(crate) fn execute_batch(
+		&self,
+		query: &str,
+	) -> Result<(), BlockStoreError> {
+		debug_assert_eq!(self.db_pool.max_size(), 1);
+		self.db_pool.get()?.execute_batch(query)?;
+		Ok(())
+	}
+}
+
+impl BlockStore for SqliteStore {
+	fn insert_block(
+		&self,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError> {
+		self.db_pool
+			.get()?
+			.execute(crate::consts::DB_INSERT_QUERY_TEMPLATE, [hash, json])?;
+		Ok(())
+	}
+
+	fn get_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<String>, BlockStoreError> {
+		use rusqlite::OptionalExtension as _;
+
+		let json = self
+			.db_pool
+			.get()?
+			.query_row(
+				crate::consts::DB_GET_BY_HASH_QUERY_TEMPLATE,
+				[hash],
+				|row| row.get(0),
+			)
+			.optional()?;
+		Ok(json)
+	}
+
+	fn get_height_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<usize>, BlockStoreError> {
+		use rusqlite::OptionalExtension as _;
+
+		let height = self
+			.db_pool
+			.get()?
+			.query_row(
+				crate::consts::DB_GET_HEIGHT_BY_HASH_QUERY_TEMPLATE,
+				[hash],
+				|row| row.get(0),
+			)
+			.optional()?;
+		Ok(height)
+	}
+
+	fn get_by_height(
+		&self,
+		height: usize,
+	) -> Result<Option<String>, BlockStoreError> {
+		use rusqlite::OptionalExtension as _;
+
+		let json = self
+			.db_pool
+			.get()?
+			.query_row(
+				crate::consts::DB_GET_BY_HEIGHT_QUERY_TEMPLATE,
+				[height],
+				|row| row.get(0),
+			)
+			.optional()?;
+		Ok(json)
+	}
+
+	fn get_all(&self) -> Result<Vec<String>, BlockStoreError> {
+		let connection = self.db_pool.get()?;
+		let mut statement =
+			connection.prepare(crate::consts::DB_GET_ALL_QUERY)?;
+		let jsons = statement
+			.query_map([], |row| row.get(0))?
+			.collect::<Result<_, _>>()?;
+		Ok(jsons)
+	}
+
+	fn get_last(&self) -> Result<Option<String>, BlockStoreError> {
+		use rusqlite::OptionalExtension as _;
+
+		let json = self
+			.db_pool
+			.get()?
+			.query_row(crate::consts::DB_GET_LAST_QUERY, [], |row| row.get(0))
+			.optional()?;
+		Ok(json)
+	}
+
+	fn count(&self) -> Result<usize, BlockStoreError> {
+		let count = self.db_pool.get()?.query_row(
+			crate::consts::DB_GET_COUNT_QUERY,
+			[],
+			|row| row.get(0),
+		)?;
+		Ok(count)
+	}
+
+	fn replace_block(
+		&self,
+		height: usize,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError> {
+		self.db_pool.get()?.execute(
+			crate::consts::DB_REPLACE_QUERY_TEMPLATE,
+			rusqlite::params![hash, json, height],
+		)?;
+		Ok(())
+	}
+
+	fn replace_from(
+		&self,
+		height: usize,
+		blocks: Vec<(String, String)>,
+	) -> Result<(), BlockStoreError> {
+		let mut connection = self.db_pool.get()?;
+		let transaction = connection.transaction()?;
+		transaction
+			.execute(crate::consts::DB_DELETE_FROM_QUERY_TEMPLATE, [height])?;
+		for (hash, json) in blocks {
+			transaction.execute(
+				crate::consts::DB_INSERT_QUERY_TEMPLATE,
+				[hash, json],
+			)?;
+		}
+		transaction.commit()?;
+		Ok(())
+	}
+
+	fn get_first(&self) -> Result<Option<String>, BlockStoreError> {
+		use rusqlite::OptionalExtension as _;
+
+		let json = self
+			.db_pool
+			.get()?
+			.query_row(crate::consts::DB_GET_FIRST_QUERY, [], |row| row.get(0))
+			.optional()?;
+		Ok(json)
+	}
+
+	fn get_last_n(
+		&self,
+		n: usize,
+	) -> Result<Vec<(usize, String)>, BlockStoreError> {
+		let connection = self.db_pool.get()?;
+		let mut statement =
+			connection.prepare(crate::consts::DB_GET_LAST_N_QUERY_TEMPLATE)?;
+		let rows = statement
+			.query_map([n], |row| Ok((row.get(0)?, row.get(1)?)))?
+			.collect::<Result<_, _>>()?;
+		Ok(rows)
+	}
+}
+
+/// The store which keeps the hashes and the JSONs of the blocks in memory,
+/// so they are lost with the last clone of it.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+	blocks: std::sync::Arc<std::sync::Mutex<Vec<(String, String)>>>,
+}
+
+impl BlockStore for MemoryStore {
+	fn insert_block(
+		&self,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError> {
+		self.blocks.lock().unwrap().push((hash, json));
+		Ok(())
+	}
+
+	fn get_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<String>, BlockStoreError> {
+		Ok(self
+			.blocks
+			.lock()
+			.unwrap()
+			.iter()
+			.find(|(h, _)| h == hash)
+			.map(|(_, json)| json.clone()))
+	}
+
+	fn get_height_by_hash(
+		&self,
+		hash: &str,
+	) -> Result<Option<usize>, BlockStoreError> {
+		Ok(self.blocks.lock().unwrap().iter().position(|(h, _)| h == hash))
+	}
+
+	fn get_by_height(
+		&self,
+		height: usize,
+	) -> Result<Option<String>, BlockStoreError> {
+		Ok(self.blocks.lock().unwrap().get(height).map(|(_, j)| j.clone()))
+	}
+
+	fn get_all(&self) -> Result<Vec<String>, BlockStoreError> {
+		Ok(self
+			.blocks
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(_, j)| j.clone())
+			.collect())
+	}
+
+	fn get_last(&self) -> Result<Option<String>, BlockStoreError> {
+		Ok(self.blocks.lock().unwrap().last().map(|(_, j)| j.clone()))
+	}
+
+	fn count(&self) -> Result<usize, BlockStoreError> {
+		Ok(self.blocks.lock().unwrap().len())
+	}
+
+	fn replace_block(
+		&self,
+		height: usize,
+		hash: String,
+		json: String,
+	) -> Result<(), BlockStoreError> {
+		if let Some(block) = self.blocks.lock().unwrap().get_mut(height) {
+			*block = (hash, json);
+		}
+		Ok(())
+	}
+
+	fn replace_from(
+		&self,
+		height: usize,
+		blocks: Vec<(String, String)>,
+	) -> Result<(), BlockStoreError> {
+		let mut stored = self.blocks.lock().unwrap();
+		stored.truncate(height);
+		stored.extend(blocks);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_mine_with_memory_store() {
+		use super::BlockStore as _;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let store = super::MemoryStore::default();
+		let mut blockchain = crate::blockchain::Blockchain::new(
+			crate::test_helpers::create_test_user(),
+			common::config::Consensus::default(),
+			store.clone(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		let recipient = transaction.recipient().to_string();
+		blockchain.add_transaction(transaction).unwrap();
+		let block = blockchain.mine_block().unwrap();
+
+		// The blocks are shared with the store and valid
+		assert_eq!(store.count().unwrap(), 2);
+		assert_eq!(
+			blockchain.get_last_block_hash().unwrap(),
+			block.compute_hash().unwrap()
+		);
+		blockchain.validate_stored_chain().unwrap();
+		assert_eq!(blockchain.get_balance(&recipient).unwrap(), 5);
+		let heights: Vec<usize> = blockchain
+			.recent_block_stats(2)
+			.unwrap()
+			.iter()
+			.map(crate::block::BlockStat::height)
+			.collect();
+		assert_eq!(heights, [0, 1]);
+	}
+
+	#[test]
+	fn test_add_columns() {
+		use super::BlockStore as _;
+
+		let (_, block) = crate::test_helpers::create_test_block();
+		let path = std::env::temp_dir()
+			.join(format!("{}.db", crate::helpers::generate_random_string()));
+		let pool =
+			r2d2::Pool::new(r2d2_sqlite::SqliteConnectionManager::file(path))
+				.unwrap();
+		// The table of the older versions
+		let connection = pool.get().unwrap();
+		connection
+			.execute(
+				"CREATE TABLE block (id INTEGER PRIMARY KEY, json TEXT)",
+				[],
+			)
+			.unwrap();
+		connection
+			.execute(
+				"INSERT INTO block (json) VALUES (?)",
+				[serde_json::to_string(&block).unwrap()],
+			)
+			.unwrap();
+		drop(connection);
+
+		let store = super::SqliteStore::new(pool).unwrap();
+		let hash = block.compute_hash().unwrap();
+		assert_eq!(
+			store.get_by_hash(&hash).unwrap(),
+			store.get_first().unwrap()
+		);
+		assert!(store.get_by_hash("unknown").unwrap().is_none());
+		// The heights are filled, so the next block follows the old one
+		assert_eq!(
+			store.get_by_height(0).unwrap(),
+			store.get_first().unwrap()
+		);
+		store.insert_block("next".to_string(), "{}".to_string()).unwrap();
+		assert_eq!(store.count().unwrap(), 2);
+		assert_eq!(store.get_by_height(1).unwrap().as_deref(), Some("{}"));
+	}
+}
@@ -1,7 +1,13 @@
+static MINING_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Serializes tests that use the global `crate::blockchain::IS_MINING`.
+pub fn lock_mining() -> std::sync::MutexGuard<'static, ()> {
+	MINING_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 #[must_use]
-pub fn create_test_user() -> User {
-	crate::user::User::new(k256::ecdsa::SigningKey::random(rand::rngs::OsRng))
-		.unwrap()
+pub fn create_test_user() -> crate::user::User {
+	crate::user::User::generate().unwrap()
 }
 
 #[must_use]
@@ -9,10 +15,123 @@ pub fn create_test_block<'a>() -> (crate::user::User, crate::block::Block<'a>)
 {
 	let user = create_test_user();
 	let block = crate::block::Block::new(
-		user.address().to_owned(),
+		user.address().to_string(),
 		None::<&str>,
 		crate::block::Transactions::new(),
 		crate::helpers::BalanceState::new(),
+		0,
 	);
 	(user, block)
 }
+
+/// Creates a blockchain with a mined genesis block in a temporary database.
+/// The database is removed with the returned directory, so keep it while the
+/// blockchain is used.
+///
+/// Hold [`lock_mining`] while using it.
+#[must_use]
+pub fn create_test_blockchain<'a>(
+) -> (crate::blockchain::Blockchain<'a>, tempfile::TempDir) {
+	create_test_blockchain_with(common::config::Consensus::default())
+}
+
+/// Same as [`create_test_blockchain`], but with the passed `consensus`.
+#[must_use]
+pub fn create_test_blockchain_with<'a>(
+	consensus: common::config::Consensus,
+) -> (crate::blockchain::Blockchain<'a>, tempfile::TempDir) {
+	let dir = tempfile::tempdir().unwrap();
+	let pool = r2d2::Pool::new(r2d2_sqlite::SqliteConnectionManager::file(
+		dir.path().join("blockchain.db"),
+	))
+	.unwrap();
+	let mut blockchain = crate::blockchain::Blockchain::new(
+		create_test_user(),
+		consensus,
+		crate::store::SqliteStore::new(pool).unwrap(),
+	)
+	.unwrap();
+	blockchain.mine_genesis_block().unwrap();
+	(blockchain, dir)
+}
+
+/// Creates an empty blockchain in a temporary database with a pool of one
+/// connection, as [`add_blocks`] requires. The database is removed with the
+/// returned directory.
+///
+/// [`add_blocks`]: crate::blockchain::Blockchain::add_blocks
+#[must_use]
+pub fn create_test_single_connection_blockchain<'a>(
+) -> (crate::blockchain::Blockchain<'a>, tempfile::TempDir) {
+	let dir = tempfile::tempdir().unwrap();
+	let pool = r2d2::Pool::builder()
+		.max_size(1)
+		.build(r2d2_sqlite::SqliteConnectionManager::file(
+			dir.path().join("blockchain.db"),
+		))
+		.unwrap();
+	let blockchain = crate::blockchain::Blockchain::new(
+		create_test_user(),
+		common::config::Consensus::default(),
+		crate::store::SqliteStore::new(pool).unwrap(),
+	)
+	.unwrap();
+	(blockchain, dir)
+}
+
+/// Creates a consensus from the passed JSON `fields`.
+#[must_use]
+pub fn create_test_consensus(
+	fields: serde_json::Value,
+) -> common::config::Consensus {
+	serde_json::from_value(fields).unwrap()
+}
+
+/// Creates a signed transaction from the miner of the `blockchain` to a new
+/// user.
+#[must_use]
+pub fn create_test_transaction<'a, S>(
+	blockchain: &crate::blockchain::Blockchain<S>,
+	amount: u64,
+) -> crate::transaction::Transaction<'a>
+where
+	S: crate::store::BlockStore,
+{
+	create_test_transaction_with_fee(blockchain, amount, 0)
+}
+
+/// Same as [`create_test_transaction`], but with the `fee`.
+#[must_use]
+pub fn create_test_transaction_with_fee<'a, S>(
+	blockchain: &crate::blockchain::Blockchain<S>,
+	amount: u64,
+	fee: u64,
+) -> crate::transaction::Transaction<'a>
+where
+	S: crate::store::BlockStore,
+{
+	let mut transaction = crate::transaction::Transaction::new(
+		blockchain.miner().address().clone(),
+		create_test_user().address().clone(),
+		std::num::NonZeroU64::new(amount).unwrap(),
+		blockchain.get_last_block_hash().unwrap(),
+		blockchain.consensus().chain_id(),
+	)
+	.with_fee(fee);
+	transaction.sign(blockchain.miner()).unwrap();
+	transaction
+}
+
+/// Creates a property test config with the `cases` and a fixed seed, so the
+/// same cases are generated on each run.
+#[must_use]
+pub fn create_test_proptest_config(
+	cases: u32,
+) -> proptest::test_runner::Config {
+	proptest::test_runner::Config {
+		cases,
+		failure_persistence: None,
+		rng_seed: proptest::test_runner::RngSeed::Fixed(0),
+		..proptest::test_runner::Config::default()
+	}
+}
@@ -1,41 +1,95 @@
 use crate::error::{
-	SignTransactionError, ValidateTransactionIntegrityError,
+	SignTransactionError, ValidateTransactionAmountError,
+	ValidateTransactionChainIdError, ValidateTransactionIntegrityError,
 	ValidateTransactionPreviousBlockHashError,
 	ValidateTransactionRecipientError,
 	ValidateTransactionSenderSignatureError,
+	VerifyTransactionSignatureOnlyError,
 };
 
 /// Structure, which is the transaction of money from one user to another.
-#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+///
+/// Unknown fields are rejected, because they are neither signed nor
+/// validated.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Transaction<'a> {
-	sender: std::borrow::Cow<'a, str>,
-	recipient: std::borrow::Cow<'a, str>,
+	/// The chain id of the consensus. It is zero, the default one, in the
+	/// transactions which were made before it, and it is hashed only if it
+	/// is not zero, so they stay valid.
+	#[serde(default)]
+	chain_id: u32,
+	sender: crate::address::Address,
+	recipient: crate::address::Address,
 	amount: std::num::NonZeroU64,
 	amount_to_storage: u64,
+	/// Paid by the sender to the miner of the block. It is omitted in the
+	/// JSON and in the hash if it is zero, so the older transactions stay
+	/// valid.
+	#[serde(default)]
+	fee: u64,
 	previous_block_hash: std::borrow::Cow<'a, str>,
 	random_string: String,
 	sender_signature: Option<String>,
 }
 
+/// Implemented by hand, because the zero fee may be omitted only in the
+/// self-describing formats. The compact formats, such as `bincode` of the
+/// snapshots, need every field to decode the transaction back.
+impl serde::Serialize for Transaction<'_> {
+	fn serialize<S: serde::Serializer>(
+		&self,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		use serde::ser::SerializeStruct as _;
+
+		let with_fee = self.fee != 0 || !serializer.is_human_readable();
+		let mut state = serializer
+			.serialize_struct("Transaction", 8 + usize::from(with_fee))?;
+		state.serialize_field("chain_id", &self.chain_id)?;
+		state.serialize_field("sender", &self.sender)?;
+		state.serialize_field("recipient", &self.recipient)?;
+		state.serialize_field("amount", &self.amount)?;
+		state.serialize_field("amount_to_storage", &self.amount_to_storage)?;
+		if with_fee {
+			state.serialize_field("fee", &self.fee)?;
+		} else {
+			state.skip_field("fee")?;
+		}
+		state.serialize_field(
+			"previous_block_hash",
+			&self.previous_block_hash,
+		)?;
+		state.serialize_field("random_string", &self.random_string)?;
+		state.serialize_field("sender_signature", &self.sender_signature)?;
+		state.end()
+	}
+}
+
 impl<'a> Transaction<'a> {
-	common::accessor!(& sender -> &str);
+	common::accessor!(copy chain_id -> u32);
+
+	common::accessor!(& sender -> &crate::address::Address);
 
-	common::accessor!(& recipient -> &str);
+	common::accessor!(& recipient -> &crate::address::Address);
 
 	common::accessor!(copy amount -> std::num::NonZeroU64);
 
 	common::accessor!(copy amount_to_storage -> u64);
 
+	common::accessor!(copy fee -> u64);
+
 	common::accessor!(& previous_block_hash -> &str);
 
 	common::accessor!(& random_string -> &str);
 
 	#[must_use = "Add transaction via `blockchain::Blockchain`."]
 	pub fn new(
-		sender: impl Into<std::borrow::Cow<'a, str>>,
-		recipient: impl Into<std::borrow::Cow<'a, str>>,
+		sender: crate::address::Address,
+		recipient: crate::address::Address,
 		amount: std::num::NonZeroU64,
 		previous_block_hash: impl Into<std::borrow::Cow<'a, str>>,
+		chain_id: u32,
 	) -> Self {
 		let amount_to_storage = if u64::from(amount)
 			>= crate::consts::STORAGE_REWARD_STARTING_FROM
@@ -45,16 +99,35 @@ impl<'a> Transaction<'a> {
 			0
 		};
 		Self {
-			sender: sender.into(),
-			recipient: recipient.into(),
+			chain_id,
+			sender,
+			recipient,
 			amount,
 			amount_to_storage,
+			fee: 0,
 			previous_block_hash: previous_block_hash.into(),
 			random_string: crate::helpers::generate_random_string(),
 			sender_signature: None,
 		}
 	}
 
+	/// Sets the fee, which is paid to the miner. Set it before
+	/// [`sign`](Transaction::sign), because it is signed.
+	#[inline]
+	#[must_use]
+	pub fn with_fee(mut self, fee: u64) -> Self {
+		self.fee = fee;
+		self
+	}
+
+	/// The amount, the amount to storage and the fee, which are withdrawn
+	/// from the sender. Returns `None` on overflow.
+	#[inline]
+	#[must_use]
+	pub fn total_cost(&self) -> Option<std::num::NonZeroU64> {
+		self.amount.checked_add(self.amount_to_storage)?.checked_add(self.fee)
+	}
+
 	/// Signs the hash of the transaction and puts it in
 	/// `self.sender_signature`.
 	#[tracing::instrument(level = tracing::Level::DEBUG)]
@@ -71,32 +144,127 @@ impl<'a> Transaction<'a> {
 	///
 	/// Call it only if last blockchain block is previous block.
 	#[tracing::instrument(level = tracing::Level::DEBUG, ret, skip(blockchain))]
-	pub fn validate_integrity(
+	pub fn validate_integrity<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateTransactionIntegrityError>
+	where
+		S: crate::store::BlockStore,
+	{
+		let previous_block_hash = blockchain
+			.get_last_block_hash()
+			.map_err(ValidateTransactionPreviousBlockHashError::from)?;
+		self.validate_integrity_at(blockchain, &previous_block_hash)
+	}
+
+	/// Same as [`validate_integrity`](Transaction::validate_integrity), but
+	/// the previous block is the block with the `previous_block_hash`.
+	pub(crate) fn validate_integrity_at<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+		previous_block_hash: &str,
+	) -> Result<(), ValidateTransactionIntegrityError>
+	where
+		S: crate::store::BlockStore,
+	{
+		self.validate_chain_id(blockchain)?;
+		self.validate_recipient()?;
+		self.validate_not_self(blockchain)?;
+		self.validate_amount(blockchain)?;
+		self.validate_sender_signature()?;
+		self.validate_previous_block_hash(previous_block_hash)?;
+		Ok(())
+	}
+
+	/// Checks the recipient and the sender signature. Unlike
+	/// [`validate_integrity`](Transaction::validate_integrity), it does not
+	/// need a blockchain, so it can be used before the submission.
+	#[tracing::instrument(level = tracing::Level::DEBUG, ret)]
+	pub fn verify_signature_only(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
-	) -> Result<(), ValidateTransactionIntegrityError> {
+	) -> Result<(), VerifyTransactionSignatureOnlyError> {
 		self.validate_recipient()?;
 		self.validate_sender_signature()?;
-		self.validate_previous_block_hash(blockchain)?;
+		Ok(())
+	}
+
+	/// Checks that the transaction is created for the consensus network.
+	fn validate_chain_id<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateTransactionChainIdError>
+	where
+		S: crate::store::BlockStore,
+	{
+		if self.chain_id != blockchain.consensus().chain_id() {
+			return Err(ValidateTransactionChainIdError::NotEquals);
+		}
+		Ok(())
+	}
+
+	/// Checks that the amount is not less than the consensus minimum. The
+	/// storage transactions are not limited.
+	///
+	/// Also, since `self.amount_to_storage` comes from the sender, checks that
+	/// it does not exceed `consts::STORAGE_REWARD` and that the total cost
+	/// does not overflow.
+	fn validate_amount<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateTransactionAmountError>
+	where
+		S: crate::store::BlockStore,
+	{
+		if !self.sender.is_storage()
+			&& u64::from(self.amount)
+				< blockchain.consensus().min_transaction_amount()
+		{
+			return Err(ValidateTransactionAmountError::LessThanMinimum);
+		} else if self.amount_to_storage > crate::consts::STORAGE_REWARD {
+			return Err(ValidateTransactionAmountError::StorageAmountTooLarge);
+		} else if self.total_cost().is_none() {
+			return Err(ValidateTransactionAmountError::TotalCostOverflow);
+		}
 		Ok(())
 	}
 
 	fn validate_recipient(
 		&self,
 	) -> Result<(), ValidateTransactionRecipientError> {
-		if self.recipient == crate::consts::STORAGE_ADDRESS {
+		if self.recipient.is_storage() {
 			return Err(ValidateTransactionRecipientError::IsStorage);
 		}
 		Ok(())
 	}
 
+	/// Checks that the sender does not send to itself, unless the consensus
+	/// allows it.
+	fn validate_not_self<S>(
+		&self,
+		blockchain: &crate::blockchain::Blockchain<S>,
+	) -> Result<(), ValidateTransactionRecipientError>
+	where
+		S: crate::store::BlockStore,
+	{
+		if !blockchain.consensus().allow_self_transactions()
+			&& self.sender == self.recipient
+		{
+			return Err(ValidateTransactionRecipientError::IsSender);
+		}
+		Ok(())
+	}
+
 	fn validate_sender_signature(
 		&self,
 	) -> Result<(), ValidateTransactionSenderSignatureError> {
-		if self.sender != crate::consts::STORAGE_ADDRESS {
+		if !self.sender.is_storage() {
 			if let Some(ref s) = self.sender_signature {
 				let hash = self.compute_hash();
-				crate::user::User::validate_signature(s, &hash, &self.sender)?;
+				crate::user::User::validate_signature(
+					s,
+					&hash,
+					self.sender.as_str(),
+				)?;
 				return Ok(());
 			}
 			return Err(ValidateTransactionSenderSignatureError::IsEmpty);
@@ -104,12 +272,11 @@ impl<'a> Transaction<'a> {
 		Ok(())
 	}
 
-	/// Call it only if last blockchain block is previous block.
 	fn validate_previous_block_hash(
 		&self,
-		blockchain: &crate::blockchain::Blockchain,
+		previous_block_hash: &str,
 	) -> Result<(), ValidateTransactionPreviousBlockHashError> {
-		if self.previous_block_hash() != blockchain.get_last_block_hash()? {
+		if self.previous_block_hash() != previous_block_hash {
 			return Err(
 				ValidateTransactionPreviousBlockHashError::HashesNotEquals,
 			);
@@ -122,8 +289,15 @@ impl<'a> Transaction<'a> {
 	#[tracing::instrument(level = tracing::Level::TRACE, ret)]
 	#[must_use]
 	fn compute_hash(&self) -> String {
+		hex::encode(self.compute_digest())
+	}
+
+	/// Same as [`compute_hash`](Transaction::compute_hash), but not encoded.
+	/// It is a leaf of the Merkle tree of the block.
+	#[must_use]
+	pub(crate) fn compute_digest(&self) -> [u8; 32] {
 		use sha2::Digest as _;
-		let json = serde_json::json!({
+		let mut json = serde_json::json!({
 			"sender": self.sender,
 			"recipient": self.recipient,
 			"amount": self.amount,
@@ -131,8 +305,13 @@ impl<'a> Transaction<'a> {
 			"previous_block_hash": self.previous_block_hash,
 			"random_string": self.random_string,
 		});
-		let hash = sha2::Sha256::digest(json.to_string().as_bytes());
-		hex::encode(hash)
+		if self.chain_id != 0 {
+			json["chain_id"] = self.chain_id.into();
+		}
+		if self.fee != 0 {
+			json["fee"] = self.fee.into();
+		}
+		sha2::Sha256::digest(json.to_string().as_bytes()).into()
 	}
 }
 
@@ -142,12 +321,479 @@ mod tests {
 	fn test_sign() {
 		let user = crate::test_helpers::create_test_user();
 		let mut transaction = super::Transaction::new(
-			user.address(),
-			"recipient",
+			user.address().clone(),
+			crate::test_helpers::create_test_user().address().clone(),
 			unsafe { std::num::NonZeroU64::new_unchecked(50) },
 			"",
+			0,
 		);
 		transaction.sign(&user).unwrap();
 		transaction.validate_sender_signature().unwrap();
 	}
+
+	#[test]
+	fn test_verify_signature_only() {
+		let user = crate::test_helpers::create_test_user();
+		let mut transaction = super::Transaction::new(
+			user.address().clone(),
+			crate::test_helpers::create_test_user().address().clone(),
+			std::num::NonZeroU64::new(50).unwrap(),
+			"",
+			0,
+		);
+		transaction.sign(&user).unwrap();
+		transaction.verify_signature_only().unwrap();
+
+		transaction.amount = std::num::NonZeroU64::new(60).unwrap();
+		assert!(transaction.verify_signature_only().is_err());
+	}
+
+	#[test]
+	fn test_deserialize_unknown_field() {
+		let user = crate::test_helpers::create_test_user();
+		let mut transaction = super::Transaction::new(
+			user.address().clone(),
+			crate::test_helpers::create_test_user().address().clone(),
+			std::num::NonZeroU64::new(50).unwrap(),
+			"",
+			0,
+		);
+		transaction.sign(&user).unwrap();
+		let mut json = serde_json::to_value(&transaction).unwrap();
+		serde_json::from_value::<super::Transaction>(json.clone()).unwrap();
+
+		json["note"] = serde_json::json!("unsigned");
+		let error =
+			serde_json::from_value::<super::Transaction>(json).unwrap_err();
+		assert!(error.to_string().contains("unknown field `note`"));
+	}
+
+	#[test]
+	fn test_serialize_fee() {
+		let user = crate::test_helpers::create_test_user();
+		for fee in [0, 3] {
+			let mut transaction = super::Transaction::new(
+				user.address().clone(),
+				crate::test_helpers::create_test_user().address().clone(),
+				std::num::NonZeroU64::new(50).unwrap(),
+				"",
+				0,
+			)
+			.with_fee(fee);
+			transaction.sign(&user).unwrap();
+
+			// The zero fee is omitted only in JSON
+			let json = serde_json::to_value(&transaction).unwrap();
+			assert_eq!(json.get("fee").is_some(), fee != 0);
+			let bytes = bincode::serialize(&transaction).unwrap();
+			let decoded: super::Transaction =
+				bincode::deserialize(&bytes).unwrap();
+			assert_eq!(decoded.fee(), fee);
+			decoded.verify_signature_only().unwrap();
+		}
+	}
+
+	#[test]
+	fn test_deserialize_long_recipient() {
+		let user = crate::test_helpers::create_test_user();
+		let mut transaction = super::Transaction::new(
+			user.address().clone(),
+			crate::test_helpers::create_test_user().address().clone(),
+			std::num::NonZeroU64::new(50).unwrap(),
+			"",
+			0,
+		);
+		transaction.sign(&user).unwrap();
+		let mut json = serde_json::to_value(&transaction).unwrap();
+
+		json["recipient"] = serde_json::json!(format!(
+			"{}{}",
+			"1".repeat(4096),
+			transaction.recipient()
+		));
+		let error =
+			serde_json::from_value::<super::Transaction>(json).unwrap_err();
+		assert!(error
+			.to_string()
+			.contains("Invalid length of the Base58 string."));
+	}
+
+	#[test]
+	fn test_validate_chain_id() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 50);
+		transaction.validate_integrity(&blockchain).unwrap();
+
+		let (other, _dir) = crate::test_helpers::create_test_blockchain_with(
+			crate::test_helpers::create_test_consensus(
+				serde_json::json!({"chain_id": 1}),
+			),
+		);
+		assert!(matches!(
+			transaction.validate_integrity(&other),
+			Err(super::ValidateTransactionIntegrityError::ChainId(_)),
+		));
+	}
+
+	#[test]
+	fn test_validate_amount() {
+		use crate::error::{
+			AddTransactionError, ValidateTransactionAmountError,
+			ValidateTransactionIntegrityError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (mut blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				crate::test_helpers::create_test_consensus(
+					serde_json::json!({"min_transaction_amount": 5}),
+				),
+			);
+
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 4);
+		assert!(matches!(
+			blockchain.add_transaction(transaction),
+			Err(AddTransactionError::ValidateIntegrity(
+				ValidateTransactionIntegrityError::Amount(
+					ValidateTransactionAmountError::LessThanMinimum
+				)
+			))
+		));
+
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 5);
+		blockchain.add_transaction(transaction).unwrap();
+	}
+
+	#[test]
+	fn test_validate_self_transaction() {
+		use crate::error::{
+			AddTransactionError, ValidateTransactionIntegrityError,
+			ValidateTransactionRecipientError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		for allow in [false, true] {
+			let (mut blockchain, _dir) =
+				crate::test_helpers::create_test_blockchain_with(
+					crate::test_helpers::create_test_consensus(
+						serde_json::json!({"allow_self_transactions": allow}),
+					),
+				);
+			let miner = blockchain.miner().clone();
+			let mut transaction = super::Transaction::new(
+				miner.address().clone(),
+				miner.address().clone(),
+				std::num::NonZeroU64::new(5).unwrap(),
+				blockchain.get_last_block_hash().unwrap(),
+				blockchain.consensus().chain_id(),
+			);
+			transaction.sign(&miner).unwrap();
+
+			let added = blockchain.add_transaction(transaction);
+			if allow {
+				added.unwrap();
+			} else {
+				assert!(matches!(
+					added,
+					Err(AddTransactionError::ValidateIntegrity(
+						ValidateTransactionIntegrityError::Recipient(
+							ValidateTransactionRecipientError::IsSender
+						)
+					))
+				));
+			}
+		}
+	}
+
+	#[test]
+	fn test_mine_self_transaction() {
+		let _lock = crate::test_helpers::lock_mining();
+		for deltas in [false, true] {
+			let (mut blockchain, _dir) =
+				crate::test_helpers::create_test_blockchain_with(
+					crate::test_helpers::create_test_consensus(
+						serde_json::json!({
+							"allow_self_transactions": true,
+							"balance_state_deltas": deltas,
+						}),
+					),
+				);
+			let miner = blockchain.miner().clone();
+			let balance =
+				blockchain.get_balance(miner.address().as_str()).unwrap();
+			let mut transaction = super::Transaction::new(
+				miner.address().clone(),
+				miner.address().clone(),
+				std::num::NonZeroU64::new(5).unwrap(),
+				blockchain.get_last_block_hash().unwrap(),
+				blockchain.consensus().chain_id(),
+			);
+			transaction.sign(&miner).unwrap();
+			let spent = transaction.amount_to_storage() + transaction.fee();
+			blockchain.add_transaction(transaction).unwrap();
+
+			let block = blockchain.mine_block().unwrap();
+			blockchain.validate_stored_chain().unwrap();
+			blockchain.validate_block_at(1).unwrap();
+			// The miner gets the fee back with the mining reward
+			assert_eq!(
+				blockchain.get_balance(miner.address().as_str()).unwrap(),
+				balance - spent
+					+ block.transactions()[0].fee()
+					+ blockchain.consensus().mining_reward(),
+			);
+		}
+	}
+
+	#[test]
+	fn test_validate_inflated_amount_to_storage() {
+		use crate::error::{
+			ValidateTransactionAmountError, ValidateTransactionIntegrityError,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let mut transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, 50);
+		transaction.validate_integrity(&blockchain).unwrap();
+
+		transaction.amount_to_storage = crate::consts::STORAGE_REWARD + 1;
+		assert!(matches!(
+			transaction.validate_integrity(&blockchain),
+			Err(ValidateTransactionIntegrityError::Amount(
+				ValidateTransactionAmountError::StorageAmountTooLarge
+			))
+		));
+
+		transaction.amount = std::num::NonZeroU64::new(u64::MAX).unwrap();
+		transaction.amount_to_storage = crate::consts::STORAGE_REWARD;
+		assert!(transaction.total_cost().is_none());
+		assert!(matches!(
+			transaction.validate_integrity(&blockchain),
+			Err(ValidateTransactionIntegrityError::Amount(
+				ValidateTransactionAmountError::TotalCostOverflow
+			))
+		));
+	}
+
+	/// A change of a single field of a valid transaction.
+	#[derive(Clone, Copy, Debug)]
+	enum Mutation {
+		ChainId,
+		RecipientIsStorage,
+		RecipientIsSender,
+		LessThanMinimum(u64),
+		StorageAmountTooLarge(u64),
+		TotalCostOverflow(u64),
+		PreviousBlockHash,
+		Amount,
+		Unsigned,
+	}
+
+	/// The minimum amount of the consensus of the property tests.
+	const MIN_AMOUNT: u64 = 10;
+
+	/// The largest amount, whose total cost does not overflow.
+	const MAX_AMOUNT: u64 = u64::MAX - crate::consts::STORAGE_REWARD;
+
+	/// The valid amounts. The bounds are more likely.
+	fn amount() -> impl proptest::strategy::Strategy<Value = u64> {
+		use proptest::prelude::*;
+
+		prop_oneof![
+			Just(MIN_AMOUNT),
+			Just(MAX_AMOUNT),
+			MIN_AMOUNT..=MAX_AMOUNT
+		]
+	}
+
+	fn mutation() -> impl proptest::strategy::Strategy<Value = Mutation> {
+		use proptest::prelude::*;
+
+		prop_oneof![
+			Just(Mutation::ChainId),
+			Just(Mutation::RecipientIsStorage),
+			Just(Mutation::RecipientIsSender),
+			(1..MIN_AMOUNT).prop_map(Mutation::LessThanMinimum),
+			(crate::consts::STORAGE_REWARD + 1..=u64::MAX)
+				.prop_map(Mutation::StorageAmountTooLarge),
+			(MAX_AMOUNT + 1..=u64::MAX).prop_map(Mutation::TotalCostOverflow),
+			Just(Mutation::PreviousBlockHash),
+			Just(Mutation::Amount),
+			Just(Mutation::Unsigned),
+		]
+	}
+
+	/// Creates a blockchain with the `MIN_AMOUNT` and a transaction of the
+	/// `amount` from its miner, which follows its last block.
+	fn create_test_transaction_with_amount<'a>(
+		amount: u64,
+	) -> (
+		crate::blockchain::Blockchain<'a, crate::store::MemoryStore>,
+		super::Transaction<'a>,
+	) {
+		let consensus = crate::test_helpers::create_test_consensus(
+			serde_json::json!({"min_transaction_amount": MIN_AMOUNT}),
+		);
+		let mut blockchain = crate::blockchain::Blockchain::new(
+			crate::test_helpers::create_test_user(),
+			consensus,
+			crate::store::MemoryStore::default(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+		let transaction =
+			crate::test_helpers::create_test_transaction(&blockchain, amount);
+		(blockchain, transaction)
+	}
+
+	/// Changes the field of the valid `transaction`, which is chosen by the
+	/// `mutation`. The transaction is signed by the `sender` again, unless
+	/// the signed data or the signature is changed.
+	fn apply_mutation(
+		mutation: Mutation,
+		sender: &crate::user::User,
+		transaction: &mut super::Transaction,
+	) {
+		match mutation {
+			Mutation::ChainId => {
+				transaction.chain_id = transaction.chain_id.wrapping_add(1);
+			}
+			Mutation::RecipientIsStorage => {
+				transaction.recipient = crate::address::Address::storage();
+			}
+			Mutation::RecipientIsSender => {
+				transaction.recipient = transaction.sender.clone();
+			}
+			Mutation::LessThanMinimum(amount) => {
+				transaction.amount =
+					std::num::NonZeroU64::new(amount).unwrap();
+			}
+			Mutation::StorageAmountTooLarge(amount) => {
+				transaction.amount_to_storage = amount;
+			}
+			Mutation::TotalCostOverflow(amount) => {
+				transaction.amount =
+					std::num::NonZeroU64::new(amount).unwrap();
+				transaction.amount_to_storage = crate::consts::STORAGE_REWARD;
+			}
+			Mutation::PreviousBlockHash => {
+				transaction.previous_block_hash = "0".repeat(64).into();
+			}
+			Mutation::Amount => {
+				let amount = u64::from(transaction.amount);
+				transaction.amount =
+					std::num::NonZeroU64::new(if amount == MAX_AMOUNT {
+						amount - 1
+					} else {
+						amount + 1
+					})
+					.unwrap();
+				return;
+			}
+			Mutation::Unsigned => {
+				transaction.sender_signature = None;
+				return;
+			}
+		}
+		transaction.sign(sender).unwrap();
+	}
+
+	/// Whether the `result` of the validation of the transaction, changed by
+	/// the `mutation`, is the expected error.
+	fn is_expected_error(
+		mutation: Mutation,
+		result: &Result<(), crate::error::ValidateTransactionIntegrityError>,
+	) -> bool {
+		use crate::error::{
+			ValidateTransactionAmountError as Amount,
+			ValidateTransactionChainIdError,
+			ValidateTransactionIntegrityError as E,
+			ValidateTransactionPreviousBlockHashError,
+			ValidateTransactionRecipientError,
+			ValidateTransactionSenderSignatureError,
+		};
+
+		match mutation {
+			Mutation::ChainId => matches!(
+				result,
+				Err(E::ChainId(ValidateTransactionChainIdError::NotEquals))
+			),
+			Mutation::RecipientIsStorage => matches!(
+				result,
+				Err(E::Recipient(
+					ValidateTransactionRecipientError::IsStorage
+				))
+			),
+			Mutation::RecipientIsSender => matches!(
+				result,
+				Err(E::Recipient(ValidateTransactionRecipientError::IsSender))
+			),
+			Mutation::LessThanMinimum(_) => {
+				matches!(result, Err(E::Amount(Amount::LessThanMinimum)))
+			}
+			Mutation::StorageAmountTooLarge(_) => {
+				matches!(result, Err(E::Amount(Amount::StorageAmountTooLarge)))
+			}
+			Mutation::TotalCostOverflow(_) => {
+				matches!(result, Err(E::Amount(Amount::TotalCostOverflow)))
+			}
+			Mutation::PreviousBlockHash => matches!(
+				result,
+				Err(E::PreviousBlockHash(
+					ValidateTransactionPreviousBlockHashError::HashesNotEquals
+				))
+			),
+			Mutation::Amount => matches!(
+				result,
+				Err(E::SenderSignature(
+					ValidateTransactionSenderSignatureError::Validate(_)
+				))
+			),
+			Mutation::Unsigned => matches!(
+				result,
+				Err(E::SenderSignature(
+					ValidateTransactionSenderSignatureError::IsEmpty
+				))
+			),
+		}
+	}
+
+	proptest::proptest! {
+		#![proptest_config(crate::test_helpers::create_test_proptest_config(64))]
+
+		#[test]
+		fn test_validate_created_transaction(amount in amount()) {
+			let _lock = crate::test_helpers::lock_mining();
+			let (blockchain, transaction) =
+				create_test_transaction_with_amount(amount);
+			proptest::prop_assert!(
+				transaction.validate_integrity(&blockchain).is_ok()
+			);
+			proptest::prop_assert!(transaction.verify_signature_only().is_ok());
+		}
+
+		#[test]
+		fn test_validate_mutated_transaction(
+			amount in amount(),
+			mutation in mutation(),
+		) {
+			let _lock = crate::test_helpers::lock_mining();
+			let (blockchain, mut transaction) =
+				create_test_transaction_with_amount(amount);
+			apply_mutation(mutation, blockchain.miner(), &mut transaction);
+			proptest::prop_assert!(
+				is_expected_error(
+					mutation,
+					&transaction.validate_integrity(&blockchain)
+				),
+				"{:?} is not rejected as expected",
+				mutation
+			);
+		}
+	}
 }
@@ -1,6 +1,7 @@
 use crate::error::{
 	ConvertPublicKeyToAddressError, LoadOrCreateUserError, NewUserError,
-	UserSignError, ValidateUserSignatureError,
+	SaveUserError, UserFromMnemonicError, UserSignError,
+	ValidateUserSignatureError,
 };
 
 /// The structure of the user, which stores his private key and address.
@@ -8,36 +9,159 @@ use crate::error::{
 /// Use `Self::load_or_create` to get the object of an existing or new user.
 #[derive(Clone, Debug)]
 pub struct User {
-	address: String,
+	address: crate::address::Address,
 	private_key: k256::ecdsa::SigningKey,
 }
 
 impl User {
-	common::accessor!(& address -> &str);
+	common::accessor!(& address -> &crate::address::Address);
 
 	/// Loads or creates a new user depending on whether
 	/// `consts::PRIVATE_KEY_PATH` exists.
+	///
+	/// If the key is encrypted, the passphrase is taken from the
+	/// `consts::PASSPHRASE_ENV` environment variable or is prompted.
 	#[tracing::instrument(ret)]
 	pub fn load_or_create() -> Result<Self, LoadOrCreateUserError> {
-		if crate::consts::PRIVATE_KEY_PATH.exists() {
+		Self::load_or_create_at(&crate::consts::PRIVATE_KEY_PATH, None)
+	}
+
+	/// Same as [`load_or_create`](User::load_or_create), but the key of a new
+	/// user is encrypted with the `passphrase`, which also decrypts the
+	/// existing key. An existing plain key is loaded as it is.
+	#[tracing::instrument(skip(passphrase), ret)]
+	pub fn load_or_create_encrypted(
+		passphrase: &str,
+	) -> Result<Self, LoadOrCreateUserError> {
+		Self::load_or_create_at(
+			&crate::consts::PRIVATE_KEY_PATH,
+			Some(passphrase),
+		)
+	}
+
+	fn load_or_create_at(
+		path: &std::path::Path,
+		passphrase: Option<&str>,
+	) -> Result<Self, LoadOrCreateUserError> {
+		if path.exists() {
 			tracing::info!("Loading an existing user...");
-			// Read bytes and convert them to a private key
-			let bytes =
-				std::fs::read(crate::consts::PRIVATE_KEY_PATH.as_path())
-					.map_err(LoadOrCreateUserError::Read)?;
-			let key = k256::ecdsa::SigningKey::from_bytes(&bytes)?;
+			// Read bytes, decrypt and convert them to a private key. The bytes
+			// are wiped on drop, so the key does not stay in the freed memory
+			let bytes = zeroize::Zeroizing::new(
+				std::fs::read(path).map_err(LoadOrCreateUserError::Read)?,
+			);
+			let key = match bytes
+				.strip_prefix(crate::consts::ENCRYPTED_KEY_MAGIC)
+			{
+				Some(encrypted) => {
+					let passphrase = match passphrase {
+						Some(p) => p.to_owned(),
+						None => prompt_passphrase()
+							.map_err(LoadOrCreateUserError::ReadPassphrase)?,
+					};
+					let bytes = decrypt(encrypted, &passphrase)?;
+					k256::ecdsa::SigningKey::from_bytes(&bytes)?
+				}
+				None => k256::ecdsa::SigningKey::from_bytes(&bytes)?,
+			};
 			return Ok(Self::new(key)?);
 		}
 
 		tracing::info!("Creating a new user...");
 		// Generating a private key and writing it to a file
-		let key = k256::ecdsa::SigningKey::random(rand::rngs::OsRng);
-		std::fs::write(
-			crate::consts::PRIVATE_KEY_PATH.as_path(),
-			key.to_bytes(),
-		)
-		.map_err(LoadOrCreateUserError::Write)?;
-		Ok(Self::new(key)?)
+		let rv = Self::generate()?;
+		let bytes = match passphrase {
+			Some(p) => encrypt(&rv.private_key.to_bytes(), p)?,
+			None => rv.private_key.to_bytes().to_vec(),
+		};
+		std::fs::write(path, bytes).map_err(LoadOrCreateUserError::Write)?;
+		Ok(rv)
+	}
+
+	/// Writes the private key to `consts::PRIVATE_KEY_PATH`, so the user is
+	/// loaded with `Self::load_or_create` next time. If the `passphrase` is
+	/// given, the key is encrypted with it.
+	///
+	/// The existing key may hold money, so it is replaced only if `force` is
+	/// `true`. Otherwise `SaveUserError::Exists` is returned.
+	#[tracing::instrument(skip(self, passphrase), fields(address = %self.address))]
+	pub fn save(
+		&self,
+		passphrase: Option<&str>,
+		force: bool,
+	) -> Result<(), SaveUserError> {
+		self.save_at(&crate::consts::PRIVATE_KEY_PATH, passphrase, force)
+	}
+
+	fn save_at(
+		&self,
+		path: &std::path::Path,
+		passphrase: Option<&str>,
+		force: bool,
+	) -> Result<(), SaveUserError> {
+		use std::io::Write as _;
+
+		let bytes = match passphrase {
+			Some(p) => encrypt(&self.private_key.to_bytes(), p)
+				.map_err(SaveUserError::Encrypt)?,
+			None => self.private_key.to_bytes().to_vec(),
+		};
+		// Checked on opening, so the key can not appear in the meantime
+		let mut file = std::fs::OpenOptions::new()
+			.write(true)
+			.create(true)
+			.create_new(!force)
+			.truncate(true)
+			.open(path)
+			.map_err(|e| match e.kind() {
+				std::io::ErrorKind::AlreadyExists => SaveUserError::Exists,
+				_ => SaveUserError::Write(e),
+			})?;
+		file.write_all(&bytes).map_err(SaveUserError::Write)
+	}
+
+	/// Whether the key at `consts::PRIVATE_KEY_PATH` is encrypted, so the
+	/// key which replaces it should be encrypted too.
+	pub fn is_saved_encrypted() -> Result<bool, std::io::Error> {
+		match std::fs::read(crate::consts::PRIVATE_KEY_PATH.as_path()) {
+			Ok(b) => Ok(b.starts_with(crate::consts::ENCRYPTED_KEY_MAGIC)),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Creates a new user with a random private key without saving it.
+	#[tracing::instrument(ret)]
+	pub fn generate() -> Result<Self, NewUserError> {
+		Self::new(k256::ecdsa::SigningKey::random(rand::rngs::OsRng))
+	}
+
+	/// Same as [`generate`](User::generate), but the private key is derived
+	/// from a random 12 words BIP39 mnemonic, which is returned with the user.
+	/// The user is recovered from it with
+	/// [`from_mnemonic`](User::from_mnemonic).
+	#[tracing::instrument(skip_all)]
+	pub fn generate_mnemonic() -> Result<(Self, String), UserFromMnemonicError>
+	{
+		use rand::RngCore as _;
+
+		let mut entropy = [0; 16];
+		rand::rngs::OsRng.fill_bytes(&mut entropy);
+		let phrase = bip39::Mnemonic::from_entropy(&entropy)?.to_string();
+		Ok((Self::from_mnemonic(&phrase)?, phrase))
+	}
+
+	/// Derives the user from the BIP39 mnemonic `phrase` of 12 or 24 words.
+	/// The private key is the BIP32 master key of the seed without a
+	/// passphrase, so the same phrase always gives the same address.
+	#[tracing::instrument(skip_all)]
+	pub fn from_mnemonic(phrase: &str) -> Result<Self, UserFromMnemonicError> {
+		let mnemonic = bip39::Mnemonic::parse(phrase)?;
+		let word_count = mnemonic.word_count();
+		if word_count != 12 && word_count != 24 {
+			return Err(UserFromMnemonicError::InvalidWordCount(word_count));
+		}
+		Ok(Self::new(master_key(&mnemonic.to_seed(""))?)?)
 	}
 
 	#[tracing::instrument(ret)]
@@ -45,7 +169,9 @@ impl User {
 		private_key: k256::ecdsa::SigningKey,
 	) -> Result<Self, NewUserError> {
 		let public_key = private_key.verifying_key();
-		let address = Self::convert_public_key_to_address(public_key)?;
+		let address = crate::address::Address::from_public_key_hash(
+			Self::convert_public_key_to_address(public_key)?,
+		);
 		Ok(Self { address, private_key })
 	}
 
@@ -118,6 +244,93 @@ impl User {
 	}
 }
 
+/// Derives the BIP32 master private key from the `seed`.
+fn master_key(
+	seed: &[u8],
+) -> Result<k256::ecdsa::SigningKey, k256::ecdsa::Error> {
+	use hmac::{Mac as _, NewMac as _};
+
+	let mut mac = hmac::Hmac::<sha2::Sha512>::new_from_slice(b"Bitcoin seed")
+		.expect("HMAC takes a key of any length");
+	mac.update(seed);
+	let bytes = mac.finalize().into_bytes();
+	// The left half is the key and the right one is the chain code, which is
+	// not needed without the child keys
+	k256::ecdsa::SigningKey::from_bytes(&bytes[..32])
+}
+
+/// Takes the passphrase of the encrypted key from the
+/// `consts::PASSPHRASE_ENV` environment variable or prompts it without echo.
+pub fn prompt_passphrase() -> Result<String, std::io::Error> {
+	match std::env::var(crate::consts::PASSPHRASE_ENV) {
+		Ok(p) => Ok(p),
+		Err(_) => rpassword::prompt_password("Passphrase: "),
+	}
+}
+
+/// Encrypts the private key `bytes` with the key derived from the
+/// `passphrase` by Argon2. Returns `consts::ENCRYPTED_KEY_MAGIC`, the salt,
+/// the nonce and the XChaCha20-Poly1305 ciphertext.
+fn encrypt(
+	bytes: &[u8],
+	passphrase: &str,
+) -> Result<Vec<u8>, LoadOrCreateUserError> {
+	use {
+		chacha20poly1305::aead::{Aead as _, KeyInit as _},
+		rand::RngCore as _,
+	};
+
+	let mut salt = [0; crate::consts::ENCRYPTED_KEY_SALT_LEN];
+	let mut nonce = [0; crate::consts::ENCRYPTED_KEY_NONCE_LEN];
+	rand::rngs::OsRng.fill_bytes(&mut salt);
+	rand::rngs::OsRng.fill_bytes(&mut nonce);
+	let ciphertext = chacha20poly1305::XChaCha20Poly1305::new(&derive_key(
+		passphrase, &salt,
+	)?)
+	.encrypt(&nonce.into(), bytes)
+	.map_err(|_| LoadOrCreateUserError::Encrypt)?;
+	Ok([crate::consts::ENCRYPTED_KEY_MAGIC, &salt, &nonce, &ciphertext]
+		.concat())
+}
+
+/// Reverses [`encrypt`]. The `bytes` are without
+/// `consts::ENCRYPTED_KEY_MAGIC`. The decrypted key is wiped on drop.
+fn decrypt(
+	bytes: &[u8],
+	passphrase: &str,
+) -> Result<zeroize::Zeroizing<Vec<u8>>, LoadOrCreateUserError> {
+	use chacha20poly1305::aead::{Aead as _, KeyInit as _};
+
+	const HEADER_LEN: usize = crate::consts::ENCRYPTED_KEY_SALT_LEN
+		+ crate::consts::ENCRYPTED_KEY_NONCE_LEN;
+
+	if bytes.len() < HEADER_LEN {
+		return Err(LoadOrCreateUserError::Decrypt);
+	}
+	let (salt, rest) = bytes.split_at(crate::consts::ENCRYPTED_KEY_SALT_LEN);
+	let (nonce, ciphertext) =
+		rest.split_at(crate::consts::ENCRYPTED_KEY_NONCE_LEN);
+	// The authentication fails the same for a wrong passphrase and for the
+	// changed ciphertext
+	chacha20poly1305::XChaCha20Poly1305::new(&derive_key(passphrase, salt)?)
+		.decrypt(chacha20poly1305::XNonce::from_slice(nonce), ciphertext)
+		.map(zeroize::Zeroizing::new)
+		.map_err(|_| LoadOrCreateUserError::BadPassphrase)
+}
+
+fn derive_key(
+	passphrase: &str,
+	salt: &[u8],
+) -> Result<chacha20poly1305::Key, argon2::Error> {
+	let mut key = chacha20poly1305::Key::default();
+	argon2::Argon2::default().hash_password_into(
+		passphrase.as_bytes(),
+		salt,
+		&mut key,
+	)?;
+	Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
 	#[test]
@@ -125,11 +338,105 @@ mod tests {
 		use base58::FromBase58 as _;
 
 		let user = crate::test_helpers::create_test_user();
-		let len = user.address.len();
+		let len = user.address.as_str().len();
 
 		assert!((26..35).contains(&len));
-		assert!(user.address.from_base58().is_ok());
-		Ok(())
+		assert!(user.address.as_str().from_base58().is_ok());
+	}
+
+	#[test]
+	fn test_load_encrypted() {
+		use crate::error::LoadOrCreateUserError;
+
+		let path = std::env::temp_dir()
+			.join(format!("blockchain-test-key-{}", std::process::id()));
+		let user =
+			super::User::load_or_create_at(&path, Some("passphrase")).unwrap();
+		let bytes = std::fs::read(&path).unwrap();
+		assert!(bytes.starts_with(crate::consts::ENCRYPTED_KEY_MAGIC));
+
+		let loaded =
+			super::User::load_or_create_at(&path, Some("passphrase")).unwrap();
+		assert_eq!(loaded.address, user.address);
+		assert!(matches!(
+			super::User::load_or_create_at(&path, Some("wrong")),
+			Err(LoadOrCreateUserError::BadPassphrase)
+		));
+
+		// Truncated after the magic
+		std::fs::write(
+			&path,
+			&bytes[..crate::consts::ENCRYPTED_KEY_MAGIC.len() + 8],
+		)
+		.unwrap();
+		assert!(matches!(
+			super::User::load_or_create_at(&path, Some("passphrase")),
+			Err(LoadOrCreateUserError::Decrypt)
+		));
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_save() {
+		use crate::error::SaveUserError;
+
+		let path = std::env::temp_dir()
+			.join(format!("blockchain-test-saved-key-{}", std::process::id()));
+		let user = crate::test_helpers::create_test_user();
+		user.save_at(&path, None, false).unwrap();
+		assert_eq!(
+			super::User::load_or_create_at(&path, None).unwrap().address,
+			user.address
+		);
+
+		// The existing key is not lost without the force
+		let other = crate::test_helpers::create_test_user();
+		assert!(matches!(
+			other.save_at(&path, Some("passphrase"), false),
+			Err(SaveUserError::Exists)
+		));
+		other.save_at(&path, Some("passphrase"), true).unwrap();
+		assert!(std::fs::read(&path)
+			.unwrap()
+			.starts_with(crate::consts::ENCRYPTED_KEY_MAGIC));
+		assert_eq!(
+			super::User::load_or_create_at(&path, Some("passphrase"))
+				.unwrap()
+				.address,
+			other.address
+		);
+		std::fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_mnemonic() {
+		let (user, phrase) = super::User::generate_mnemonic().unwrap();
+		assert_eq!(phrase.split(' ').count(), 12);
+		let recovered = super::User::from_mnemonic(&phrase).unwrap();
+		assert_eq!(recovered.address, user.address);
+
+		// A valid mnemonic, but of 15 words
+		let phrase = bip39::Mnemonic::from_entropy(&[0; 20]).unwrap();
+		assert!(matches!(
+			super::User::from_mnemonic(&phrase.to_string()),
+			Err(crate::error::UserFromMnemonicError::InvalidWordCount(15))
+		));
+		assert!(matches!(
+			super::User::from_mnemonic(
+				&phrase.to_string().replacen("abandon", "abandonx", 1)
+			),
+			Err(crate::error::UserFromMnemonicError::Parse(_))
+		));
+	}
+
+	#[test]
+	fn test_master_key() {
+		// The test vector 1 of BIP32
+		let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+		assert_eq!(
+			hex::encode(super::master_key(&seed).unwrap().to_bytes()),
+			"e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+		);
 	}
 
 	#[test]
@@ -137,8 +444,11 @@ mod tests {
 		const DATA: &str = "DATA";
 		let user = crate::test_helpers::create_test_user();
 		let signature = user.sign(DATA).unwrap();
-		super::User::validate_signature(&signature, DATA, &user.address)
-			.unwrap();
-		Ok(())
+		super::User::validate_signature(
+			&signature,
+			DATA,
+			user.address.as_str(),
+		)
+		.unwrap();
 	}
 }
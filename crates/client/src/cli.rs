@@ -1,6 +1,21 @@
 #[derive(clap::Clap)]
 #[clap(setting = clap::AppSettings::ColoredHelp)]
 pub(crate) struct Opts {
+	/// Overrides the config location, as the `CRYPTO_CONFIG` environment
+	/// variable does.
+	#[clap(long, global = true)]
+	pub config: Option<std::path::PathBuf>,
+	/// Overrides the receive timeout of the config, in seconds.
+	#[clap(long, global = true)]
+	pub timeout: Option<std::num::NonZeroU64>,
+	/// Submits the transactions even if a node is on another chain than the
+	/// configured `chain_id`.
+	#[clap(long, global = true)]
+	pub allow_chain_id_mismatch: bool,
+	/// Encrypts the private key of a new user with a passphrase. It is taken
+	/// from the `CRYPTO_PASSPHRASE` environment variable or is prompted.
+	#[clap(long, global = true)]
+	pub encrypt_key: bool,
 	#[clap(subcommand)]
 	pub subcommand: SubCommand,
 }
@@ -15,13 +30,72 @@ pub(crate) enum SubCommand {
 pub(crate) enum UserSubCommand {
 	Address,
 	Balance,
+	/// Generates keys until the address starts with the prefix.
+	Vanity(UserVanityCommand),
+	/// Generates a key from a new BIP39 mnemonic and prints the mnemonic.
+	Mnemonic(UserMnemonicCommand),
+	/// Replaces the current user key with the one of the BIP39 mnemonic.
+	Recover(UserRecoverCommand),
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct UserMnemonicCommand {
+	/// Replace the current user key with the generated one.
+	#[clap(long)]
+	pub save: bool,
+	/// Replace the existing key, which may hold money.
+	#[clap(long, requires = "save")]
+	pub force: bool,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct UserRecoverCommand {
+	/// The 12 or 24 words of the mnemonic.
+	#[clap(required = true)]
+	pub words: Vec<String>,
+	/// Replace the existing key, which may hold money.
+	#[clap(long)]
+	pub force: bool,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct UserVanityCommand {
+	/// The Base58 prefix. Addresses always start with `1`.
+	pub prefix: String,
+	/// How many threads search. Defaults to the number of CPUs.
+	#[clap(long)]
+	pub threads: Option<std::num::NonZeroUsize>,
+	/// Replace the current user key with the found one.
+	#[clap(long)]
+	pub save: bool,
+	/// Replace the existing key, which may hold money.
+	#[clap(long, requires = "save")]
+	pub force: bool,
 }
 
 #[derive(clap::Clap)]
 pub(crate) enum BlockchainSubCommand {
-	Len,
+	Len(BlockchainLenCommand),
 	Balance(BlockchainBalanceCommand),
+	/// Prints the block with the hash from every node.
+	Block(BlockchainBlockCommand),
 	Transaction(BlockchainTransactionCommand),
+	/// Submits the transactions from a JSON file with a list of
+	/// `{"recipient": ..., "amount": ...}` objects, with an optional `fee`.
+	TransactionBatch(BlockchainTransactionBatchCommand),
+	/// Resends the transactions saved with `transaction --persist`.
+	Resend,
+	/// Asks the nodes to mine the pending transactions immediately.
+	Mine,
+	/// Prints the addresses with the largest balances.
+	Richlist(BlockchainRichlistCommand),
+	/// Prints the number of the pending transactions by fee.
+	Fees,
+	/// Writes the blocks of the nodes as a Graphviz DOT file.
+	Graph(BlockchainGraphCommand),
+	/// Downloads the blocks JSON and reconstructs the blockchain of the node
+	/// from it.
+	Import(BlockchainImportCommand),
 }
 
 #[derive(clap::Clap)]
@@ -29,8 +103,57 @@ pub(crate) struct BlockchainBalanceCommand {
 	pub address: String,
 }
 
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainBlockCommand {
+	pub hash: String,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainGraphCommand {
+	pub path: std::path::PathBuf,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainImportCommand {
+	/// The `http://` URL of the blocks JSON, for example, of a trusted
+	/// snapshot server. HTTPS is not supported.
+	#[clap(long)]
+	pub url: String,
+	/// The size limit of the downloaded JSON, in bytes. Defaults to 256 MiB.
+	#[clap(long)]
+	pub max_size: Option<u64>,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainLenCommand {
+	/// Group the nodes by length to see whether they agree.
+	#[clap(long)]
+	pub all: bool,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainRichlistCommand {
+	/// How many addresses to print.
+	#[clap(long, default_value = "10")]
+	pub top: usize,
+}
+
 #[derive(clap::Clap)]
 pub(crate) struct BlockchainTransactionCommand {
-	pub address: String,
+	pub address: blockchain::address::Address,
 	pub amount: std::num::NonZeroU64,
+	/// Paid to the miner of the block with the transaction.
+	#[clap(long, default_value = "0")]
+	pub fee: u64,
+	/// Save the transaction for `resend` if no node accepted it.
+	#[clap(long)]
+	pub persist: bool,
+	/// Print each network step of the transaction.
+	#[clap(long)]
+	pub verbose: bool,
+}
+
+#[derive(clap::Clap)]
+pub(crate) struct BlockchainTransactionBatchCommand {
+	pub path: std::path::PathBuf,
 }
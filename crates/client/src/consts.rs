@@ -0,0 +1,22 @@
+lazy_static::lazy_static! {
+	pub(crate) static ref PENDING_TRANSACTIONS_PATH: std::path::PathBuf =
+		blockchain::consts::RESOURCES_DIR.join("pending-transactions.json");
+}
+
+/// Each next character of a vanity prefix makes the search about 58 times
+/// longer.
+pub(crate) const VANITY_MAX_PREFIX_LEN: usize = 6;
+pub(crate) const VANITY_PROGRESS_INTERVAL: std::time::Duration =
+	std::time::Duration::from_secs(1);
+
+/// The default limit of the size of an imported chain, in bytes.
+pub(crate) const IMPORT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+/// How many bytes of an imported chain are downloaded between the progress
+/// reports.
+pub(crate) const DOWNLOAD_PROGRESS_EVERY: u64 = 1024 * 1024;
+/// How many blocks are imported between the progress reports.
+pub(crate) const IMPORT_PROGRESS_EVERY: std::num::NonZeroUsize =
+	match std::num::NonZeroUsize::new(1000) {
+		Some(n) => n,
+		None => unreachable!(),
+	};
@@ -0,0 +1,133 @@
+/// How many characters of the block hash are in the labels.
+const SHORT_HASH_LEN: usize = 8;
+
+/// Makes a Graphviz DOT graph of the `blocks`, which may come from several
+/// nodes, so the same blocks are merged. The blocks are labeled by the height
+/// and the short hash, and the edges go from the previous block to the next
+/// one.
+///
+/// The blocks which are not on the longest branch, like the candidates of a
+/// fork, are dashed. The orphans, whose previous block is unknown, have no
+/// height and are dotted.
+pub(crate) fn to_dot(
+	blocks: &[blockchain::block::Block],
+) -> Result<String, blockchain::error::ComputeBlockHashError> {
+	use std::fmt::Write as _;
+
+	let mut previous = std::collections::BTreeMap::new();
+	for block in blocks {
+		previous.insert(block.compute_hash()?, block.previous_hash());
+	}
+
+	// The height is counted back to the genesis block. The number of steps
+	// is limited, so a malformed chain does not loop
+	let height = |hash: &str| {
+		let mut current = hash;
+		for height in 0..previous.len() {
+			match previous.get(current)? {
+				Some(p) => current = p,
+				None => return Some(height),
+			}
+		}
+		None
+	};
+	let heights: std::collections::BTreeMap<&str, Option<usize>> =
+		previous.keys().map(|h| (h.as_str(), height(h))).collect();
+
+	// The longest branch goes back from the highest block
+	let mut longest = std::collections::BTreeSet::new();
+	let mut current = heights
+		.iter()
+		.filter_map(|(&hash, height)| Some((hash, (*height)?)))
+		.max_by_key(|&(_, height)| height)
+		.map(|(hash, _)| hash);
+	while let Some(hash) = current {
+		longest.insert(hash);
+		current = previous.get(hash).copied().flatten();
+	}
+
+	// Writing to a `String` does not fail
+	let mut rv = String::from("digraph blockchain {\n\trankdir=LR;\n");
+	for (&hash, height) in &heights {
+		let (height, style) = match height {
+			Some(h) if longest.contains(hash) => (h.to_string(), "solid"),
+			Some(h) => (h.to_string(), "dashed"),
+			None => ("?".to_owned(), "dotted"),
+		};
+		let short_hash = &hash[..SHORT_HASH_LEN.min(hash.len())];
+		writeln!(
+			rv,
+			"\t\"{hash}\" [label=\"{height}\\n{short_hash}\", style={style}];"
+		)
+		.unwrap();
+	}
+	for (hash, previous_hash) in &previous {
+		if let Some(p) = previous_hash.filter(|p| previous.contains_key(*p)) {
+			writeln!(rv, "\t\"{p}\" -> \"{hash}\";").unwrap();
+		}
+	}
+	rv.push_str("}\n");
+	Ok(rv)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_to_dot() {
+		let block = |previous_hash: Option<String>, nonce: u64| {
+			let block: blockchain::block::Block =
+				serde_json::from_value(serde_json::json!({
+					"chain_id": 0,
+					"miner": "miner",
+					"previous_hash": previous_hash,
+					"transactions": [],
+					"balance_state": {},
+					"nonce": nonce,
+					"created_at": 1.0,
+					"miner_signature": null,
+				}))
+				.unwrap();
+			let hash = block.compute_hash().unwrap();
+			(block, hash)
+		};
+		let (genesis, genesis_hash) = block(None, 0);
+		let (first, first_hash) = block(Some(genesis_hash.clone()), 1);
+		let (second, second_hash) = block(Some(first_hash.clone()), 2);
+		// A candidate of a fork and a block whose previous one is unknown
+		let (candidate, candidate_hash) = block(Some(first_hash.clone()), 3);
+		let (orphan, orphan_hash) = block(Some("f".repeat(64)), 4);
+
+		// The same blocks from another node are merged
+		let dot = super::to_dot(&[
+			genesis,
+			first.clone(),
+			second,
+			first,
+			candidate,
+			orphan,
+		])
+		.unwrap();
+		assert!(dot.starts_with("digraph blockchain {"));
+		assert_eq!(dot.matches("label=").count(), 5);
+		assert_eq!(dot.matches(" -> ").count(), 3);
+		for (previous, next) in &[
+			(&genesis_hash, &first_hash),
+			(&first_hash, &second_hash),
+			(&first_hash, &candidate_hash),
+		] {
+			assert!(dot.contains(&format!("\"{previous}\" -> \"{next}\";")));
+		}
+		assert!(dot.contains(&format!(
+			"\"{second_hash}\" [label=\"2\\n{}\", style=solid]",
+			&second_hash[..8]
+		)));
+		assert!(dot.contains(&format!(
+			"\"{candidate_hash}\" [label=\"2\\n{}\", style=dashed]",
+			&candidate_hash[..8]
+		)));
+		assert!(dot.contains(&format!(
+			"\"{orphan_hash}\" [label=\"?\\n{}\", style=dotted]",
+			&orphan_hash[..8]
+		)));
+	}
+}
@@ -0,0 +1,233 @@
+use anyhow::{Context as _, Result};
+
+/// Downloads the blocks JSON from the `url` and reconstructs the blockchain
+/// from it in the database at `path`, as a node bootstrap. The response is
+/// rejected if it is larger than `max_size` bytes. Only the `http://` URLs
+/// are supported, because the client is built without TLS.
+///
+/// The downloaded bytes are reported every `consts::DOWNLOAD_PROGRESS_EVERY`
+/// bytes and the imported blocks every `consts::IMPORT_PROGRESS_EVERY`
+/// blocks. Returns the blockchain length.
+#[tracing::instrument(skip(config, user))]
+pub(crate) fn import(
+	config: &common::config::Config,
+	user: blockchain::user::User,
+	url: &str,
+	max_size: u64,
+	path: &std::path::Path,
+) -> Result<usize> {
+	let json = download(config, url, max_size, &mut |downloaded, total| {
+		println!("Downloaded {downloaded}/{total} bytes.");
+	})?;
+
+	let mut callback = |processed, total| {
+		println!("Imported {processed}/{total} blocks.");
+	};
+	let blockchain = blockchain::Blockchain::from_str_into_with_progress(
+		user,
+		config.consensus().clone(),
+		&json,
+		path,
+		Some(&mut blockchain::progress::Progress::new(
+			crate::consts::IMPORT_PROGRESS_EVERY,
+			&mut callback,
+		)),
+	)
+	.context("Failed to import the blockchain.")?;
+	blockchain.len().context("Failed to get blockchain len.")
+}
+
+/// Downloads the body of the `url` as a string. The receive timeout of the
+/// `config` is used as the read timeout.
+///
+/// The `progress` is called with the downloaded bytes and the
+/// `Content-Length`, or the `max_size` if it is unknown, every
+/// `consts::DOWNLOAD_PROGRESS_EVERY` bytes and after the last ones.
+fn download(
+	config: &common::config::Config,
+	url: &str,
+	max_size: u64,
+	progress: &mut dyn FnMut(u64, u64),
+) -> Result<String> {
+	use std::io::Read as _;
+
+	// Otherwise, the request fails with the unclear transport error
+	anyhow::ensure!(
+		url.get(..7).is_some_and(|s| s.eq_ignore_ascii_case("http://")),
+		"Only http:// URLs are supported, the client is built without TLS."
+	);
+	let response = ureq::AgentBuilder::new()
+		.timeout_read(std::time::Duration::from_secs(
+			config.package_limits().receive_timeout_secs(),
+		))
+		.build()
+		.get(url)
+		.call()
+		.map_err(|e| match e {
+			ureq::Error::Status(code, r) => {
+				anyhow::anyhow!(
+					"The server responded with {code} {}.",
+					r.status_text()
+				)
+			}
+			ureq::Error::Transport(t) => {
+				anyhow::Error::new(t).context("Failed to request the chain.")
+			}
+		})?;
+
+	// The length is checked before the download, if it is known
+	let len =
+		response.header("Content-Length").and_then(|l| l.parse::<u64>().ok());
+	if let Some(len) = len {
+		anyhow::ensure!(
+			len <= max_size,
+			"The chain is {len} bytes, which is larger than {max_size} bytes."
+		);
+	}
+	let total = len.unwrap_or(max_size);
+
+	let mut reader = response.into_reader().take(max_size.saturating_add(1));
+	let mut bytes = Vec::new();
+	let mut buffer = [0; 8 * 1024];
+	let mut reported = 0;
+	loop {
+		let read = match reader.read(&mut buffer) {
+			Ok(0) => break,
+			Ok(n) => n,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => {
+				return Err(
+					anyhow::Error::new(e).context("Failed to read the chain.")
+				)
+			}
+		};
+		bytes.extend_from_slice(&buffer[..read]);
+		anyhow::ensure!(
+			bytes.len() as u64 <= max_size,
+			"The chain is larger than {max_size} bytes."
+		);
+		let downloaded = bytes.len() as u64;
+		if downloaded - reported >= crate::consts::DOWNLOAD_PROGRESS_EVERY {
+			progress(downloaded, total);
+			reported = downloaded;
+		}
+	}
+	let downloaded = bytes.len() as u64;
+	if downloaded != reported {
+		progress(downloaded, total);
+	}
+	String::from_utf8(bytes).context("The chain is not valid UTF-8.")
+}
+
+#[cfg(test)]
+mod tests {
+	/// Creates a chain with a genesis block and returns it as JSON with its
+	/// last block hash.
+	fn create_test_chain() -> (String, String) {
+		let consensus = serde_json::from_value(
+			serde_json::json!({"require_proof_of_work": false}),
+		)
+		.unwrap();
+		let mut blockchain = blockchain::Blockchain::load_or_create_at(
+			blockchain::user::User::generate().unwrap(),
+			consensus,
+			&crate::test_helpers::create_test_path(),
+		)
+		.unwrap();
+		blockchain.mine_genesis_block().unwrap();
+		(
+			blockchain.to_string().unwrap(),
+			blockchain.get_last_block_hash().unwrap(),
+		)
+	}
+
+	#[test]
+	fn test_import() {
+		let (chain, hash) = create_test_chain();
+		let url = crate::test_helpers::spawn_mock_http_server("200 OK", chain);
+		let config = crate::test_helpers::create_test_config(&[]);
+		let path = crate::test_helpers::create_test_path();
+
+		let len = super::import(
+			&config,
+			blockchain::user::User::generate().unwrap(),
+			&url,
+			1024 * 1024,
+			&path,
+		)
+		.unwrap();
+		assert_eq!(len, 1);
+		let imported = blockchain::Blockchain::load_or_create_at(
+			blockchain::user::User::generate().unwrap(),
+			config.consensus().clone(),
+			&path,
+		)
+		.unwrap();
+		assert_eq!(imported.get_last_block_hash().unwrap(), hash);
+	}
+
+	#[test]
+	fn test_import_http_error() {
+		let url = crate::test_helpers::spawn_mock_http_server(
+			"404 Not Found",
+			String::new(),
+		);
+		let config = crate::test_helpers::create_test_config(&[]);
+		let e =
+			super::download(&config, &url, 1024, &mut |_, _| {}).unwrap_err();
+		assert!(e.to_string().contains("404"));
+	}
+
+	#[test]
+	fn test_import_https() {
+		let config = crate::test_helpers::create_test_config(&[]);
+		let e = super::download(
+			&config,
+			"https://127.0.0.1:1/chain.json",
+			1024,
+			&mut |_, _| {},
+		)
+		.unwrap_err();
+		assert!(e.to_string().contains("Only http:// URLs are supported"));
+	}
+
+	#[test]
+	fn test_download_progress() {
+		use std::convert::TryFrom as _;
+
+		let size = crate::consts::DOWNLOAD_PROGRESS_EVERY * 2 + 1;
+		let url = crate::test_helpers::spawn_mock_http_server(
+			"200 OK",
+			"0".repeat(usize::try_from(size).unwrap()),
+		);
+		let config = crate::test_helpers::create_test_config(&[]);
+		let mut reports = Vec::new();
+		super::download(&config, &url, size, &mut |downloaded, total| {
+			reports.push((downloaded, total));
+		})
+		.unwrap();
+		// Reported against the `Content-Length` with the last bytes
+		assert!(reports.len() >= 2);
+		assert!(reports.iter().all(|&(_, total)| total == size));
+		assert!(reports.windows(2).all(|r| r[0].0 < r[1].0));
+		assert_eq!(reports.last(), Some(&(size, size)));
+	}
+
+	#[test]
+	fn test_import_oversized() {
+		let url = crate::test_helpers::spawn_mock_http_server(
+			"200 OK",
+			"[]".repeat(1024),
+		);
+		let config = crate::test_helpers::create_test_config(&[]);
+		let e =
+			super::download(&config, &url, 1024, &mut |_, _| {}).unwrap_err();
+		assert!(e.to_string().contains("larger than 1024 bytes"));
+		assert_eq!(
+			super::download(&config, &url, 2048, &mut |_, _| {})
+				.unwrap()
+				.len(),
+			2048
+		);
+	}
+}
@@ -1,5 +1,4 @@
 #![deny(clippy::correctness)]
-#![feature(error_iter)]
 #![warn(
 	clippy::complexity,
 	clippy::pedantic,
@@ -11,11 +10,21 @@
 	clippy::as_conversions,
 	clippy::implicit_return,
 	clippy::missing_docs_in_private_items,
-	clippy::missing_errors_doc
+	clippy::missing_errors_doc,
+	clippy::non_std_lazy_statics
 )]
 
 mod cli;
+mod consts;
+mod graph;
+mod import;
+mod pending;
 mod request;
+#[cfg(test)]
+mod test_helpers;
+mod tip;
+mod vanity;
+mod verbose;
 
 use anyhow::{Context as _, Result};
 
@@ -26,32 +35,254 @@ pub fn launch(args: Vec<String>) -> Result<()> {
 
 	// Basic dependencies for work
 	let opts = cli::Opts::parse_from(args);
-	let config = common::config::Config::load(None)
-		.context("Failed to load the config.")?;
-	let user = blockchain::user::User::load_or_create()
-		.context("Failed to load or create a user.")?;
+	let mut config =
+		common::config::Config::load(opts.config.as_deref(), None)
+			.context("Failed to load the config.")?;
+	apply_opts(&mut config, &opts);
+	// Not loaded by the commands which replace the key, because a new key
+	// would be created for nothing
+	let encrypt_key = opts.encrypt_key;
+	let user = || load_user(encrypt_key);
 
 	let _tracing_guard =
 		common::tracing::set_subscriber(config.tracing().client())
 			.context("Failed to set tracing subscriber.")?;
 
+	if submits_transactions(&opts.subcommand) {
+		request::check_chain_id(&config, opts.allow_chain_id_mismatch)
+			.context("Failed to check chain id.")?;
+	}
 	match opts.subcommand {
 		cli::SubCommand::User(c) => match c {
-			cli::UserSubCommand::Address => println!("{}", user.address()),
+			cli::UserSubCommand::Address => println!("{}", user()?.address()),
 			cli::UserSubCommand::Balance => {
-				request::balance(&config, user.address());
+				request::balance(&config, user()?.address().as_str());
 			}
+			cli::UserSubCommand::Vanity(c) => vanity(&c, encrypt_key)?,
+			cli::UserSubCommand::Mnemonic(c) => mnemonic(&c, encrypt_key)?,
+			cli::UserSubCommand::Recover(c) => recover(&c, encrypt_key)?,
 		},
 		cli::SubCommand::Blockchain(c) => match c {
-			cli::BlockchainSubCommand::Len => request::blockchain_len(&config),
+			cli::BlockchainSubCommand::Len(c) => {
+				request::blockchain_len(&config, c.all);
+			}
 			cli::BlockchainSubCommand::Balance(c) => {
 				request::balance(&config, &c.address);
 			}
+			cli::BlockchainSubCommand::Block(c) => {
+				request::block(&config, &c.hash);
+			}
 			cli::BlockchainSubCommand::Transaction(c) => {
-				request::transaction(&config, &user, &c.address, c.amount)
-					.context("Failed to request transaction.")?;
+				transaction(&config, &user()?, c)?;
+			}
+			cli::BlockchainSubCommand::TransactionBatch(c) => {
+				request::transaction_batch(&config, &user()?, &c.path)
+					.context("Failed to request transactions.")?;
+			}
+			cli::BlockchainSubCommand::Resend => {
+				request::resend(
+					&config,
+					&user()?,
+					&consts::PENDING_TRANSACTIONS_PATH,
+				)
+				.context("Failed to resend transactions.")?;
+			}
+			cli::BlockchainSubCommand::Mine => request::force_mine(&config),
+			cli::BlockchainSubCommand::Richlist(c) => {
+				request::richlist(&config, c.top);
+			}
+			cli::BlockchainSubCommand::Fees => request::fees(&config),
+			cli::BlockchainSubCommand::Graph(c) => {
+				request::graph(&config, &c.path)
+					.context("Failed to export the graph.")?;
+			}
+			cli::BlockchainSubCommand::Import(c) => {
+				let len = import::import(
+					&config,
+					user()?,
+					&c.url,
+					c.max_size.unwrap_or(consts::IMPORT_MAX_SIZE),
+					&blockchain::consts::DB_PATH,
+				)
+				.context("Failed to import the blockchain.")?;
+				println!("Imported. Blockchain length: {len}");
 			}
 		},
 	}
 	Ok(())
 }
+
+/// Requests the transaction of the `c`. If no node accepts it, it is saved
+/// for `client blockchain resend`, if `c.persist` is `true`.
+fn transaction(
+	config: &common::config::Config,
+	user: &blockchain::user::User,
+	c: cli::BlockchainTransactionCommand,
+) -> Result<()> {
+	let mut stdout = std::io::stdout();
+	let mut steps = if c.verbose {
+		verbose::Steps::new(&mut stdout)
+	} else {
+		verbose::Steps::disabled()
+	};
+	let accepted = request::transaction(
+		config,
+		user,
+		&c.address,
+		c.amount,
+		c.fee,
+		&mut tip::Tip::default(),
+		&mut steps,
+	)
+	.context("Failed to request transaction.")?;
+	if accepted == 0 && c.persist {
+		pending::push(
+			&consts::PENDING_TRANSACTIONS_PATH,
+			pending::PendingTransaction::new(c.address, c.amount, c.fee),
+		)
+		.context("Failed to persist the transaction.")?;
+		println!(
+			"The transaction was saved. Resend it with `client blockchain \
+			 resend`."
+		);
+	}
+	Ok(())
+}
+
+/// Loads or creates the user. If `encrypt_key` is `true`, the key of a new
+/// user is encrypted with the passphrase.
+fn load_user(encrypt_key: bool) -> Result<blockchain::user::User> {
+	if encrypt_key {
+		let passphrase = blockchain::user::prompt_passphrase()
+			.context("Failed to read the passphrase.")?;
+		blockchain::user::User::load_or_create_encrypted(&passphrase)
+	} else {
+		blockchain::user::User::load_or_create()
+	}
+	.context("Failed to load or create a user.")
+}
+
+/// Replaces the key of the user with the key of the `new_user`. The new key
+/// is encrypted if `encrypt_key` is `true` or the replaced key is encrypted.
+fn save_user(
+	new_user: &blockchain::user::User,
+	encrypt_key: bool,
+	force: bool,
+) -> Result<()> {
+	let encrypt = encrypt_key
+		|| blockchain::user::User::is_saved_encrypted()
+			.context("Failed to read the existing key.")?;
+	let passphrase = if encrypt {
+		Some(
+			blockchain::user::prompt_passphrase()
+				.context("Failed to read the passphrase.")?,
+		)
+	} else {
+		None
+	};
+	match new_user.save(passphrase.as_deref(), force) {
+		Err(blockchain::error::SaveUserError::Exists) => anyhow::bail!(
+			"The user key already exists. Use `--force` to replace it."
+		),
+		r => r.context("Failed to save the user.")?,
+	}
+	println!("The user key was replaced.");
+	Ok(())
+}
+
+/// Searches a vanity address as the `c` specifies.
+fn vanity(c: &cli::UserVanityCommand, encrypt_key: bool) -> Result<()> {
+	let threads = c.threads.unwrap_or_else(|| {
+		std::thread::available_parallelism()
+			.unwrap_or(std::num::NonZeroUsize::MIN)
+	});
+	let started = std::time::Instant::now();
+	let (vanity_user, attempts) =
+		vanity::search(&c.prefix, threads, |attempts| {
+			eprintln!(
+				"{attempts} attempts ({}/s)...",
+				vanity::rate(attempts, started.elapsed())
+			);
+		})
+		.context("Failed to search a vanity address.")?;
+	println!(
+		"{} ({attempts} attempts, {}/s)",
+		vanity_user.address(),
+		vanity::rate(attempts, started.elapsed())
+	);
+	if c.save {
+		save_user(&vanity_user, encrypt_key, c.force)?;
+	}
+	Ok(())
+}
+
+/// Generates a user from a new mnemonic and saves it, if `c.save` is set.
+fn mnemonic(c: &cli::UserMnemonicCommand, encrypt_key: bool) -> Result<()> {
+	let (mnemonic_user, phrase) = blockchain::user::User::generate_mnemonic()
+		.context("Failed to generate a mnemonic.")?;
+	println!("{phrase}");
+	println!("{}", mnemonic_user.address());
+	if c.save {
+		save_user(&mnemonic_user, encrypt_key, c.force)?;
+	}
+	Ok(())
+}
+
+/// Replaces the user key with the one recovered from the `c.words`.
+fn recover(c: &cli::UserRecoverCommand, encrypt_key: bool) -> Result<()> {
+	let recovered = blockchain::user::User::from_mnemonic(&c.words.join(" "))
+		.context("Failed to recover the user.")?;
+	println!("{}", recovered.address());
+	save_user(&recovered, encrypt_key, c.force)
+}
+
+/// Whether the `subcommand` signs transactions, so the chain id of the nodes
+/// must be checked first.
+fn submits_transactions(subcommand: &cli::SubCommand) -> bool {
+	matches!(
+		subcommand,
+		cli::SubCommand::Blockchain(
+			cli::BlockchainSubCommand::Transaction(_)
+				| cli::BlockchainSubCommand::TransactionBatch(_)
+				| cli::BlockchainSubCommand::Resend
+		)
+	)
+}
+
+/// Overrides the `config` values with the global `opts`.
+fn apply_opts(config: &mut common::config::Config, opts: &cli::Opts) {
+	if let Some(t) = opts.timeout {
+		config.override_receive_timeout_secs(t);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_timeout_override() {
+		use clap::Clap as _;
+
+		let opts = crate::cli::Opts::parse_from([
+			"client",
+			"blockchain",
+			"len",
+			"--timeout",
+			"1",
+		]);
+		let mut config = crate::test_helpers::create_test_config(&[]);
+		assert_eq!(config.package_limits().receive_timeout_secs(), 5);
+		super::apply_opts(&mut config, &opts);
+		assert_eq!(config.package_limits().receive_timeout_secs(), 1);
+
+		// A node which accepts the connection, but does not respond
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut stream =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let _silent = listener.accept().unwrap();
+		let started = std::time::Instant::now();
+		assert!(common::package::Package::receive(&config, &mut stream, None)
+			.is_err());
+		assert!(started.elapsed() < std::time::Duration::from_secs(5));
+	}
+}
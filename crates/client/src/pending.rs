@@ -0,0 +1,60 @@
+use anyhow::{Context as _, Result};
+
+/// Outgoing transaction that was not accepted by any node. It is signed again
+/// against the current last block hash when it is resent.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub(crate) struct PendingTransaction {
+	recipient: blockchain::address::Address,
+	amount: std::num::NonZeroU64,
+	#[serde(default)]
+	fee: u64,
+}
+
+impl PendingTransaction {
+	common::accessor!(& recipient -> &blockchain::address::Address);
+
+	common::accessor!(copy amount -> std::num::NonZeroU64);
+
+	common::accessor!(copy fee -> u64);
+
+	#[inline]
+	#[must_use]
+	pub fn new(
+		recipient: blockchain::address::Address,
+		amount: std::num::NonZeroU64,
+		fee: u64,
+	) -> Self {
+		Self { recipient, amount, fee }
+	}
+}
+
+/// Loads the pending transactions from `path`. If there is no file, there
+/// are no pending transactions.
+pub(crate) fn load(path: &std::path::Path) -> Result<Vec<PendingTransaction>> {
+	if !path.exists() {
+		return Ok(Vec::new());
+	}
+	let content = std::fs::read(path).context("Failed to read a file.")?;
+	serde_json::from_slice(&content)
+		.context("Failed to convert JSON to pending transactions.")
+}
+
+/// Overwrites the pending transactions at `path`.
+pub(crate) fn save(
+	path: &std::path::Path,
+	transactions: &[PendingTransaction],
+) -> Result<()> {
+	let json = serde_json::to_vec(transactions)
+		.context("Failed to convert pending transactions to JSON.")?;
+	std::fs::write(path, json).context("Failed to write a file.")
+}
+
+/// Appends `transaction` to the pending transactions at `path`.
+pub(crate) fn push(
+	path: &std::path::Path,
+	transaction: PendingTransaction,
+) -> Result<()> {
+	let mut transactions = load(path).context("Failed to load.")?;
+	transactions.push(transaction);
+	save(path, &transactions).context("Failed to save.")
+}
@@ -4,10 +4,23 @@ use anyhow::{Context as _, Result};
 /// `nodes`.
 #[tracing::instrument]
 pub(crate) fn balance(config: &common::config::Config, address: &str) {
+	for (node, balance) in request_balances(config, address) {
+		common::nprintln!(node, "Balance: {}", balance);
+	}
+}
+
+/// Requests the balance at the `address` from all `nodes`. The nodes which
+/// did not respond properly, including with a balance which is not a number,
+/// are printed and skipped.
+fn request_balances(
+	config: &common::config::Config,
+	address: &str,
+) -> Vec<(common::nodes::Node, u64)> {
 	let package = common::package::Package::new(
 		common::package::Action::GetBalance,
 		address.to_owned(),
 	);
+	let mut rv = Vec::new();
 	for node in config.nodes() {
 		let mut stream = common::connect_or_continue!(node);
 		common::send_package_or_continue!(config, package, &mut stream, node);
@@ -17,17 +30,71 @@ pub(crate) fn balance(config: &common::config::Config, address: &str) {
 			Some(common::set![common::package::Action::GetBalanceSuccess]),
 			node,
 		);
-		common::nprintln!(node, "Balance: {}", response.data());
+		match response.data().parse() {
+			Ok(balance) => rv.push((*node, balance)),
+			Err(e) => common::nprintln!(node, "Invalid balance: {}", e),
+		}
 	}
+	rv
 }
 
-/// Used to request the blockchain length for all `nodes`.
+/// Used to request the block with the `hash` from all `nodes`. The block is
+/// printed as JSON.
 #[tracing::instrument]
-pub(crate) fn blockchain_len(config: &common::config::Config) {
+pub(crate) fn block(config: &common::config::Config, hash: &str) {
+	let package = common::package::Package::new(
+		common::package::Action::GetBlockByHash,
+		hash.to_owned(),
+	);
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![common::package::Action::GetBlockByHashSuccess]),
+			node,
+		);
+		match serde_json::from_str::<Option<blockchain::block::Block>>(
+			response.data(),
+		) {
+			Ok(Some(b)) => match serde_json::to_string_pretty(&b) {
+				Ok(json) => common::nprintln!(node, "Block: {}", json),
+				Err(e) => common::nprintln!(node, "Invalid block: {}", e),
+			},
+			Ok(None) => common::nprintln!(node, "No block with the hash."),
+			Err(e) => common::nprintln!(node, "Invalid block: {}", e),
+		}
+	}
+}
+
+/// Used to request the blockchain length from all `nodes`. If `all`, the
+/// nodes are grouped by length instead, so a disagreement is seen at once.
+#[tracing::instrument]
+pub(crate) fn blockchain_len(config: &common::config::Config, all: bool) {
+	let lens = request_lens(config);
+	if all {
+		let lens: Vec<u64> = lens.into_iter().map(|(_, len)| len).collect();
+		for line in summarize_lens(&lens) {
+			println!("{line}");
+		}
+	} else {
+		for (node, len) in lens {
+			common::nprintln!(node, "Blockchain length: {}", len);
+		}
+	}
+}
+
+/// Requests the blockchain length from all `nodes`. The nodes which did not
+/// respond properly are printed and skipped.
+fn request_lens(
+	config: &common::config::Config,
+) -> Vec<(common::nodes::Node, u64)> {
 	let package = common::package::Package::new(
 		common::package::Action::GetBlockchainLen,
 		"",
 	);
+	let mut rv = Vec::new();
 	for node in config.nodes() {
 		let mut stream = common::connect_or_continue!(node);
 		common::send_package_or_continue!(config, package, &mut stream, node);
@@ -39,19 +106,268 @@ pub(crate) fn blockchain_len(config: &common::config::Config) {
 			]),
 			node,
 		);
-		common::nprintln!(node, "Blockchain length: {}", response.data());
+		match response.data().parse() {
+			Ok(len) => rv.push((*node, len)),
+			Err(e) => common::nprintln!(node, "Invalid length: {}", e),
+		}
+	}
+	rv
+}
+
+/// Groups the `lens` of the nodes from the longest one. The shorter ones are
+/// marked as behind and the disagreement is reported, because it may be a
+/// fork.
+fn summarize_lens(lens: &[u64]) -> Vec<String> {
+	let mut groups = std::collections::BTreeMap::<u64, usize>::new();
+	for &len in lens {
+		*groups.entry(len).or_default() += 1;
+	}
+	let longest = groups.keys().next_back().copied();
+	let mut rv: Vec<String> = groups
+		.iter()
+		.rev()
+		.map(|(&len, &count)| {
+			let nodes = if count == 1 { "node" } else { "nodes" };
+			let behind = if Some(len) == longest { "" } else { " (behind)" };
+			format!("{count} {nodes} at length {len}{behind}")
+		})
+		.collect();
+	rv.push(
+		match groups.len() {
+			0 => "No node responded.",
+			1 => "The nodes agree.",
+			_ => "The nodes disagree, there may be a fork.",
+		}
+		.to_owned(),
+	);
+	rv
+}
+
+/// Used to request the balances of all addresses from all `nodes` and print
+/// the `top` richest of them.
+#[tracing::instrument]
+pub(crate) fn richlist(config: &common::config::Config, top: usize) {
+	let package = common::package::Package::new(
+		common::package::Action::GetBalances,
+		"",
+	);
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![common::package::Action::GetBalancesSuccess]),
+			node,
+		);
+		let balances = match serde_json::from_str(response.data()) {
+			Ok(b) => b,
+			Err(e) => {
+				common::nprintln!(node, "Invalid balances: {}", e);
+				continue;
+			}
+		};
+		for (i, (address, balance)) in
+			richest(balances, top).into_iter().enumerate()
+		{
+			common::nprintln!(
+				node,
+				format!("{}. {address}: {balance}", i + 1)
+			);
+		}
+	}
+}
+
+/// Used to request the number of the pending transactions by fee from all
+/// `nodes`, so a fee for a new transaction can be chosen.
+#[tracing::instrument]
+pub(crate) fn fees(config: &common::config::Config) {
+	let package = common::package::Package::new(
+		common::package::Action::GetFeeHistogram,
+		"",
+	);
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![
+				common::package::Action::GetFeeHistogramSuccess
+			]),
+			node,
+		);
+		let histogram: Vec<(u64, usize)> =
+			match serde_json::from_str(response.data()) {
+				Ok(h) => h,
+				Err(e) => {
+					common::nprintln!(node, "Invalid fee histogram: {}", e);
+					continue;
+				}
+			};
+		if histogram.is_empty() {
+			common::nprintln!(node, "No pending transactions.");
+		}
+		for (fee, count) in histogram {
+			common::nprintln!(node, format!("Fee {fee}: {count}"));
+		}
+	}
+}
+
+/// Sorts the `balances` without the storage address by descending balance
+/// and then by address, and keeps the `top` of them.
+fn richest(
+	balances: std::collections::BTreeMap<String, u64>,
+	top: usize,
+) -> Vec<(String, u64)> {
+	let mut rv: Vec<_> = balances
+		.into_iter()
+		.filter(|(a, _)| a != blockchain::address::Address::storage().as_str())
+		.collect();
+	rv.sort_by(|(a1, b1), (a2, b2)| b2.cmp(b1).then_with(|| a1.cmp(a2)));
+	rv.truncate(top);
+	rv
+}
+
+/// Requests the blocks from all `nodes` and writes them to the `path` as a
+/// Graphviz DOT graph, so the branches of the nodes are seen together.
+#[tracing::instrument]
+pub(crate) fn graph(
+	config: &common::config::Config,
+	path: &std::path::Path,
+) -> Result<()> {
+	let package =
+		common::package::Package::new(common::package::Action::GetBlocks, "");
+	let mut blocks = Vec::new();
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![common::package::Action::GetBlocksSuccess]),
+			node,
+		);
+		match serde_json::from_str::<Vec<blockchain::block::Block<'static>>>(
+			response.data(),
+		) {
+			Ok(b) => blocks.extend(b),
+			Err(e) => common::nprintln!(node, "Invalid blocks: {}", e),
+		}
+	}
+
+	let dot =
+		crate::graph::to_dot(&blocks).context("Failed to make the graph.")?;
+	std::fs::write(path, dot).context("Failed to write the graph.")?;
+	println!("The graph was written to {}.", path.display());
+	Ok(())
+}
+
+/// Used to request all `nodes` to mine their pending transactions without
+/// waiting for a full block.
+#[tracing::instrument]
+pub(crate) fn force_mine(config: &common::config::Config) {
+	let package =
+		common::package::Package::new(common::package::Action::ForceMine, "");
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![
+				common::package::Action::ForceMineSuccess,
+				common::package::Action::ForceMineFail
+			]),
+			node,
+		);
+		if response.action() == common::package::Action::ForceMineSuccess {
+			common::nprintln!(
+				node,
+				"Mined. Blockchain length: {}",
+				response.data()
+			);
+		} else {
+			common::nprintln!(node, "Failed to mine: {}", response.data());
+		}
+	}
+}
+
+/// The part of the network parameters, sent by a node in response to
+/// `GetConfig`, which is checked by the client.
+#[derive(serde::Deserialize)]
+struct NetworkParams {
+	consensus: common::config::Consensus,
+}
+
+/// Requests the network parameters from all `nodes` and compares their
+/// chain id with the configured one, so the transactions are not signed for
+/// another network. A mismatch is an error, unless it is allowed by
+/// `allow_mismatch`, in which case it is only printed. The nodes which did
+/// not respond properly are skipped.
+#[tracing::instrument]
+pub(crate) fn check_chain_id(
+	config: &common::config::Config,
+	allow_mismatch: bool,
+) -> Result<()> {
+	let package =
+		common::package::Package::new(common::package::Action::GetConfig, "");
+	let expected = config.consensus().chain_id();
+	for node in config.nodes() {
+		let mut stream = common::connect_or_continue!(node);
+		common::send_package_or_continue!(config, package, &mut stream, node);
+		let response = common::receive_package_or_continue!(
+			config,
+			&mut stream,
+			Some(common::set![common::package::Action::GetConfigSuccess]),
+			node,
+		);
+		let params: NetworkParams = match serde_json::from_str(response.data())
+		{
+			Ok(p) => p,
+			Err(e) => {
+				common::nprintln!(node, "Invalid config: {}", e);
+				continue;
+			}
+		};
+		let actual = params.consensus.chain_id();
+		if actual == expected {
+			continue;
+		}
+		anyhow::ensure!(
+			allow_mismatch,
+			"The node {node} is on the chain {actual}, but the client is \
+			 configured for the chain {expected}."
+		);
+		common::nprintln!(
+			node,
+			format!(
+				"Warning: the node is on the chain {actual}, but the client \
+				 is configured for the chain {expected}."
+			)
+		);
 	}
+	Ok(())
 }
 
 /// Used to request all `nodes` to validate and add a transaction with these
 /// parameters.
-#[tracing::instrument]
+///
+/// The last block hash is taken from the `tip`, if it is known, and is
+/// requested otherwise. The `tip` is invalidated if a node rejects the
+/// transaction. Each network step is reported to `steps`.
+///
+/// Returns the number of nodes that accepted the transaction.
+#[tracing::instrument(skip(tip, steps))]
 pub(crate) fn transaction(
 	config: &common::config::Config,
 	user: &blockchain::user::User,
-	recipient: &str,
+	recipient: &blockchain::address::Address,
 	amount: std::num::NonZeroU64,
-) -> Result<()> {
+	fee: u64,
+	tip: &mut crate::tip::Tip,
+	steps: &mut crate::verbose::Steps<'_>,
+) -> Result<usize> {
 	let hash_package = common::package::Package::new(
 		common::package::Action::GetLastBlockHash,
 		"",
@@ -59,18 +375,23 @@ pub(crate) fn transaction(
 	// This package will be created after we get the hash of the last block
 	// from one of the nodes
 	let mut transaction_package: Option<common::package::Package> = None;
+	let mut accepted = 0;
 
 	for node in config.nodes() {
 		if transaction_package.is_none() {
-			// Trying to connect and get the hash of the last block
-			let mut stream = common::connect_or_continue!(node);
-			common::send_package_or_continue!(
-				config,
-				hash_package,
-				&mut stream,
-				node
-			);
-			transaction_package = {
+			if tip.get().is_none() {
+				// Trying to connect and get the hash of the last block
+				steps.step(
+					*node,
+					format_args!("Requesting the last block hash."),
+				);
+				let mut stream = common::connect_or_continue!(node);
+				common::send_package_or_continue!(
+					config,
+					hash_package,
+					&mut stream,
+					node
+				);
 				// Getting a response with the hash of the last block
 				let response = common::receive_package_or_continue!(
 					config,
@@ -80,29 +401,48 @@ pub(crate) fn transaction(
 					]),
 					node,
 				);
+				steps.step(
+					*node,
+					format_args!(
+						"Provided the last block hash: {}",
+						response.data()
+					),
+				);
+				tip.set(response.data().to_owned());
+			}
+			transaction_package = {
 				// Creating and signing a transaction
 				let mut transaction =
 					blockchain::transaction::Transaction::new(
-						user.address(),
-						recipient,
+						user.address().clone(),
+						recipient.clone(),
 						amount,
-						response.data().to_owned(),
-					);
+						tip.get().unwrap().to_owned(),
+						config.consensus().chain_id(),
+					)
+					.with_fee(fee);
 				transaction
 					.sign(user)
 					.context("Failed to sign transaction.")?;
+				transaction
+					.verify_signature_only()
+					.context("Failed to verify transaction.")?;
 				// Creating a package with a transaction
 				let data = serde_json::to_string(&transaction)
 					.context("Failed to convert transaction to JSON.")?;
+				steps.step(
+					*node,
+					format_args!("Constructed the transaction: {data}"),
+				);
 				Some(common::package::Package::new(
 					common::package::Action::AddTransaction,
 					data,
 				))
 			};
-			tracing::debug!("Transaction package was made with {node} help.");
 		}
 
 		// Send transaction request
+		steps.step(*node, format_args!("Sending the transaction."));
 		let mut stream = common::connect_or_continue!(node);
 		common::send_package_or_continue!(
 			config,
@@ -123,8 +463,15 @@ pub(crate) fn transaction(
 		// Display messages about the status of addition
 		if response.action() == common::package::Action::AddTransactionSuccess
 		{
+			accepted += 1;
+			steps.step(*node, format_args!("Accepted the transaction."));
 			common::nprintln!(node, "The transaction was successfully made.");
 		} else {
+			tip.invalidate();
+			steps.step(
+				*node,
+				format_args!("Rejected the transaction: {}", response.data()),
+			);
 			common::nprintln!(
 				node,
 				"Failed to add transaction: {}",
@@ -133,5 +480,502 @@ pub(crate) fn transaction(
 		}
 	}
 
+	Ok(accepted)
+}
+
+/// Resends the transactions which are pending at `path`. They are signed
+/// again against the current last block hash, so a transaction rejected
+/// because of an outdated hash can be accepted now.
+///
+/// Transactions that were accepted by at least one node are removed.
+#[tracing::instrument(skip(config, user))]
+pub(crate) fn resend(
+	config: &common::config::Config,
+	user: &blockchain::user::User,
+	path: &std::path::Path,
+) -> Result<()> {
+	let transactions = crate::pending::load(path)
+		.context("Failed to load pending transactions.")?;
+	let total = transactions.len();
+
+	let mut tip = crate::tip::Tip::default();
+	let mut still_pending = Vec::new();
+	let mut transactions = transactions.into_iter();
+	while let Some(t) = transactions.next() {
+		match transaction(
+			config,
+			user,
+			t.recipient(),
+			t.amount(),
+			t.fee(),
+			&mut tip,
+			&mut crate::verbose::Steps::disabled(),
+		) {
+			Ok(0) => still_pending.push(t),
+			Ok(_) => {}
+			Err(e) => {
+				// The transactions, which were not sent yet, are kept, so the
+				// accepted ones are not resent on the next run
+				still_pending.push(t);
+				still_pending.extend(transactions);
+				crate::pending::save(path, &still_pending)
+					.context("Failed to save pending transactions.")?;
+				return Err(e.context("Failed to request transaction."));
+			}
+		}
+	}
+	crate::pending::save(path, &still_pending)
+		.context("Failed to save pending transactions.")?;
+
+	println!(
+		"Resent: {}. Still pending: {}.",
+		total - still_pending.len(),
+		still_pending.len()
+	);
 	Ok(())
 }
+
+/// Submits the transactions listed at `path` one by one, in the same JSON
+/// format as the pending ones. The last block hash is requested again for
+/// each of them, because the previous one may have filled a block.
+///
+/// A failed transaction does not stop the batch. Returns the number of nodes
+/// that accepted each transaction.
+#[tracing::instrument(skip(config, user))]
+pub(crate) fn transaction_batch(
+	config: &common::config::Config,
+	user: &blockchain::user::User,
+	path: &std::path::Path,
+) -> Result<Vec<usize>> {
+	let content = std::fs::read(path).context("Failed to read a file.")?;
+	let transactions: Vec<crate::pending::PendingTransaction> =
+		serde_json::from_slice(&content)
+			.context("Failed to convert JSON to transactions.")?;
+
+	let mut rv = Vec::with_capacity(transactions.len());
+	for (i, t) in transactions.iter().enumerate() {
+		let accepted = transaction(
+			config,
+			user,
+			t.recipient(),
+			t.amount(),
+			t.fee(),
+			&mut crate::tip::Tip::default(),
+			&mut crate::verbose::Steps::disabled(),
+		)
+		.unwrap_or_else(|e| {
+			println!("Failed to request transaction: {e:?}");
+			0
+		});
+		println!(
+			"[{}/{}] {} to {}: accepted by {accepted} nodes.",
+			i + 1,
+			transactions.len(),
+			t.amount(),
+			t.recipient()
+		);
+		rv.push(accepted);
+	}
+	println!("{}", summarize_batch(&rv));
+	Ok(rv)
+}
+
+/// Counts the transactions of a batch, which were `accepted` by at least one
+/// node, and the failed ones.
+fn summarize_batch(accepted: &[usize]) -> String {
+	let failed = accepted.iter().filter(|&&a| a == 0).count();
+	format!("Submitted: {}. Failed: {failed}.", accepted.len() - failed)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_resend_after_outdated_hash() {
+		use common::package::{Action, Package};
+
+		let tip = std::sync::Arc::new(std::sync::Mutex::new("h1".to_owned()));
+		let hash_requests = std::sync::atomic::AtomicUsize::new(0);
+		let node_tip = std::sync::Arc::clone(&tip);
+		let node = crate::test_helpers::spawn_mock_node(move |package| {
+			let mut tip = node_tip.lock().unwrap();
+			match package.action() {
+				Action::GetLastBlockHash => {
+					let response = Package::new(
+						Action::GetLastBlockHashSuccess,
+						tip.clone(),
+					);
+					// The chain advances right after the first request
+					if hash_requests
+						.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
+						== 0
+					{
+						*tip = "h2".to_owned();
+					}
+					Some(response)
+				}
+				Action::AddTransaction => {
+					let transaction: blockchain::transaction::Transaction =
+						serde_json::from_str(package.data()).unwrap();
+					if transaction.previous_block_hash() == *tip {
+						Some(Package::new(Action::AddTransactionSuccess, ""))
+					} else {
+						Some(Package::new(Action::AddTransactionFail, ""))
+					}
+				}
+				_ => None,
+			}
+		});
+		let config = crate::test_helpers::create_test_config(&[node]);
+		let user = blockchain::user::User::generate().unwrap();
+		let recipient =
+			blockchain::user::User::generate().unwrap().address().clone();
+		let amount = std::num::NonZeroU64::new(5).unwrap();
+		let path = crate::test_helpers::create_test_path();
+
+		// Rejected because of the outdated hash
+		let accepted = super::transaction(
+			&config,
+			&user,
+			&recipient,
+			amount,
+			0,
+			&mut crate::tip::Tip::default(),
+			&mut crate::verbose::Steps::disabled(),
+		)
+		.unwrap();
+		assert_eq!(accepted, 0);
+		crate::pending::push(
+			&path,
+			crate::pending::PendingTransaction::new(recipient, amount, 0),
+		)
+		.unwrap();
+
+		// Resent against the current hash
+		super::resend(&config, &user, &path).unwrap();
+		assert!(crate::pending::load(&path).unwrap().is_empty());
+		assert_eq!(*tip.lock().unwrap(), "h2");
+	}
+
+	#[test]
+	fn test_resend_failed_send() {
+		use common::package::{Action, Package};
+
+		let node =
+			crate::test_helpers::spawn_mock_node(|package| {
+				match package.action() {
+					Action::GetLastBlockHash => Some(Package::new(
+						Action::GetLastBlockHashSuccess,
+						"h",
+					)),
+					Action::AddTransaction => {
+						Some(Package::new(Action::AddTransactionSuccess, ""))
+					}
+					_ => None,
+				}
+			});
+		let config = crate::test_helpers::create_test_config(&[node]);
+		let user = blockchain::user::User::generate().unwrap();
+		let path = crate::test_helpers::create_test_path();
+		// The second transaction fails to be verified before the sending
+		let recipients = [
+			blockchain::user::User::generate().unwrap().address().clone(),
+			blockchain::address::Address::storage(),
+			blockchain::user::User::generate().unwrap().address().clone(),
+		];
+		let transactions: Vec<_> = recipients
+			.iter()
+			.map(|recipient| {
+				crate::pending::PendingTransaction::new(
+					recipient.clone(),
+					std::num::NonZeroU64::new(5).unwrap(),
+					0,
+				)
+			})
+			.collect();
+		crate::pending::save(&path, &transactions).unwrap();
+
+		assert!(super::resend(&config, &user, &path).is_err());
+		let still_pending = crate::pending::load(&path).unwrap();
+		assert_eq!(
+			still_pending
+				.iter()
+				.map(crate::pending::PendingTransaction::recipient)
+				.collect::<Vec<_>>(),
+			[&recipients[1], &recipients[2]]
+		);
+	}
+
+	#[test]
+	fn test_invalid_balance() {
+		use common::package::{Action, Package};
+
+		let nodes: Vec<_> = ["12", "not a number", "-1"]
+			.iter()
+			.map(|&balance| {
+				crate::test_helpers::spawn_mock_node(move |package| {
+					(package.action() == Action::GetBalance).then(|| {
+						Package::new(Action::GetBalanceSuccess, balance)
+					})
+				})
+			})
+			.collect();
+		let config = crate::test_helpers::create_test_config(&nodes);
+
+		let address =
+			blockchain::user::User::generate().unwrap().address().clone();
+		assert_eq!(
+			super::request_balances(&config, address.as_str()),
+			[(nodes[0], 12)]
+		);
+	}
+
+	#[test]
+	fn test_chain_id_mismatch() {
+		use common::package::{Action, Package};
+
+		let node = crate::test_helpers::spawn_mock_node(|package| {
+			(package.action() == Action::GetConfig).then(|| {
+				Package::new(
+					Action::GetConfigSuccess,
+					r#"{"consensus": {"chain_id": 7}}"#,
+				)
+			})
+		});
+		let config = crate::test_helpers::create_test_config(&[node]);
+		assert_eq!(config.consensus().chain_id(), 0);
+
+		let e = super::check_chain_id(&config, false).unwrap_err();
+		assert!(e.to_string().contains("is on the chain 7"));
+		assert!(super::check_chain_id(&config, true).is_ok());
+	}
+
+	#[test]
+	fn test_transaction_batch() {
+		use common::package::{Action, Package};
+
+		let hash_requests =
+			std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		let node_hash_requests = std::sync::Arc::clone(&hash_requests);
+		let node = crate::test_helpers::spawn_mock_node(move |package| {
+			match package.action() {
+				Action::GetLastBlockHash => {
+					node_hash_requests
+						.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+					Some(Package::new(Action::GetLastBlockHashSuccess, "h"))
+				}
+				Action::AddTransaction => {
+					let transaction: blockchain::transaction::Transaction =
+						serde_json::from_str(package.data()).unwrap();
+					// Not enough money for the large amounts
+					if transaction.amount().get() < 100 {
+						Some(Package::new(Action::AddTransactionSuccess, ""))
+					} else {
+						Some(Package::new(Action::AddTransactionFail, ""))
+					}
+				}
+				_ => None,
+			}
+		});
+		let config = crate::test_helpers::create_test_config(&[node]);
+		let user = blockchain::user::User::generate().unwrap();
+		let path = crate::test_helpers::create_test_path();
+		let transactions: Vec<_> = [5, 500, 7]
+			.iter()
+			.map(|&amount| {
+				crate::pending::PendingTransaction::new(
+					blockchain::user::User::generate()
+						.unwrap()
+						.address()
+						.clone(),
+					std::num::NonZeroU64::new(amount).unwrap(),
+					0,
+				)
+			})
+			.collect();
+		crate::pending::save(&path, &transactions).unwrap();
+
+		let accepted =
+			super::transaction_batch(&config, &user, &path).unwrap();
+		assert_eq!(accepted, [1, 0, 1]);
+		assert_eq!(hash_requests.load(std::sync::atomic::Ordering::SeqCst), 3);
+		assert_eq!(
+			super::summarize_batch(&accepted),
+			"Submitted: 2. Failed: 1."
+		);
+	}
+
+	#[test]
+	fn test_summarize_lens() {
+		use common::package::{Action, Package};
+
+		let nodes: Vec<_> = [42_u64, 42, 42, 40]
+			.iter()
+			.map(|&len| {
+				crate::test_helpers::spawn_mock_node(move |package| {
+					(package.action() == Action::GetBlockchainLen).then(|| {
+						Package::new(
+							Action::GetBlockchainLenSuccess,
+							len.to_string(),
+						)
+					})
+				})
+			})
+			.collect();
+		let config = crate::test_helpers::create_test_config(&nodes);
+
+		let lens: Vec<u64> = super::request_lens(&config)
+			.into_iter()
+			.map(|(_, len)| len)
+			.collect();
+		assert_eq!(
+			super::summarize_lens(&lens),
+			[
+				"3 nodes at length 42",
+				"1 node at length 40 (behind)",
+				"The nodes disagree, there may be a fork.",
+			]
+		);
+		assert_eq!(
+			super::summarize_lens(&[7, 7]),
+			["2 nodes at length 7", "The nodes agree."]
+		);
+		assert_eq!(super::summarize_lens(&[]), ["No node responded."]);
+	}
+
+	#[test]
+	fn test_richest() {
+		let balances: std::collections::BTreeMap<String, u64> = vec![
+			("c", 5),
+			("STORAGE", 100),
+			("a", 7),
+			("d", 1),
+			("b", 7),
+			("e", 5),
+		]
+		.into_iter()
+		.map(|(a, b)| (a.to_owned(), b))
+		.collect();
+
+		let expected = [("a", 7), ("b", 7), ("c", 5), ("e", 5), ("d", 1)]
+			.map(|(a, b)| (a.to_owned(), b));
+		assert_eq!(super::richest(balances.clone(), 10), expected);
+		assert_eq!(super::richest(balances.clone(), 3), expected[..3]);
+		assert!(super::richest(balances, 0).is_empty());
+	}
+
+	#[test]
+	fn test_transaction_reuses_tip() {
+		use {
+			common::package::{Action, Package},
+			std::sync::atomic::{AtomicUsize, Ordering},
+		};
+
+		let hash_requests = std::sync::Arc::new(AtomicUsize::new(0));
+		let node_hash_requests = std::sync::Arc::clone(&hash_requests);
+		let added = AtomicUsize::new(0);
+		let node = crate::test_helpers::spawn_mock_node(move |package| {
+			match package.action() {
+				Action::GetLastBlockHash => {
+					node_hash_requests.fetch_add(1, Ordering::SeqCst);
+					Some(Package::new(Action::GetLastBlockHashSuccess, "h1"))
+				}
+				// The chain advances after the second transaction
+				Action::AddTransaction
+					if added.fetch_add(1, Ordering::SeqCst) < 2 =>
+				{
+					Some(Package::new(Action::AddTransactionSuccess, ""))
+				}
+				Action::AddTransaction => {
+					Some(Package::new(Action::AddTransactionFail, ""))
+				}
+				_ => None,
+			}
+		});
+		let config = crate::test_helpers::create_test_config(&[node]);
+		let user = blockchain::user::User::generate().unwrap();
+		let recipient =
+			blockchain::user::User::generate().unwrap().address().clone();
+		let amount = std::num::NonZeroU64::new(5).unwrap();
+		let mut tip = crate::tip::Tip::default();
+		let send = |tip: &mut crate::tip::Tip| {
+			super::transaction(
+				&config,
+				&user,
+				&recipient,
+				amount,
+				0,
+				tip,
+				&mut crate::verbose::Steps::disabled(),
+			)
+			.unwrap()
+		};
+
+		// The second transaction reuses the cached tip
+		assert_eq!(send(&mut tip), 1);
+		assert_eq!(send(&mut tip), 1);
+		assert_eq!(hash_requests.load(Ordering::SeqCst), 1);
+		assert_eq!(tip.get(), Some("h1"));
+
+		// The rejection invalidates it, so it is requested again
+		assert_eq!(send(&mut tip), 0);
+		assert_eq!(tip.get(), None);
+		assert_eq!(send(&mut tip), 0);
+		assert_eq!(hash_requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn test_verbose_transaction() {
+		use common::package::{Action, Package};
+
+		fn respond(
+			accept: bool,
+			package: &Package,
+		) -> Option<Package<'static>> {
+			match package.action() {
+				Action::GetLastBlockHash => {
+					Some(Package::new(Action::GetLastBlockHashSuccess, "h1"))
+				}
+				Action::AddTransaction if accept => {
+					Some(Package::new(Action::AddTransactionSuccess, ""))
+				}
+				Action::AddTransaction => {
+					Some(Package::new(Action::AddTransactionFail, "Too late."))
+				}
+				_ => None,
+			}
+		}
+		let accepting =
+			crate::test_helpers::spawn_mock_node(|p| respond(true, &p));
+		let rejecting =
+			crate::test_helpers::spawn_mock_node(|p| respond(false, &p));
+		let config =
+			crate::test_helpers::create_test_config(&[accepting, rejecting]);
+		let user = blockchain::user::User::generate().unwrap();
+		let recipient =
+			blockchain::user::User::generate().unwrap().address().clone();
+		let amount = std::num::NonZeroU64::new(5).unwrap();
+
+		let mut output = Vec::new();
+		let accepted = super::transaction(
+			&config,
+			&user,
+			&recipient,
+			amount,
+			0,
+			&mut crate::tip::Tip::default(),
+			&mut crate::verbose::Steps::new(&mut output),
+		)
+		.unwrap();
+		assert_eq!(accepted, 1);
+
+		let output = String::from_utf8(output).unwrap();
+		assert!([accepting, rejecting].iter().any(|n| output
+			.contains(&format!("[{n}]: Provided the last block hash: h1"))));
+		assert!(output.contains("Constructed the transaction: "));
+		assert!(output
+			.contains(&format!("[{accepting}]: Accepted the transaction.")));
+		assert!(output.contains(&format!(
+			"[{rejecting}]: Rejected the transaction: Too late."
+		)));
+	}
+}
@@ -0,0 +1,79 @@
+/// Creates a config with the passed `nodes` without loading it from a file.
+#[must_use]
+pub fn create_test_config(
+	nodes: &[common::nodes::Node],
+) -> common::config::Config {
+	serde_json::from_value(serde_json::json!({
+		"nodes": nodes,
+		"package_limits": {"max_size": 8192, "receive_timeout_secs": 5},
+		"tracing": {
+			"client": {"level": "TRACE", "path": "client-logs.log"},
+			"node": {"level": "TRACE", "path": "node-logs.log"},
+		},
+	}))
+	.unwrap()
+}
+
+/// Creates a unique path in the temporary directory.
+#[must_use]
+pub fn create_test_path() -> std::path::PathBuf {
+	static COUNTER: std::sync::atomic::AtomicUsize =
+		std::sync::atomic::AtomicUsize::new(0);
+	let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+	std::env::temp_dir()
+		.join(format!("client-test-{}-{n}.json", std::process::id()))
+}
+
+/// Starts a node which responds to each received package with the package
+/// returned by `handle`, if any.
+pub fn spawn_mock_node<F>(handle: F) -> common::nodes::Node
+where
+	F: Fn(common::package::Package) -> Option<common::package::Package<'_>>
+		+ Send
+		+ 'static,
+{
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap();
+	std::thread::spawn(move || {
+		let config = create_test_config(&[]);
+		for stream in listener.incoming() {
+			let Ok(mut stream) = stream else { continue };
+			let Ok(package) =
+				common::package::Package::receive(&config, &mut stream, None)
+			else {
+				continue;
+			};
+			if let Some(response) = handle(package) {
+				let _ = response.send(&config, &mut stream);
+			}
+		}
+	});
+	address
+}
+
+/// Starts an HTTP server which responds to each request with the `status`
+/// and the `body`. Returns its URL.
+pub fn spawn_mock_http_server(status: &'static str, body: String) -> String {
+	use std::io::{BufRead as _, Write as _};
+
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let address = listener.local_addr().unwrap();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			let Ok(mut stream) = stream else { continue };
+			// Skip the request until the empty line after the headers
+			let mut reader = std::io::BufReader::new(&stream);
+			let mut line = String::new();
+			while reader.read_line(&mut line).is_ok_and(|n| n > 2) {
+				line.clear();
+			}
+			let _ = write!(
+				stream,
+				"HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: \
+				 close\r\n\r\n{body}",
+				body.len()
+			);
+		}
+	});
+	format!("http://{address}/chain.json")
+}
@@ -0,0 +1,27 @@
+/// The last known block hash, so the consecutive transactions do not request
+/// it from a node each time.
+///
+/// It must be invalidated when a transaction is rejected, because the chain
+/// may have advanced.
+#[derive(Debug, Default)]
+pub(crate) struct Tip {
+	hash: Option<String>,
+}
+
+impl Tip {
+	#[inline]
+	#[must_use]
+	pub fn get(&self) -> Option<&str> {
+		self.hash.as_deref()
+	}
+
+	#[inline]
+	pub fn set(&mut self, hash: String) {
+		self.hash = Some(hash);
+	}
+
+	#[inline]
+	pub fn invalidate(&mut self) {
+		self.hash = None;
+	}
+}
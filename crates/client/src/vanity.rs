@@ -0,0 +1,105 @@
+use anyhow::{Context as _, Result};
+
+/// Generates users in `threads` threads until the address of one of them
+/// starts with the `prefix`. Returns it with the number of attempts.
+///
+/// `progress` is called with the number of attempts every
+/// `consts::VANITY_PROGRESS_INTERVAL`.
+pub(crate) fn search<P>(
+	prefix: &str,
+	threads: std::num::NonZeroUsize,
+	mut progress: P,
+) -> Result<(blockchain::user::User, u64)>
+where
+	P: FnMut(u64),
+{
+	use std::sync::{atomic::Ordering, mpsc::RecvTimeoutError};
+
+	validate_prefix(prefix)?;
+	let found = std::sync::atomic::AtomicBool::new(false);
+	let attempts = std::sync::atomic::AtomicU64::new(0);
+	std::thread::scope(|scope| {
+		let (sender, receiver) = std::sync::mpsc::channel();
+		for _ in 0..threads.get() {
+			let sender = sender.clone();
+			let (found, attempts) = (&found, &attempts);
+			scope.spawn(move || {
+				while !found.load(Ordering::Relaxed) {
+					let user = blockchain::user::User::generate();
+					attempts.fetch_add(1, Ordering::Relaxed);
+					let matches = user.as_ref().map_or(true, |u| {
+						u.address().as_str().starts_with(prefix)
+					});
+					// Only the first found user is sent
+					if matches && !found.swap(true, Ordering::Relaxed) {
+						// The receiver is alive until something is sent
+						sender.send(user).unwrap();
+					}
+				}
+			});
+		}
+		drop(sender);
+
+		loop {
+			match receiver
+				.recv_timeout(crate::consts::VANITY_PROGRESS_INTERVAL)
+			{
+				Ok(user) => {
+					let user = user.context("Failed to generate a user.")?;
+					return Ok((user, attempts.load(Ordering::Relaxed)));
+				}
+				Err(RecvTimeoutError::Timeout) => {
+					progress(attempts.load(Ordering::Relaxed));
+				}
+				Err(RecvTimeoutError::Disconnected) => {
+					anyhow::bail!("The search threads stopped.");
+				}
+			}
+		}
+	})
+}
+
+/// How many attempts were made per second.
+#[must_use]
+pub(crate) fn rate(attempts: u64, elapsed: std::time::Duration) -> u128 {
+	u128::from(attempts) * 1000 / elapsed.as_millis().max(1)
+}
+
+/// Checks that some address can start with the `prefix` and the search is
+/// not too long.
+fn validate_prefix(prefix: &str) -> Result<()> {
+	anyhow::ensure!(
+		prefix.chars().all(|c| common::consts::BASE58_ALPHABET.contains(c)),
+		"The prefix is not in Base58."
+	);
+	anyhow::ensure!(prefix.starts_with('1'), "Addresses start with 1.");
+	anyhow::ensure!(
+		prefix.len() <= crate::consts::VANITY_MAX_PREFIX_LEN,
+		"The prefix is longer than {}.",
+		crate::consts::VANITY_MAX_PREFIX_LEN
+	);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_search() {
+		let (user, attempts) = super::search(
+			"1",
+			std::num::NonZeroUsize::new(2).unwrap(),
+			|_| {},
+		)
+		.unwrap();
+		assert!(user.address().as_str().starts_with('1'));
+		assert!(attempts >= 1);
+	}
+
+	#[test]
+	fn test_validate_prefix() {
+		super::validate_prefix("1abc").unwrap();
+		for prefix in ["", "10", "abc", "1abcdefg"] {
+			assert!(super::validate_prefix(prefix).is_err());
+		}
+	}
+}
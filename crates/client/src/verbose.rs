@@ -0,0 +1,34 @@
+/// Prints each network step of a request on behalf of a node, if enabled.
+///
+/// The steps are always traced, so they are also in the client logs.
+pub(crate) struct Steps<'a> {
+	writer: Option<&'a mut dyn std::io::Write>,
+}
+
+impl<'a> Steps<'a> {
+	#[inline]
+	#[must_use]
+	pub fn new(writer: &'a mut dyn std::io::Write) -> Self {
+		Self { writer: Some(writer) }
+	}
+
+	#[inline]
+	#[must_use]
+	pub fn disabled() -> Self {
+		Self { writer: None }
+	}
+
+	/// Traces the step and writes it to the writer in the
+	/// [`common::nprintln`] format.
+	pub fn step(
+		&mut self,
+		node: common::nodes::Node,
+		message: std::fmt::Arguments<'_>,
+	) {
+		tracing::debug!("[{node}]: {message}");
+		if let Some(w) = self.writer.as_mut() {
+			// Verbose output is best effort and must not break the request
+			let _ = writeln!(w, "[{node}]: {message}");
+		}
+	}
+}
@@ -4,11 +4,15 @@ trait Validate {
 	fn validate(&self) -> Result<(), ValidateConfigError>;
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 #[non_exhaustive]
 pub struct Config {
 	nodes: crate::nodes::Nodes,
 	package_limits: PackageLimits,
+	#[serde(default)]
+	consensus: Consensus,
+	#[serde(default)]
+	node: NodeOptions,
 	tracing: Tracing,
 }
 
@@ -17,17 +21,79 @@ impl Config {
 
 	crate::accessor!(& package_limits -> &PackageLimits);
 
+	crate::accessor!(& consensus -> &Consensus);
+
+	crate::accessor!(& node -> &NodeOptions);
+
 	crate::accessor!(& tracing -> &Tracing);
 
 	/// # Params
 	///
+	/// `path` overrides the location of the config, see
+	/// [`Config::resolve_path`].
+	///
 	/// `exclude_node` for nodes to exclude themselves from their list.
 	#[tracing::instrument(ret)]
 	pub fn load(
+		path: Option<&std::path::Path>,
+		exclude_node: Option<crate::nodes::Node>,
+	) -> Result<Self, LoadConfigError> {
+		Self::load_from(&Self::resolve_path(path), exclude_node)
+	}
+
+	/// Loads the config file again and swaps it into `current`, so it is
+	/// applied to everyone who reads `current` afterwards. If the new config
+	/// is invalid, the old one is kept.
+	///
+	/// The running blockchain keeps the consensus it was created with, so the
+	/// new config with another consensus is rejected too.
+	///
+	/// # Params
+	///
+	/// `path` and `exclude_node` are the same as in [`Config::load`].
+	#[tracing::instrument(skip(current))]
+	pub fn reload(
+		current: &std::sync::RwLock<std::sync::Arc<Self>>,
+		path: Option<&std::path::Path>,
+		exclude_node: Option<crate::nodes::Node>,
+	) -> Result<(), LoadConfigError> {
+		Self::reload_from(&Self::resolve_path(path), current, exclude_node)
+	}
+
+	/// The location of the config: the `path` if it is passed, else the
+	/// `crate::consts::CONFIG_PATH_ENV` environment variable if it is set,
+	/// else `crate::consts::CONFIG_PATH`.
+	#[must_use]
+	pub fn resolve_path(path: Option<&std::path::Path>) -> std::path::PathBuf {
+		path.map(std::path::Path::to_path_buf)
+			.or_else(|| {
+				std::env::var_os(crate::consts::CONFIG_PATH_ENV)
+					.map(std::path::PathBuf::from)
+			})
+			.unwrap_or_else(|| crate::consts::CONFIG_PATH.clone())
+	}
+
+	fn reload_from(
+		path: &std::path::Path,
+		current: &std::sync::RwLock<std::sync::Arc<Self>>,
+		exclude_node: Option<crate::nodes::Node>,
+	) -> Result<(), LoadConfigError> {
+		let new = Self::load_from(path, exclude_node)?;
+		let mut current =
+			current.write().unwrap_or_else(std::sync::PoisonError::into_inner);
+		if new.consensus != current.consensus {
+			return Err(LoadConfigError::ConsensusChanged);
+		}
+		*current = std::sync::Arc::new(new);
+		Ok(())
+	}
+
+	fn load_from(
+		path: &std::path::Path,
 		exclude_node: Option<crate::nodes::Node>,
 	) -> Result<Self, LoadConfigError> {
 		// Read and deserialize the config file
-		let content = std::fs::read(&*crate::consts::CONFIG_PATH)?;
+		let content = std::fs::read(path)?;
 		let mut rv: Self = serde_json::from_slice(&content)?;
 
 		// Remove `exclude_node` node
@@ -38,6 +104,16 @@ impl Config {
 		rv.validate()?;
 		Ok(rv)
 	}
+
+	/// Overrides `package_limits.receive_timeout_secs` for one run, for
+	/// example, with a command line flag.
+	#[inline]
+	pub fn override_receive_timeout_secs(
+		&mut self,
+		secs: std::num::NonZeroU64,
+	) {
+		self.package_limits.receive_timeout_secs = secs.get();
+	}
 }
 
 impl Validate for Config {
@@ -46,22 +122,47 @@ impl Validate for Config {
 			return Err(ValidateConfigError::NoNodes)?;
 		}
 		self.package_limits.validate()?;
+		self.consensus.validate()?;
+		self.node.validate()?;
 		self.tracing.validate()?;
 		Ok(())
 	}
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 #[non_exhaustive]
 pub struct PackageLimits {
 	max_size: usize,
 	receive_timeout_secs: u64,
+	/// The payload is read in chunks of this size, so the memory grows with
+	/// the received bytes instead of being allocated for the declared size
+	/// at once.
+	#[serde(default = "default_read_buffer_size")]
+	read_buffer_size: usize,
+	/// The encoding of the packages. All nodes and clients of the network
+	/// must use the same one.
+	#[serde(default)]
+	encoding: PackageEncoding,
+	/// The packages longer than this are compressed with zstd, if it makes
+	/// them shorter.
+	#[serde(default = "default_compression_threshold")]
+	compression_threshold: usize,
+	#[serde(default = "default_compression_level")]
+	compression_level: i32,
 }
 
 impl PackageLimits {
 	crate::accessor!(copy max_size -> usize);
 
 	crate::accessor!(copy receive_timeout_secs -> u64);
+
+	crate::accessor!(copy read_buffer_size -> usize);
+
+	crate::accessor!(copy encoding -> PackageEncoding);
+
+	crate::accessor!(copy compression_threshold -> usize);
+
+	crate::accessor!(copy compression_level -> i32);
 }
 
 impl Validate for PackageLimits {
@@ -69,11 +170,308 @@ impl Validate for PackageLimits {
 		if self.max_size > isize::MAX as usize {
 			return Err(ValidateConfigError::InvalidPackageMaxSizeLimit);
 		}
+		if self.read_buffer_size == 0 {
+			return Err(ValidateConfigError::InvalidPackageReadBufferSize);
+		}
+		if !zstd::compression_level_range().contains(&self.compression_level) {
+			return Err(ValidateConfigError::InvalidPackageCompressionLevel);
+		}
+		Ok(())
+	}
+}
+
+const fn default_read_buffer_size() -> usize {
+	crate::consts::DEFAULT_PACKAGE_READ_BUFFER_SIZE
+}
+
+const fn default_compression_threshold() -> usize {
+	crate::consts::DEFAULT_PACKAGE_COMPRESSION_THRESHOLD
+}
+
+const fn default_compression_level() -> i32 {
+	crate::consts::DEFAULT_PACKAGE_COMPRESSION_LEVEL
+}
+
+/// How the packages are encoded after their length prefix.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageEncoding {
+	#[default]
+	Json,
+	/// More compact than JSON, but not readable.
+	Bincode,
+}
+
+/// Rules of the blockchain that all nodes of the network must agree on.
+#[derive(
+	Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize,
+)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Consensus {
+	/// Network identifier which is included in the hashes of transactions
+	/// and blocks, so they can not be replayed on another network.
+	chain_id: u32,
+	min_transaction_amount: u64,
+	min_user_transactions_per_block: u8,
+	/// If `false`, blocks are authorized by the miner signature only.
+	require_proof_of_work: bool,
+	/// Addresses which are allowed to mine. If `None`, anyone can mine.
+	authorized_miners: Option<std::collections::HashSet<String>>,
+	/// Miners which take turns: the block at the height `h` is mined only
+	/// by `miner_schedule[h % len]`. If `None`, anyone authorized can mine
+	/// at any height.
+	miner_schedule: Option<Vec<String>>,
+	/// Balance of the genesis block miner.
+	genesis_block_reward: u64,
+	/// Balance of the storage in the genesis block.
+	storage_start_balance: u64,
+	/// Paid by the storage for each mined block.
+	mining_reward: u64,
+	/// Address which receives `treasury_percent` of the mining reward.
+	treasury: Option<String>,
+	treasury_percent: u8,
+	/// If `false`, the transactions from an address to itself are rejected,
+	/// because they only take a block slot and pay the storage fee.
+	allow_self_transactions: bool,
+	/// If `true`, the blocks store only the changes of the balances of the
+	/// touched addresses instead of the whole balances, so they are smaller.
+	/// The balances are reconstructed by summing the changes.
+	balance_state_deltas: bool,
+}
+
+impl Consensus {
+	crate::accessor!(copy chain_id -> u32);
+
+	crate::accessor!(copy min_transaction_amount -> u64);
+
+	crate::accessor!(copy min_user_transactions_per_block -> u8);
+
+	crate::accessor!(copy require_proof_of_work -> bool);
+
+	crate::accessor!(
+		as_ref authorized_miners -> Option<&std::collections::HashSet<String>>
+	);
+
+	crate::accessor!(as_deref miner_schedule -> Option<&[String]>);
+
+	crate::accessor!(copy genesis_block_reward -> u64);
+
+	crate::accessor!(copy storage_start_balance -> u64);
+
+	crate::accessor!(copy mining_reward -> u64);
+
+	crate::accessor!(as_deref treasury -> Option<&str>);
+
+	crate::accessor!(copy treasury_percent -> u8);
+
+	crate::accessor!(copy allow_self_transactions -> bool);
+
+	crate::accessor!(copy balance_state_deltas -> bool);
+
+	/// The miner whose turn it is at the `height` by the `miner_schedule`.
+	/// If there is no schedule, returns `None`.
+	#[must_use]
+	pub fn scheduled_miner(&self, height: usize) -> Option<&str> {
+		self.miner_schedule
+			.as_ref()
+			.filter(|s| !s.is_empty())
+			.map(|s| s[height % s.len()].as_str())
+	}
+
+	/// Splits the `mining_reward` into the shares of the miner and the
+	/// treasury. The treasury gets `treasury_percent` of it rounded down and
+	/// the remainder goes to the miner. Without a treasury, the miner gets
+	/// all.
+	#[must_use]
+	pub fn split_mining_reward(&self) -> (u64, u64) {
+		if self.treasury.is_none() {
+			return (self.mining_reward, 0);
+		}
+		// Does not overflow unlike `mining_reward * percent / 100`
+		let percent = u64::from(self.treasury_percent);
+		let treasury = self.mining_reward / 100 * percent
+			+ self.mining_reward % 100 * percent / 100;
+		(self.mining_reward - treasury, treasury)
+	}
+}
+
+impl Default for Consensus {
+	fn default() -> Self {
+		Self {
+			chain_id: 0,
+			min_transaction_amount: 0,
+			min_user_transactions_per_block: 1,
+			require_proof_of_work: true,
+			authorized_miners: None,
+			miner_schedule: None,
+			genesis_block_reward: 100,
+			storage_start_balance: 100,
+			mining_reward: 1,
+			treasury: None,
+			treasury_percent: 0,
+			allow_self_transactions: false,
+			balance_state_deltas: false,
+		}
+	}
+}
+
+impl Validate for Consensus {
+	fn validate(&self) -> Result<(), ValidateConfigError> {
+		if self.min_user_transactions_per_block == 0 {
+			return Err(
+				ValidateConfigError::InvalidMinUserTransactionsPerBlock,
+			);
+		} else if self.mining_reward == 0 {
+			return Err(ValidateConfigError::InvalidMiningReward);
+		} else if self.treasury_percent > 100 {
+			return Err(ValidateConfigError::InvalidTreasuryPercent);
+		} else if self.miner_schedule.as_ref().is_some_and(Vec::is_empty) {
+			return Err(ValidateConfigError::EmptyMinerSchedule);
+		}
+		Ok(())
+	}
+}
+
+/// Settings of the node, which do not affect other nodes.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct NodeOptions {
+	validate_served_chain: bool,
+	/// Connections beyond this cap are closed with a busy package.
+	max_connections: usize,
+	/// How many times a mined block is resent to the nodes which did not
+	/// receive it.
+	block_send_retries: u32,
+	/// Delay before the first resending of a mined block. It is doubled
+	/// before each next one, up to 10 seconds.
+	block_send_backoff_ms: u64,
+	/// Timeout of connecting and writing to each node, so a slow node does
+	/// not hold the broadcast.
+	block_send_timeout_ms: u64,
+	/// How many mined blocks may wait for the broadcast. If the queue is
+	/// full, the new block is dropped, because the queued ones are its
+	/// ancestors and must reach the nodes first.
+	block_broadcast_queue_size: usize,
+	/// Actions which are rejected on receiving. For example, a query-only
+	/// node disallows `AddBlock` and `AddTransaction`.
+	disallowed_actions: std::collections::HashSet<crate::package::Action>,
+	/// A received block or the last own block older than this is warned
+	/// about, because the network may be stalled.
+	stale_block_secs: u64,
+	/// If `true`, the actions which only nodes may send, like `AddBlock`, are
+	/// rejected on receiving from the addresses not in the `nodes`.
+	reject_unknown_writers: bool,
+	/// If `true`, the accepted transactions are relayed to the `nodes` with
+	/// `BroadcastTransaction`.
+	relay_transactions: bool,
+	/// If `true`, blocks are mined by a background thread, so the handling
+	/// of the transaction which fills a block does not wait for the mining.
+	background_mining: bool,
+	/// With `background_mining`, how long a full block waits before mining,
+	/// so a transaction with a higher fee can still take its last slot.
+	last_slot_window_ms: u64,
+	/// How long the oldest pending transaction waits for a full block before
+	/// a partial block is mined anyway. If zero, only full blocks are mined
+	/// automatically.
+	partial_block_grace_ms: u64,
+	/// How long the last block may stay the same before the blockchain is
+	/// moved from a node with a longer one. If zero, the stalled blockchain
+	/// is not detected.
+	stall_resync_secs: u64,
+	/// If `true`, the blockchain is requested from the other nodes
+	/// compressed, see [`crate::consts::GET_BLOCKS_GZIP`].
+	compress_blocks_transfer: bool,
+	/// How many times the mining is retried, if it is stopped by a new
+	/// block. The pending transactions, which are still valid, are mined
+	/// again.
+	mining_retries: u32,
+	/// How many received blocks may wait for their parents. If the pool is
+	/// full, the oldest block is evicted.
+	orphan_pool_size: usize,
+}
+
+impl NodeOptions {
+	crate::accessor!(copy validate_served_chain -> bool);
+
+	crate::accessor!(copy max_connections -> usize);
+
+	crate::accessor!(copy block_send_retries -> u32);
+
+	crate::accessor!(copy block_send_backoff_ms -> u64);
+
+	crate::accessor!(copy block_send_timeout_ms -> u64);
+
+	crate::accessor!(copy block_broadcast_queue_size -> usize);
+
+	crate::accessor!(
+		& disallowed_actions
+			-> &std::collections::HashSet<crate::package::Action>
+	);
+
+	crate::accessor!(copy stale_block_secs -> u64);
+
+	crate::accessor!(copy reject_unknown_writers -> bool);
+
+	crate::accessor!(copy relay_transactions -> bool);
+
+	crate::accessor!(copy background_mining -> bool);
+
+	crate::accessor!(copy last_slot_window_ms -> u64);
+
+	crate::accessor!(copy partial_block_grace_ms -> u64);
+
+	crate::accessor!(copy stall_resync_secs -> u64);
+
+	crate::accessor!(copy compress_blocks_transfer -> bool);
+
+	crate::accessor!(copy mining_retries -> u32);
+
+	crate::accessor!(copy orphan_pool_size -> usize);
+}
+
+impl Default for NodeOptions {
+	fn default() -> Self {
+		Self {
+			validate_served_chain: false,
+			max_connections: 64,
+			block_send_retries: 3,
+			block_send_backoff_ms: 500,
+			block_send_timeout_ms: 5000,
+			block_broadcast_queue_size: 16,
+			disallowed_actions: std::collections::HashSet::new(),
+			stale_block_secs: 600,
+			reject_unknown_writers: false,
+			relay_transactions: false,
+			background_mining: false,
+			last_slot_window_ms: 0,
+			partial_block_grace_ms: 0,
+			stall_resync_secs: 0,
+			compress_blocks_transfer: false,
+			mining_retries: 1,
+			orphan_pool_size: 16,
+		}
+	}
+}
+
+impl Validate for NodeOptions {
+	fn validate(&self) -> Result<(), ValidateConfigError> {
+		if self.max_connections == 0 {
+			return Err(ValidateConfigError::InvalidMaxConnections);
+		}
+		if self.block_send_timeout_ms == 0 {
+			return Err(ValidateConfigError::InvalidBlockSendTimeout);
+		}
+		if self.block_broadcast_queue_size == 0 {
+			return Err(ValidateConfigError::InvalidBlockBroadcastQueueSize);
+		}
 		Ok(())
 	}
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 pub struct Tracing {
 	client: TracingTarget,
 	node: TracingTarget,
@@ -93,16 +491,27 @@ impl Validate for Tracing {
 	}
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Clone, Debug, serde::Deserialize)]
 pub struct TracingTarget {
 	level: String,
 	path: String,
+	#[serde(default)]
+	rotation: TracingRotation,
+	/// Fields which are removed from the records, for example, `signature`
+	/// or `return` of the instrumented functions. The core Bunyan fields,
+	/// like `msg`, can not be redacted.
+	#[serde(default)]
+	redacted_fields: Vec<String>,
 }
 
 impl TracingTarget {
 	crate::accessor!(& level -> &str);
 
 	crate::accessor!(& path -> &str);
+
+	crate::accessor!(copy rotation -> TracingRotation);
+
+	crate::accessor!(& redacted_fields -> &[String]);
 }
 
 impl Validate for TracingTarget {
@@ -110,6 +519,184 @@ impl Validate for TracingTarget {
 		if self.level.parse::<tracing::Level>().is_err() {
 			return Err(ValidateConfigError::InvalidTracingLevel);
 		}
+		tracing_bunyan_formatter::BunyanFormattingLayer::new(
+			String::new(),
+			std::io::sink,
+		)
+		.skip_fields(self.redacted_fields.iter().map(String::as_str))
+		.map_err(ValidateConfigError::InvalidRedactedField)?;
+		if self.path != crate::consts::TRACING_STDOUT_PATH {
+			self.validate_path_writable()?;
+		}
 		Ok(())
 	}
 }
+
+impl TracingTarget {
+	/// Checks that the log files can be created next to the `self.path`: the
+	/// nearest existing ancestor of it, in which the appender creates the
+	/// missing directories, must be a directory and not read-only. Nothing
+	/// is created, so the validation has no side effects.
+	fn validate_path_writable(&self) -> Result<(), ValidateConfigError> {
+		let path = std::path::Path::new(&self.path);
+		let not_writable = |e| {
+			ValidateConfigError::TracingPathNotWritable(path.to_owned(), e)
+		};
+
+		let ancestor = path
+			.ancestors()
+			.skip(1)
+			.map(|a| {
+				if a.as_os_str().is_empty() {
+					std::path::Path::new(".")
+				} else {
+					a
+				}
+			})
+			.find(|a| a.exists())
+			.ok_or_else(|| {
+				not_writable(std::io::ErrorKind::NotFound.into())
+			})?;
+		let metadata = std::fs::metadata(ancestor).map_err(not_writable)?;
+		if !metadata.is_dir() {
+			return Err(not_writable(std::io::Error::other(format!(
+				"{} is not a directory.",
+				ancestor.display()
+			))));
+		} else if metadata.permissions().readonly() {
+			return Err(not_writable(
+				std::io::ErrorKind::PermissionDenied.into(),
+			));
+		}
+		Ok(())
+	}
+}
+
+/// How often a new log file is started. The files are rotated by the time
+/// only: `tracing_appender` can not roll them by the size, so the size-based
+/// rotation is not supported.
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TracingRotation {
+	Minutely,
+	Hourly,
+	Daily,
+	#[default]
+	Never,
+}
+
+#[cfg(test)]
+mod tests {
+	fn write_config(path: &std::path::Path, nodes: &[&str]) {
+		let json = serde_json::json!({
+			"nodes": nodes,
+			"package_limits": {"max_size": 8192, "receive_timeout_secs": 5},
+			"tracing": {
+				"client": {"level": "TRACE", "path": "client-logs.log"},
+				"node": {"level": "TRACE", "path": "node-logs.log"},
+			},
+		});
+		std::fs::write(path, json.to_string()).unwrap();
+	}
+
+	#[test]
+	fn test_unwritable_tracing_path() {
+		use super::Validate as _;
+
+		// A file can not be a parent directory, even for the root user
+		let dir = tempfile::tempdir().unwrap();
+		let file = dir.path().join("not-dir");
+		std::fs::write(&file, "").unwrap();
+		let missing = dir.path().join("logs");
+		for (path, writable) in [
+			(missing.join("node.log"), true),
+			(file.join("node.log"), false),
+			(file.join("logs/node.log"), false),
+		] {
+			let target: super::TracingTarget =
+				serde_json::from_value(serde_json::json!({
+					"level": "INFO",
+					"path": path,
+				}))
+				.unwrap();
+			assert_eq!(
+				!writable,
+				matches!(
+					target.validate(),
+					Err(crate::error::ValidateConfigError::TracingPathNotWritable(
+						..
+					))
+				)
+			);
+		}
+		// Nothing is created by the validation
+		assert!(!missing.exists());
+	}
+
+	#[test]
+	fn test_load_from_path() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("custom-config.json");
+		write_config(&path, &["127.0.0.1:8000", "127.0.0.1:8001"]);
+		assert_eq!(super::Config::resolve_path(Some(&path)), path);
+
+		let config = super::Config::load(
+			Some(&path),
+			Some("127.0.0.1:8001".parse().unwrap()),
+		)
+		.unwrap();
+		assert_eq!(config.nodes().len(), 1);
+		assert!(config.nodes().contains(&"127.0.0.1:8000".parse().unwrap()));
+	}
+
+	#[test]
+	fn test_reload() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("config.json");
+		write_config(&path, &["127.0.0.1:8000"]);
+		let current = std::sync::RwLock::new(std::sync::Arc::new(
+			super::Config::load_from(&path, None).unwrap(),
+		));
+
+		// The changed nodes list is picked up
+		write_config(&path, &["127.0.0.1:8000", "127.0.0.1:8001"]);
+		super::Config::reload_from(&path, &current, None).unwrap();
+		assert_eq!(current.read().unwrap().nodes().len(), 2);
+
+		// The invalid config is rejected and the old one is kept
+		write_config(&path, &[]);
+		assert!(matches!(
+			super::Config::reload_from(&path, &current, None),
+			Err(crate::error::LoadConfigError::ValidateConfig(_))
+		));
+		assert_eq!(current.read().unwrap().nodes().len(), 2);
+	}
+
+	#[test]
+	fn test_reload_changed_consensus() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("config.json");
+		write_config(&path, &["127.0.0.1:8000"]);
+		let current = std::sync::RwLock::new(std::sync::Arc::new(
+			super::Config::load_from(&path, None).unwrap(),
+		));
+
+		// The running blockchain does not enforce the new consensus
+		let mut json: serde_json::Value =
+			serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+		json["nodes"] =
+			serde_json::json!(["127.0.0.1:8000", "127.0.0.1:8001"]);
+		json["consensus"] = serde_json::json!({"chain_id": 2});
+		std::fs::write(&path, json.to_string()).unwrap();
+		assert!(matches!(
+			super::Config::reload_from(&path, &current, None),
+			Err(crate::error::LoadConfigError::ConsensusChanged)
+		));
+		let current = current.read().unwrap();
+		assert_eq!(current.nodes().len(), 1);
+		assert_eq!(
+			current.consensus().chain_id(),
+			super::Consensus::default().chain_id()
+		);
+	}
+}
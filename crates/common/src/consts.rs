@@ -4,3 +4,35 @@ lazy_static::lazy_static! {
 	pub(crate) static ref CONFIG_PATH: std::path::PathBuf =
 		RESOURCES_DIR.join("config.json");
 }
+
+/// The environment variable which overrides the config path, if there is no
+/// explicit one.
+pub const CONFIG_PATH_ENV: &str = "CRYPTO_CONFIG";
+
+/// The address of the storage, which pays the mining rewards.
+pub const STORAGE_ADDRESS: &str = "STORAGE";
+
+/// Base58 alphabet of the addresses, which has no `0`, `O`, `I` and `l`.
+pub const BASE58_ALPHABET: &str =
+	"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Lengths of the Base58 strings, which may be decoded to the 25 bytes of an
+/// address: each leading zero byte is one character and the rest takes up to
+/// 35 characters in total.
+pub const ADDRESS_ENCODED_LEN_RANGE: std::ops::RangeInclusive<usize> = 25..=35;
+
+/// `GetBlocks` data, which asks for the blocks compressed with gzip and
+/// encoded with Base64.
+pub const GET_BLOCKS_GZIP: &str = "gzip";
+
+/// Tracing target path, which means that the logs are written to the stdout.
+pub const TRACING_STDOUT_PATH: &str = "stdout";
+
+/// Default `package_limits.read_buffer_size`.
+pub(crate) const DEFAULT_PACKAGE_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default `package_limits.compression_threshold`.
+pub(crate) const DEFAULT_PACKAGE_COMPRESSION_THRESHOLD: usize = 64 * 1024;
+
+/// Default `package_limits.compression_level`.
+pub(crate) const DEFAULT_PACKAGE_COMPRESSION_LEVEL: i32 = 3;
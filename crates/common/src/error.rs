@@ -3,6 +3,8 @@
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum LoadConfigError {
+	#[error("The consensus differs from the one of the running blockchain.")]
+	ConsensusChanged,
 	#[error("Failed to convert JSON to config.")]
 	FromJson(#[from] serde_json::Error),
 	#[error("Failed to validate the config.")]
@@ -14,21 +16,33 @@ pub enum LoadConfigError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ReceivePackageError {
+	#[error("Failed to convert bincode to package.")]
+	FromBincode(#[from] bincode::Error),
 	#[error("Failed to convert JSON to package.")]
 	FromJson(#[from] serde_json::Error),
 	#[error("Invalid action.")]
 	InvalidAction,
+	#[error("Package is not valid UTF-8.")]
+	NotUtf8(#[from] std::str::Utf8Error),
 	#[error("Failed to receive bytes.")]
 	ReceiveBytes(#[from] ReceivePackageBytesError),
+	#[error("Failed to validate data.")]
+	ValidateData(#[from] ValidatePackageDataError),
 }
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ReceivePackageBytesError {
+	#[error("Failed to decompress a bytes.")]
+	Decompress(#[source] std::io::Error),
+	#[error("Unknown compression flag {0}.")]
+	InvalidFlag(u8),
 	#[error("Package is too big.")]
 	TooBig,
 	#[error("Failed to read a bytes.")]
 	ReadBytes(#[source] std::io::Error),
+	#[error("Failed to read a compression flag.")]
+	ReadFlag(#[source] std::io::Error),
 	#[error("Failed to read a len.")]
 	ReadLen(#[source] std::io::Error),
 	#[error("Timeout error.")]
@@ -38,12 +52,18 @@ pub enum ReceivePackageBytesError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum SendPackageError {
+	#[error("Failed to compress a package.")]
+	Compress(#[source] std::io::Error),
+	#[error("Failed to convert a package to bincode.")]
+	ToBincode(#[from] bincode::Error),
 	#[error("Failed to convert a package to JSON.")]
 	ToJson(#[from] serde_json::Error),
 	#[error("Package is too big.")]
 	TooBig,
 	#[error("Failed to write a bytes.")]
 	WriteBytes(#[source] std::io::Error),
+	#[error("Failed to write a compression flag.")]
+	WriteFlag(#[source] std::io::Error),
 	#[error("Failed to write a len.")]
 	WriteLen(#[source] std::io::Error),
 }
@@ -51,6 +71,8 @@ pub enum SendPackageError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum SetTracingSubscriberError {
+	#[error("Failed to redact the fields.")]
+	RedactFields(#[from] tracing_bunyan_formatter::SkipFieldError),
 	#[error("Failed to set the global default.")]
 	SetGlobalDefault(#[from] tracing::subscriber::SetGlobalDefaultError),
 }
@@ -58,10 +80,41 @@ pub enum SetTracingSubscriberError {
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum ValidateConfigError {
+	#[error("Miner schedule is empty.")]
+	EmptyMinerSchedule,
+	#[error("Block broadcast queue size is zero.")]
+	InvalidBlockBroadcastQueueSize,
+	#[error("Block send timeout is zero.")]
+	InvalidBlockSendTimeout,
 	#[error("Invalid tracing level.")]
 	InvalidTracingLevel,
+	#[error("Maximum connections is zero.")]
+	InvalidMaxConnections,
+	#[error("Minimum user transactions per block is zero.")]
+	InvalidMinUserTransactionsPerBlock,
+	#[error("Mining reward is zero.")]
+	InvalidMiningReward,
+	#[error("Treasury percent is greater than 100.")]
+	InvalidTreasuryPercent,
+	#[error("Package's compression level is not supported by zstd.")]
+	InvalidPackageCompressionLevel,
 	#[error("Package's max size limit is greater than isize::MAX.")]
 	InvalidPackageMaxSizeLimit,
+	#[error("Package's read buffer size is zero.")]
+	InvalidPackageReadBufferSize,
+	#[error("Tracing field can not be redacted.")]
+	InvalidRedactedField(#[source] tracing_bunyan_formatter::SkipFieldError),
 	#[error("The list of nodes is empty.")]
 	NoNodes,
+	#[error("Tracing path {0:?} is not writable.")]
+	TracingPathNotWritable(std::path::PathBuf, #[source] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidatePackageDataError {
+	#[error("Data is not a Base58 address.")]
+	InvalidAddress,
+	#[error("Data is not a hex hash.")]
+	InvalidHash,
 }
@@ -10,7 +10,8 @@
 	clippy::as_conversions,
 	clippy::implicit_return,
 	clippy::missing_docs_in_private_items,
-	clippy::missing_errors_doc
+	clippy::missing_errors_doc,
+	clippy::non_std_lazy_statics
 )]
 
 pub mod config;
@@ -19,4 +20,6 @@ pub mod error;
 mod macros;
 pub mod nodes;
 pub mod package;
+#[cfg(any(test, feature = "test-helpers"))]
+pub mod test_helpers;
 pub mod tracing;
@@ -1,7 +1,18 @@
-use crate::error::{
-	ReceivePackageBytesError, ReceivePackageError, SendPackageError,
+use crate::{
+	config::PackageEncoding,
+	error::{
+		ReceivePackageBytesError, ReceivePackageError, SendPackageError,
+		ValidatePackageDataError,
+	},
 };
 
+/// The length of the hex SHA-256 hash.
+const HASH_LEN: usize = 64;
+/// The first byte of a package, which is sent as it is.
+const UNCOMPRESSED_FLAG: u8 = 0;
+/// The first byte of a package, which is compressed with zstd.
+const ZSTD_FLAG: u8 = 1;
+
 /// `Package` action.
 #[derive(
 	Clone,
@@ -15,17 +26,71 @@ use crate::error::{
 )]
 pub enum Action {
 	AddBlock,
+	AddBlockBuffered,
+	AddBlockFail,
+	AddBlockSuccess,
 	AddTransaction,
 	AddTransactionFail,
 	AddTransactionSuccess,
+	BroadcastTransaction,
+	ForceMine,
+	ForceMineFail,
+	ForceMineSuccess,
+	GetAddresses,
+	GetAddressesSuccess,
 	GetBalance,
 	GetBalanceSuccess,
+	GetBalances,
+	GetBalancesSuccess,
+	GetBlockByHash,
+	GetBlockByHashSuccess,
 	GetBlockchainLen,
 	GetBlockchainLenSuccess,
 	GetBlocks,
 	GetBlocksSuccess,
+	GetBlocksSince,
+	GetBlocksSinceSuccess,
+	GetBlockStats,
+	GetBlockStatsSuccess,
+	GetConfig,
+	GetConfigSuccess,
+	GetFeeHistogram,
+	GetFeeHistogramSuccess,
+	GetInclusionProof,
+	GetInclusionProofSuccess,
 	GetLastBlockHash,
 	GetLastBlockHashSuccess,
+	GetLocator,
+	GetLocatorSuccess,
+	GetStatus,
+	GetStatusSuccess,
+	ServerBusy,
+}
+
+impl Action {
+	/// The shape of the data of the action, which is checked on receiving,
+	/// so the malformed data does not reach the blockchain. `None` if the
+	/// data is parsed by the handler.
+	#[must_use]
+	pub const fn data_shape(self) -> Option<DataShape> {
+		match self {
+			Self::GetBalance => Some(DataShape::Address),
+			Self::GetBlockByHash | Self::GetBlocksSince => {
+				Some(DataShape::Hash)
+			}
+			_ => None,
+		}
+	}
+}
+
+/// The shapes of the `Package` data. See [`Action::data_shape`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DataShape {
+	/// A Base58 address or the storage address. Its checksum is not
+	/// checked.
+	Address,
+	/// A hex SHA-256 hash.
+	Hash,
 }
 
 /// The structure that is required for each shipment. It makes it easy to
@@ -51,11 +116,20 @@ impl<'a> Package<'a> {
 	}
 
 	/// Receiving `Self` with `config.package_limits().receive_timeout()` and
-	/// validates action with `accepted_actions`.
+	/// validates action with `accepted_actions`. The bytes are decoded with
+	/// `config.package_limits().encoding()`, must be UTF-8 and the data must
+	/// have the [`Action::data_shape`].
+	///
+	/// The received payload size and the elapsed time are recorded to the
+	/// span.
 	///
 	/// See also: [`send`](Package::send).
 	#[tracing::instrument(
-		fields(max_size = config.package_limits().max_size()),
+		fields(
+			max_size = config.package_limits().max_size(),
+			bytes = tracing::field::Empty,
+			elapsed_secs = tracing::field::Empty,
+		),
 		level = tracing::Level::DEBUG,
 		skip(config, stream),
 	)]
@@ -64,26 +138,65 @@ impl<'a> Package<'a> {
 		stream: &mut std::net::TcpStream,
 		accepted_actions: Option<std::collections::HashSet<Action>>,
 	) -> Result<Self, ReceivePackageError> {
+		use bincode::Options as _;
+
 		let bytes = Self::receive_bytes(config, stream)?;
-		let package: Self = serde_json::from_slice(&bytes)?;
+		let package: Self = match config.package_limits().encoding() {
+			PackageEncoding::Json => {
+				serde_json::from_str(std::str::from_utf8(&bytes)?)?
+			}
+			// The limit stops a length inside the bytes from allocating more
+			// than the package may have
+			PackageEncoding::Bincode => bincode::options()
+				.with_limit(config.package_limits().max_size() as u64)
+				.deserialize(&bytes)?,
+		};
 		if let Some(aa) = accepted_actions {
 			if !aa.contains(&package.action) {
 				return Err(ReceivePackageError::InvalidAction);
 			}
 		}
+		package.validate_data()?;
 		Ok(package)
 	}
 
+	/// Checks that the data has the [`Action::data_shape`].
+	pub fn validate_data(&self) -> Result<(), ValidatePackageDataError> {
+		let data = &*self.data;
+		match self.action.data_shape() {
+			Some(DataShape::Address)
+				if data != crate::consts::STORAGE_ADDRESS
+					&& (!crate::consts::ADDRESS_ENCODED_LEN_RANGE
+						.contains(&data.len())
+						|| !data.chars().all(|c| {
+							crate::consts::BASE58_ALPHABET.contains(c)
+						})) =>
+			{
+				Err(ValidatePackageDataError::InvalidAddress)
+			}
+			Some(DataShape::Hash)
+				if data.len() != HASH_LEN
+					|| !data.bytes().all(|b| b.is_ascii_hexdigit()) =>
+			{
+				Err(ValidatePackageDataError::InvalidHash)
+			}
+			_ => Ok(()),
+		}
+	}
+
 	/// Receiving `Self` bytes with
-	/// `config.package_limits().receive_timeout_secs()` timeout.
+	/// `config.package_limits().receive_timeout_secs()` timeout. The
+	/// compressed bytes are decompressed, and `max_size` limits them after
+	/// the decompression too.
 	///
 	/// See also: [`send`](Package::send).
 	fn receive_bytes(
 		config: &crate::config::Config,
 		stream: &mut std::net::TcpStream,
 	) -> Result<Box<[u8]>, ReceivePackageBytesError> {
-		use std::io::Read as _;
+		use std::{convert::TryFrom as _, io::Read as _};
 
+		let started = std::time::Instant::now();
 		// Get an old and set a new timeout
 		let old_timeout = stream
 			.read_timeout()
@@ -94,39 +207,89 @@ impl<'a> Package<'a> {
 			)))
 			.map_err(ReceivePackageBytesError::Timeout)?;
 
-		// Receive a size
+		// Receive the compression flag
+		let compressed = {
+			let mut flag = [0; 1];
+			stream
+				.read_exact(&mut flag)
+				.map_err(ReceivePackageBytesError::ReadFlag)?;
+			match flag[0] {
+				UNCOMPRESSED_FLAG => false,
+				ZSTD_FLAG => true,
+				f => return Err(ReceivePackageBytesError::InvalidFlag(f)),
+			}
+		};
+
+		// Receive a size. It is always 8 bytes, so it is read as `u64` and
+		// converted to `usize`, which may be narrower
 		let size = {
 			let mut be_bytes_buffer = [0; 8];
 			stream
 				.read_exact(&mut be_bytes_buffer)
 				.map_err(ReceivePackageBytesError::ReadLen)?;
-			usize::from_be_bytes(be_bytes_buffer)
+			u64::from_be_bytes(be_bytes_buffer)
+		};
+		let size = match usize::try_from(size) {
+			Ok(s)
+				if s <= config.package_limits().max_size()
+					&& isize::try_from(s).is_ok() =>
+			{
+				s
+			}
+			_ => return Err(ReceivePackageBytesError::TooBig),
 		};
-		if size > config.package_limits().max_size() {
-			return Err(ReceivePackageBytesError::TooBig);
-		}
 
-		// Receive a bytes
-		let mut bytes_buffer = vec![0; size].into_boxed_slice();
-		stream
-			.read_exact(&mut bytes_buffer)
-			.map_err(ReceivePackageBytesError::ReadBytes)?;
+		// Receive a bytes in chunks of `read_buffer_size`, so the buffer only
+		// grows as the bytes actually arrive
+		let mut chunk =
+			vec![0; size.min(config.package_limits().read_buffer_size())];
+		let mut bytes_buffer = Vec::with_capacity(chunk.len());
+		while bytes_buffer.len() < size {
+			let chunk_size = chunk.len().min(size - bytes_buffer.len());
+			stream
+				.read_exact(&mut chunk[..chunk_size])
+				.map_err(ReceivePackageBytesError::ReadBytes)?;
+			bytes_buffer.extend_from_slice(&chunk[..chunk_size]);
+			tracing::trace!(
+				received = bytes_buffer.len(),
+				size,
+				"Package chunk received."
+			);
+		}
 
 		// Set the old timeout
 		stream
 			.set_read_timeout(old_timeout)
 			.map_err(ReceivePackageBytesError::Timeout)?;
 
-		tracing::debug!("{size} bytes received...");
-		Ok(bytes_buffer)
+		if compressed {
+			bytes_buffer =
+				decompress(&bytes_buffer, config.package_limits().max_size())?;
+		}
+
+		let elapsed_secs = started.elapsed().as_secs_f64();
+		tracing::Span::current()
+			.record("bytes", size)
+			.record("elapsed_secs", elapsed_secs);
+		tracing::debug!(bytes = size, elapsed_secs, "Package received.");
+		Ok(bytes_buffer.into_boxed_slice())
 	}
 
 	/// Sends `self` to [`stream`](std::net::TcpStream).
 	///
-	/// First it sends a data with a length of 8 bytes, which contains the
-	/// length of the `self`. Then it sends the `self`'s bytes.
+	/// First it sends a byte, which tells whether the `self`'s bytes are
+	/// compressed, and a data with a length of 8 bytes, which contains the
+	/// length of the bytes. Then it sends the `self`'s bytes, encoded with
+	/// `config.package_limits().encoding()`. They are compressed with zstd,
+	/// if they are longer than `config.package_limits().compression_threshold()`
+	/// and the compression makes them shorter. The payload size and the
+	/// elapsed time are recorded to the span.
 	#[tracing::instrument(
-		fields(max_size = config.package_limits().max_size()),
+		fields(
+			max_size = config.package_limits().max_size(),
+			bytes = tracing::field::Empty,
+			elapsed_secs = tracing::field::Empty,
+		),
 		level = tracing::Level::DEBUG,
 		skip(self, stream),
 	)]
@@ -135,19 +298,338 @@ impl<'a> Package<'a> {
 		config: &crate::config::Config,
 		stream: &mut std::net::TcpStream,
 	) -> Result<(), SendPackageError> {
-		use std::io::Write as _;
+		use {bincode::Options as _, std::io::Write as _};
 
-		let bytes = serde_json::to_vec(self)?;
+		let started = std::time::Instant::now();
+		let bytes = match config.package_limits().encoding() {
+			PackageEncoding::Json => serde_json::to_vec(self)?,
+			PackageEncoding::Bincode => bincode::options().serialize(self)?,
+		};
 		if bytes.len() > config.package_limits().max_size() {
 			return Err(SendPackageError::TooBig);
 		}
+		let (flag, bytes) = compress(config, bytes)?;
+		stream.write_all(&[flag]).map_err(SendPackageError::WriteFlag)?;
 		let size_u64_be_bytes = (bytes.len() as u64).to_be_bytes();
 		stream
 			.write_all(&size_u64_be_bytes)
 			.map_err(SendPackageError::WriteLen)?;
 		stream.write_all(&bytes).map_err(SendPackageError::WriteBytes)?;
 
-		tracing::debug!("{} bytes sent.", bytes.len());
+		let elapsed_secs = started.elapsed().as_secs_f64();
+		tracing::Span::current()
+			.record("bytes", bytes.len())
+			.record("elapsed_secs", elapsed_secs);
+		tracing::debug!(bytes = bytes.len(), elapsed_secs, "Package sent.");
 		Ok(())
 	}
 }
+
+/// Compresses the package `bytes` with zstd, if it is worth it according to
+/// the `config`. Returns the flag of the compression with the bytes to send.
+fn compress(
+	config: &crate::config::Config,
+	bytes: Vec<u8>,
+) -> Result<(u8, Vec<u8>), SendPackageError> {
+	let limits = config.package_limits();
+	if bytes.len() <= limits.compression_threshold() {
+		return Ok((UNCOMPRESSED_FLAG, bytes));
+	}
+	let compressed = zstd::bulk::compress(&bytes, limits.compression_level())
+		.map_err(SendPackageError::Compress)?;
+	Ok(if compressed.len() < bytes.len() {
+		(ZSTD_FLAG, compressed)
+	} else {
+		(UNCOMPRESSED_FLAG, bytes)
+	})
+}
+
+/// Decompresses the zstd `bytes`. They must not expand to more than
+/// `max_size` bytes, so a small package can not take a lot of memory.
+fn decompress(
+	bytes: &[u8],
+	max_size: usize,
+) -> Result<Vec<u8>, ReceivePackageBytesError> {
+	use std::io::Read as _;
+
+	let mut rv = Vec::new();
+	zstd::stream::read::Decoder::new(bytes)
+		.map_err(ReceivePackageBytesError::Decompress)?
+		.take(max_size as u64 + 1)
+		.read_to_end(&mut rv)
+		.map_err(ReceivePackageBytesError::Decompress)?;
+	if rv.len() > max_size {
+		return Err(ReceivePackageBytesError::TooBig);
+	}
+	Ok(rv)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_receive_too_big_size() {
+		use std::io::Write as _;
+
+		let config: crate::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {"max_size": 16, "receive_timeout_secs": 5},
+				"tracing": {
+					"client": {"level": "INFO", "path": "stdout"},
+					"node": {"level": "INFO", "path": "stdout"},
+				},
+			}))
+			.unwrap();
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut peer =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let (mut stream, _) = listener.accept().unwrap();
+
+		for size in [17, isize::MAX as u64 + 1, u64::MAX] {
+			peer.write_all(&[super::UNCOMPRESSED_FLAG]).unwrap();
+			peer.write_all(&size.to_be_bytes()).unwrap();
+			assert!(matches!(
+				super::Package::receive_bytes(&config, &mut stream),
+				Err(crate::error::ReceivePackageBytesError::TooBig)
+			));
+		}
+	}
+
+	#[test]
+	fn test_receive_in_chunks() {
+		let config: crate::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {
+					"max_size": 8192,
+					"receive_timeout_secs": 5,
+					"read_buffer_size": 7,
+				},
+				"tracing": {
+					"client": {"level": "INFO", "path": "stdout"},
+					"node": {"level": "INFO", "path": "stdout"},
+				},
+			}))
+			.unwrap();
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut peer =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let (mut stream, _) = listener.accept().unwrap();
+
+		// Much larger than the buffer and not a multiple of its size
+		let data: String =
+			(b'a'..=b'z').cycle().take(1000).map(char::from).collect();
+		super::Package::new(super::Action::AddTransactionFail, data.as_str())
+			.send(&config, &mut peer)
+			.unwrap();
+		let package =
+			super::Package::receive(&config, &mut stream, None).unwrap();
+		assert_eq!(package.action(), super::Action::AddTransactionFail);
+		assert_eq!(package.data(), data);
+	}
+
+	#[test]
+	fn test_log_transferred_bytes() {
+		let config: crate::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {"max_size": 64, "receive_timeout_secs": 5},
+				"tracing": {
+					"client": {"level": "INFO", "path": "stdout"},
+					"node": {"level": "INFO", "path": "stdout"},
+				},
+			}))
+			.unwrap();
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut peer =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let (mut stream, _) = listener.accept().unwrap();
+		let package = super::Package::new(
+			super::Action::GetBalance,
+			crate::consts::STORAGE_ADDRESS,
+		);
+		let size = serde_json::to_vec(&package).unwrap().len();
+
+		let logs = crate::test_helpers::Logs::capture(|| {
+			package.send(&config, &mut peer).unwrap();
+			super::Package::receive(&config, &mut stream, None).unwrap();
+		});
+
+		let lines: Vec<&str> = logs.lines().collect();
+		assert_eq!(lines.len(), 2);
+		assert!(lines[0].contains(&format!("Package sent. bytes={size}")));
+		assert!(lines[1].contains(&format!("Package received. bytes={size}")));
+	}
+
+	#[test]
+	fn test_validate_data() {
+		use {
+			super::{Action, Package},
+			crate::error::{ReceivePackageError, ValidatePackageDataError},
+			std::io::Write as _,
+		};
+
+		let config: crate::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {"max_size": 256, "receive_timeout_secs": 5},
+				"tracing": {
+					"client": {"level": "INFO", "path": "stdout"},
+					"node": {"level": "INFO", "path": "stdout"},
+				},
+			}))
+			.unwrap();
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let mut peer =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let (mut stream, _) = listener.accept().unwrap();
+		let mut receive = |action, data: &str| {
+			Package::new(action, data).send(&config, &mut peer).unwrap();
+			Package::receive(&config, &mut stream, None)
+		};
+
+		let address = "13guULBTGwjaZmWmCyy17SsnfGAgomoqNb";
+		receive(Action::GetBalance, address).unwrap();
+		receive(Action::GetBalance, crate::consts::STORAGE_ADDRESS).unwrap();
+		for data in ["", "0OIl", &address.repeat(2), "13guULBTGw jaZmWmC"] {
+			assert!(matches!(
+				receive(Action::GetBalance, data),
+				Err(ReceivePackageError::ValidateData(
+					ValidatePackageDataError::InvalidAddress
+				))
+			));
+		}
+
+		let hash = "0a".repeat(32);
+		receive(Action::GetBlocksSince, &hash).unwrap();
+		for data in ["", "0a0a", &"0g".repeat(32), &"0a".repeat(33)] {
+			assert!(matches!(
+				receive(Action::GetBlocksSince, data),
+				Err(ReceivePackageError::ValidateData(
+					ValidatePackageDataError::InvalidHash
+				))
+			));
+		}
+
+		// The other actions are not checked
+		receive(Action::AddTransactionFail, "0OIl").unwrap();
+
+		// Not UTF-8
+		let bytes = b"{\"action\":\"GetBalance\",\"data\":\"\xff\"}";
+		peer.write_all(&[super::UNCOMPRESSED_FLAG]).unwrap();
+		peer.write_all(&(bytes.len() as u64).to_be_bytes()).unwrap();
+		peer.write_all(bytes).unwrap();
+		assert!(matches!(
+			Package::receive(&config, &mut stream, None),
+			Err(ReceivePackageError::NotUtf8(_))
+		));
+	}
+
+	#[test]
+	fn test_compress_large_package() {
+		use std::{
+			convert::TryFrom as _,
+			fmt::Write as _,
+			io::{Read as _, Write as _},
+		};
+
+		const SIZE: usize = 5 * 1024 * 1024;
+
+		let config: crate::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {
+					"max_size": 2 * SIZE,
+					"receive_timeout_secs": 5,
+				},
+				"tracing": {
+					"client": {"level": "INFO", "path": "stdout"},
+					"node": {"level": "INFO", "path": "stdout"},
+				},
+			}))
+			.unwrap();
+		let (mut sender, mut wire) = create_test_streams();
+		let (mut forwarder, mut receiver) = create_test_streams();
+
+		// Blocks alike JSON with pseudorandom hashes
+		let mut state = 0x2545_f491_4f6c_dd1d_u64;
+		let mut blocks = Vec::new();
+		let mut size = 0;
+		while size < SIZE {
+			let mut hash = String::new();
+			for _ in 0..4 {
+				state ^= state << 13;
+				state ^= state >> 7;
+				state ^= state << 17;
+				write!(hash, "{state:016x}").unwrap();
+			}
+			let block = serde_json::json!({
+				"chain_id": 0,
+				"miner": "13guULBTGwjaZmWmCyy17SsnfGAgomoqNb",
+				"previous_hash": hash,
+				"transactions": [],
+				"balance_state": {"13guULBTGwjaZmWmCyy17SsnfGAgomoqNb": "100"},
+				"nonce": blocks.len(),
+				"created_at": 1_700_000_000.0,
+				"miner_signature": null,
+			});
+			size += block.to_string().len() + 1;
+			blocks.push(block);
+		}
+		let data = serde_json::to_string(&blocks).unwrap();
+		assert!(data.len() >= SIZE);
+
+		// The package is sent compressed, so it is smaller on the wire
+		let thread = std::thread::spawn({
+			let data = data.clone();
+			let config = config.clone();
+			move || {
+				super::Package::new(super::Action::GetBlocksSuccess, data)
+					.send(&config, &mut sender)
+					.unwrap();
+			}
+		});
+		let mut header = [0; 9];
+		wire.read_exact(&mut header).unwrap();
+		assert_eq!(header[0], super::ZSTD_FLAG);
+		let mut len = [0; 8];
+		len.copy_from_slice(&header[1..]);
+		let len = usize::try_from(u64::from_be_bytes(len)).unwrap();
+		assert!(len < data.len() / 2);
+		let mut payload = vec![0; len];
+		wire.read_exact(&mut payload).unwrap();
+		thread.join().unwrap();
+
+		// And is received as it was
+		forwarder.write_all(&header).unwrap();
+		forwarder.write_all(&payload).unwrap();
+		let package =
+			super::Package::receive(&config, &mut receiver, None).unwrap();
+		assert_eq!(package.action(), super::Action::GetBlocksSuccess);
+		assert_eq!(package.data(), data);
+	}
+
+	#[test]
+	fn test_decompress_too_big() {
+		let bytes = zstd::bulk::compress(&[b'a'; 1024], 3).unwrap();
+		assert!(bytes.len() < 64);
+		assert!(matches!(
+			super::decompress(&bytes, 1023),
+			Err(crate::error::ReceivePackageBytesError::TooBig)
+		));
+		assert_eq!(super::decompress(&bytes, 1024).unwrap().len(), 1024);
+	}
+
+	fn create_test_streams() -> (std::net::TcpStream, std::net::TcpStream) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let peer =
+			std::net::TcpStream::connect(listener.local_addr().unwrap())
+				.unwrap();
+		let (stream, _) = listener.accept().unwrap();
+		(peer, stream)
+	}
+}
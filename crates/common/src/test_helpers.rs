@@ -0,0 +1,32 @@
+/// Writer, which keeps the logs in memory, so the tests can check them.
+#[derive(Clone, Default)]
+pub struct Logs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl Logs {
+	/// Runs the `f` with the `DEBUG` logs written into the memory. Returns
+	/// the logs.
+	#[must_use]
+	pub fn capture(f: impl FnOnce()) -> String {
+		let logs = Self::default();
+		let writer = logs.clone();
+		let subscriber = tracing_subscriber::fmt()
+			.with_ansi(false)
+			.with_max_level(tracing::Level::DEBUG)
+			.with_writer(move || writer.clone())
+			.finish();
+		tracing::subscriber::with_default(subscriber, f);
+		let bytes =
+			logs.0.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+		String::from_utf8_lossy(&bytes).into_owned()
+	}
+}
+
+impl std::io::Write for Logs {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
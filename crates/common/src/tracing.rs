@@ -6,21 +6,152 @@ pub fn set_subscriber(
 	tracing_appender::non_blocking::WorkerGuard,
 	SetTracingSubscriberError,
 > {
-	use tracing_subscriber::layer::SubscriberExt as _;
+	let (subscriber, guard) = make_subscriber(target)?;
+	tracing::subscriber::set_global_default(subscriber)?;
+	Ok(guard)
+}
+
+/// Makes the subscriber which writes to the `target.path()` file, rotated
+/// according to `target.rotation()`, or to the stdout if the path is
+/// `crate::consts::TRACING_STDOUT_PATH`. The `target.redacted_fields()` are
+/// not written.
+fn make_subscriber(
+	target: &crate::config::TracingTarget,
+) -> Result<
+	(impl tracing::Subscriber, tracing_appender::non_blocking::WorkerGuard),
+	SetTracingSubscriberError,
+> {
+	use {
+		crate::config::TracingRotation, tracing_appender::rolling::Rotation,
+		tracing_subscriber::layer::SubscriberExt as _,
+	};
 
 	// Create the writer
-	let (writer, guard) = tracing_appender::non_blocking(
-		tracing_appender::rolling::never("", target.path()),
-	);
+	let (writer, guard) =
+		if target.path() == crate::consts::TRACING_STDOUT_PATH {
+			tracing_appender::non_blocking(std::io::stdout())
+		} else {
+			let rotation = match target.rotation() {
+				TracingRotation::Minutely => Rotation::MINUTELY,
+				TracingRotation::Hourly => Rotation::HOURLY,
+				TracingRotation::Daily => Rotation::DAILY,
+				TracingRotation::Never => Rotation::NEVER,
+			};
+			tracing_appender::non_blocking(
+				tracing_appender::rolling::RollingFileAppender::new(
+					rotation,
+					"",
+					target.path(),
+				),
+			)
+		};
 
-	// Create and set the subscriber
+	// Create the subscriber
 	let subscriber = tracing_subscriber::Registry::default()
 		.with(tracing_subscriber::EnvFilter::new(target.level()))
 		.with(tracing_bunyan_formatter::JsonStorageLayer)
-		.with(tracing_bunyan_formatter::BunyanFormattingLayer::new(
-			String::new(),
-			writer,
-		));
-	tracing::subscriber::set_global_default(subscriber)?;
-	Ok(guard)
+		.with(
+			tracing_bunyan_formatter::BunyanFormattingLayer::new(
+				String::new(),
+				writer,
+			)
+			.skip_fields(
+				target.redacted_fields().iter().map(String::as_str),
+			)?,
+		);
+	Ok((subscriber, guard))
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_make_rotated_subscribers() {
+		let dir = tempfile::tempdir().unwrap();
+		for rotation in ["minutely", "hourly", "daily", "never"] {
+			let path = dir.path().join(format!("{rotation}.log"));
+			let target: crate::config::TracingTarget =
+				serde_json::from_value(serde_json::json!({
+					"level": "INFO",
+					"path": path,
+					"rotation": rotation,
+				}))
+				.unwrap();
+			let (subscriber, _guard) =
+				super::make_subscriber(&target).unwrap();
+			tracing::subscriber::with_default(subscriber, || {
+				tracing::info!("Written to the rotated file.");
+			});
+		}
+	}
+
+	#[test]
+	fn test_drop_guard_flushes() {
+		const MESSAGE: &str = "Written before the shutdown.";
+
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("flush.log");
+		let target: crate::config::TracingTarget =
+			serde_json::from_value(serde_json::json!({
+				"level": "INFO",
+				"path": path,
+			}))
+			.unwrap();
+		let (subscriber, guard) = super::make_subscriber(&target).unwrap();
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!(MESSAGE);
+		});
+
+		// The writer is non-blocking, so the line is guaranteed to be written
+		// only after the guard is dropped
+		drop(guard);
+		assert!(std::fs::read_to_string(&path).unwrap().contains(MESSAGE));
+	}
+
+	#[test]
+	fn test_redacted_fields() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("redact.log");
+		let target: crate::config::TracingTarget =
+			serde_json::from_value(serde_json::json!({
+				"level": "INFO",
+				"path": path,
+				"redacted_fields": ["signature"],
+			}))
+			.unwrap();
+		let (subscriber, guard) = super::make_subscriber(&target).unwrap();
+		tracing::subscriber::with_default(subscriber, || {
+			let _span = tracing::info_span!("sign", signature = "span-secret")
+				.entered();
+			tracing::info!(signature = "event-secret", "Signed.");
+		});
+		drop(guard);
+
+		let logs = std::fs::read_to_string(&path).unwrap();
+		assert!(logs.contains("Signed."));
+		assert!(!logs.contains("secret"));
+
+		// The core fields are required
+		let target: crate::config::TracingTarget =
+			serde_json::from_value(serde_json::json!({
+				"level": "INFO",
+				"path": path,
+				"redacted_fields": ["msg"],
+			}))
+			.unwrap();
+		assert!(super::make_subscriber(&target).is_err());
+	}
+
+	#[test]
+	fn test_make_stdout_subscriber() {
+		let target: crate::config::TracingTarget =
+			serde_json::from_value(serde_json::json!({
+				"level": "INFO",
+				"path": crate::consts::TRACING_STDOUT_PATH,
+			}))
+			.unwrap();
+		let (subscriber, _guard) = super::make_subscriber(&target).unwrap();
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!("Written to the stdout.");
+		});
+	}
 }
@@ -12,65 +12,195 @@
 	clippy::missing_docs_in_private_items
 )]
 
-use anyhow::{Context as _, Result};
-
 const EXECUTABLE_NAME_POSITION: u8 = 0;
 const NODE_ADDRESS_POSITION: u8 = 1;
+const REPAIR_REWRITE_FLAG: &str = "--rewrite";
+const CONFIG_PATH_FLAG: &str = "--config";
+
+/// The process exit code if the arguments are invalid.
+const EXIT_CODE_ARGS: u8 = 2;
+/// The process exit code if the config failed to load.
+const EXIT_CODE_CONFIG: u8 = 3;
+/// The process exit code of the other failures.
+const EXIT_CODE_RUNTIME: u8 = 1;
+
+/// The reason why the launch failed. Each one has its own process exit code,
+/// so scripts and supervisors can react to it.
+#[derive(Debug)]
+enum Failure {
+	Args(String),
+	Config(anyhow::Error),
+	Runtime(anyhow::Error),
+}
+
+impl Failure {
+	#[must_use]
+	fn exit_code(&self) -> u8 {
+		match self {
+			Self::Args(_) => EXIT_CODE_ARGS,
+			Self::Config(_) => EXIT_CODE_CONFIG,
+			Self::Runtime(_) => EXIT_CODE_RUNTIME,
+		}
+	}
+}
+
+impl From<anyhow::Error> for Failure {
+	/// Finds out whether the `error` of the component is caused by the config.
+	fn from(error: anyhow::Error) -> Self {
+		if error.downcast_ref::<common::error::LoadConfigError>().is_some() {
+			Self::Config(error)
+		} else {
+			Self::Runtime(error)
+		}
+	}
+}
+
+impl std::fmt::Display for Failure {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Args(message) => write!(f, "{message}"),
+			Self::Config(e) | Self::Runtime(e) => write!(f, "Error: {e:?}"),
+		}
+	}
+}
 
 /// Used to pull an argument or, if it does not exist, to ask the user to
 /// specify it.
-#[must_use]
-pub fn parse_arg(position: u8, name: &str) -> String {
-	let mut iter = std::env::args().skip(1); // Skip filename
-	iter.nth(position as usize).unwrap_or_else(|| {
-		eprintln!("Enter the {name}.");
-		std::process::exit(1);
-	})
+fn parse_arg<'a>(
+	args: &'a [String],
+	position: u8,
+	name: &str,
+) -> Result<&'a str, Failure> {
+	args.iter()
+		.skip(1) // Skip filename
+		.nth(position as usize)
+		.map(String::as_str)
+		.ok_or_else(|| Failure::Args(format!("Enter the {name}.")))
 }
 
-/// Parses the executable component name from [`std::env::args`], which is
-/// either `node` or `client`.
-#[inline]
-#[must_use]
-fn extract_executable_name_from_args() -> String {
-	let name = parse_arg(EXECUTABLE_NAME_POSITION, "executable name");
-	if name != "node" && name != "client" {
-		eprintln!("Invalid executable name.");
-		std::process::exit(1);
+/// Parses the executable component name from `args`, which is `node`,
+/// `client` or `repair`.
+fn extract_executable_name_from_args(
+	args: &[String],
+) -> Result<&str, Failure> {
+	let name = parse_arg(args, EXECUTABLE_NAME_POSITION, "executable name")?;
+	if name != "node" && name != "client" && name != "repair" {
+		return Err(Failure::Args("Invalid executable name.".to_owned()));
 	}
-	name
+	Ok(name)
 }
 
-/// Parses node binding address from [`std::env::args`].
-#[inline]
-#[must_use]
-fn extract_node_address_from_args() -> common::nodes::Node {
-	let address_string = parse_arg(NODE_ADDRESS_POSITION, "node address");
-	if let Ok(n) = address_string.parse() {
-		n
-	} else {
-		eprintln!("Invalid address.");
-		std::process::exit(1);
-	}
+/// Parses node binding address from `args`.
+fn extract_node_address_from_args(
+	args: &[String],
+) -> Result<common::nodes::Node, Failure> {
+	parse_arg(args, NODE_ADDRESS_POSITION, "node address")?
+		.parse()
+		.map_err(|_| Failure::Args("Invalid address.".to_owned()))
 }
 
-/// Parses arguments for the `client` (`client::launch`) from
-/// [`std::env::args`].
+/// Parses the config path of the `node` or `repair` from `args`, which
+/// follows the `--config` flag. The client parses it itself.
+fn extract_config_path_from_args(
+	args: &[String],
+) -> Result<Option<&std::path::Path>, Failure> {
+	let Some(flag) = args.iter().position(|a| a == CONFIG_PATH_FLAG) else {
+		return Ok(None);
+	};
+	args.get(flag + 1)
+		.map(|p| Some(std::path::Path::new(p)))
+		.ok_or_else(|| Failure::Args("Enter the config path.".to_owned()))
+}
+
+/// Parses arguments for the `client` (`client::launch`) from `args`.
 #[inline]
 #[must_use]
-fn extract_client_args_from_args() -> Vec<String> {
-	let mut args: Vec<String> = std::env::args().collect();
+fn extract_client_args_from_args(args: &[String]) -> Vec<String> {
+	let mut args = args.to_vec();
 	args.remove(1); // Remove executable name
 	args
 }
 
-fn main() -> Result<()> {
-	if extract_executable_name_from_args() == "client" {
-		let client_args = extract_client_args_from_args();
+/// Launches the component, which is specified in the `args`.
+fn run(args: &[String]) -> Result<(), Failure> {
+	use anyhow::Context as _;
+
+	let executable_name = extract_executable_name_from_args(args)?;
+	if executable_name == "client" {
+		let client_args = extract_client_args_from_args(args);
 		client::launch(client_args).context("Failed to launch the client.")?;
+	} else if executable_name == "repair" {
+		let rewrite = args.iter().any(|a| a == REPAIR_REWRITE_FLAG);
+		let config_path = extract_config_path_from_args(args)?;
+		node::repair_balance_states(rewrite, config_path)
+			.context("Failed to repair balance states.")?;
 	} else {
-		let node_address = extract_node_address_from_args();
-		node::launch(node_address).context("Failed to launch the node.")?;
+		let node_address = extract_node_address_from_args(args)?;
+		let config_path = extract_config_path_from_args(args)?;
+		node::launch(node_address, config_path)
+			.context("Failed to launch the node.")?;
 	}
 	Ok(())
 }
+
+fn main() -> std::process::ExitCode {
+	let args: Vec<String> = std::env::args().collect();
+	match run(&args) {
+		Ok(()) => std::process::ExitCode::SUCCESS,
+		Err(failure) => {
+			eprintln!("{failure}");
+			std::process::ExitCode::from(failure.exit_code())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_exit_codes() {
+		use anyhow::Context as _;
+
+		let args = |args: &[&str]| -> Vec<String> {
+			args.iter().map(|&a| a.to_owned()).collect()
+		};
+		for invalid in [
+			args(&["launcher"]),
+			args(&["launcher", "wallet"]),
+			args(&["launcher", "node"]),
+			args(&["launcher", "node", "address"]),
+			args(&["launcher", "node", "127.0.0.1:8888", "--config"]),
+		] {
+			let failure = super::run(&invalid).unwrap_err();
+			assert_eq!(failure.exit_code(), super::EXIT_CODE_ARGS);
+		}
+
+		let config_error = Err::<(), _>(common::error::LoadConfigError::Read(
+			std::io::Error::from(std::io::ErrorKind::NotFound),
+		))
+		.context("Failed to load the config.")
+		.context("Failed to launch the node.")
+		.unwrap_err();
+		let failure = super::Failure::from(config_error);
+		assert_eq!(failure.exit_code(), super::EXIT_CODE_CONFIG);
+		assert_ne!(failure.exit_code(), super::EXIT_CODE_ARGS);
+
+		let failure = super::Failure::from(anyhow::anyhow!("Failed to bind."));
+		assert_eq!(failure.exit_code(), super::EXIT_CODE_RUNTIME);
+	}
+
+	#[test]
+	fn test_extract_config_path() {
+		let args: Vec<String> = ["launcher", "node", "127.0.0.1:8888"]
+			.iter()
+			.map(|&a| a.to_owned())
+			.collect();
+		assert_eq!(super::extract_config_path_from_args(&args).unwrap(), None);
+
+		let mut args = args;
+		args.extend(["--config".to_owned(), "other.json".to_owned()]);
+		assert_eq!(
+			super::extract_config_path_from_args(&args).unwrap(),
+			Some(std::path::Path::new("other.json"))
+		);
+	}
+}
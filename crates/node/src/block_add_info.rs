@@ -6,7 +6,7 @@ pub(crate) struct BlockAddInfo<'a> {
 }
 
 impl<'a> BlockAddInfo<'a> {
-	common::accessor!(& block -> &blockchain::block::Block);
+	common::accessor!(& block -> &blockchain::block::Block<'a>);
 
 	common::accessor!(copy blockchain_len -> usize);
 
@@ -18,4 +18,69 @@ impl<'a> BlockAddInfo<'a> {
 	) -> Self {
 		Self { block: std::borrow::Cow::Borrowed(block), blockchain_len }
 	}
+
+	#[inline]
+	#[must_use]
+	pub fn into_block(self) -> blockchain::block::Block<'a> {
+		self.block.into_owned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_send_in_encodings() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				serde_json::from_value(serde_json::json!({
+					"require_proof_of_work": false
+				}))
+				.unwrap(),
+			);
+		let mut blockchain = blockchain.into_inner().unwrap();
+		let miner = blockchain.miner().clone();
+		for _ in 0..blockchain::consts::USER_TRANSACTIONS_PER_BLOCK {
+			let transaction = crate::test_helpers::create_test_transaction(
+				&blockchain,
+				&miner,
+			);
+			blockchain.add_transaction(transaction).unwrap();
+		}
+		let block = blockchain.mine_block().unwrap();
+		let data = serde_json::to_string(&super::BlockAddInfo::new(
+			&block,
+			blockchain.len().unwrap(),
+		))
+		.unwrap();
+
+		for encoding in ["json", "bincode"] {
+			let config: common::config::Config =
+				serde_json::from_value(serde_json::json!({
+					"nodes": [],
+					"package_limits": {
+						"max_size": 65536,
+						"receive_timeout_secs": 5,
+						"encoding": encoding,
+					},
+					"tracing": {
+						"client": {"level": "INFO", "path": "stdout"},
+						"node": {"level": "INFO", "path": "stdout"},
+					},
+				}))
+				.unwrap();
+			let (mut stream, mut peer) =
+				crate::test_helpers::create_test_streams();
+
+			Package::new(Action::AddBlock, data.as_str())
+				.send(&config, &mut peer)
+				.unwrap();
+			let package =
+				Package::receive(&config, &mut stream, None).unwrap();
+			assert_eq!(package.action(), Action::AddBlock);
+			assert_eq!(package.data(), data);
+		}
+	}
 }
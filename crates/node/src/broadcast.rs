@@ -0,0 +1,162 @@
+/// The queue of the node, see [`enqueue`].
+static QUEUE: Queue = Queue::new();
+
+/// Packages waiting for the background worker, with the config which was
+/// current when they were queued.
+struct Queue {
+	broadcasts: std::sync::Mutex<std::collections::VecDeque<Broadcast>>,
+	queued: std::sync::Condvar,
+	worker: std::sync::Once,
+}
+
+struct Broadcast {
+	package: common::package::Package<'static>,
+	config: common::config::Config,
+}
+
+/// Queues the `package` to be sent to `config.nodes()` by the background
+/// worker, so the caller does not wait for the nodes. The worker is started
+/// on the first call.
+///
+/// If there are already `config.node().block_broadcast_queue_size()`
+/// packages, the `package` is dropped. The queued blocks are its ancestors,
+/// so the nodes could not add it without them anyway.
+#[inline]
+pub(crate) fn enqueue(
+	package: common::package::Package<'static>,
+	config: &common::config::Config,
+) {
+	QUEUE.enqueue(package, config);
+}
+
+impl Queue {
+	const fn new() -> Self {
+		Self {
+			broadcasts: std::sync::Mutex::new(
+				std::collections::VecDeque::new(),
+			),
+			queued: std::sync::Condvar::new(),
+			worker: std::sync::Once::new(),
+		}
+	}
+
+	/// See [`enqueue`].
+	fn enqueue(
+		&'static self,
+		package: common::package::Package<'static>,
+		config: &common::config::Config,
+	) {
+		self.worker.call_once(|| {
+			std::thread::spawn(move || self.work());
+		});
+		if self.push(Broadcast { package, config: config.clone() }) {
+			self.queued.notify_one();
+		}
+	}
+
+	/// Pushes the `broadcast`, if the queue is not full. Returns whether it
+	/// was pushed.
+	fn push(&self, broadcast: Broadcast) -> bool {
+		let mut broadcasts = self
+			.broadcasts
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+		if broadcasts.len()
+			>= broadcast.config.node().block_broadcast_queue_size()
+		{
+			tracing::warn!(
+				"The broadcast queue is full, dropped the new one."
+			);
+			return false;
+		}
+		broadcasts.push_back(broadcast);
+		true
+	}
+
+	/// Sends the queued packages one by one.
+	fn work(&self) {
+		loop {
+			let broadcast = {
+				let mut broadcasts = self
+					.queued
+					.wait_while(
+						self.broadcasts.lock().unwrap_or_else(
+							std::sync::PoisonError::into_inner,
+						),
+						|b| b.is_empty(),
+					)
+					.unwrap_or_else(std::sync::PoisonError::into_inner);
+				match broadcasts.pop_front() {
+					Some(b) => b,
+					None => continue,
+				}
+			};
+			crate::helpers::send_to_nodes(
+				&broadcast.package,
+				&broadcast.config,
+				broadcast.config.node().block_send_retries(),
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_drop_newest() {
+		use common::package::{Action, Package};
+
+		let config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"block_broadcast_queue_size": 2}),
+		);
+		let queue = super::Queue::new();
+		for data in ["first", "second", "third"] {
+			queue.push(super::Broadcast {
+				package: Package::new(Action::AddBlock, data),
+				config: config.clone(),
+			});
+		}
+		let broadcasts = queue.broadcasts.into_inner().unwrap();
+		assert!(broadcasts
+			.iter()
+			.map(|b| b.package.data())
+			.eq(["first", "second"]));
+	}
+
+	#[test]
+	fn test_slow_node() {
+		use {
+			common::package::{Action, Package},
+			std::time::{Duration, Instant},
+		};
+
+		// The slow node accepts, but does not respond, so the block is
+		// waited for until the receive timeout
+		let slow = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let fast = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let config: common::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": [slow.local_addr().unwrap(), fast.local_addr().unwrap()],
+				"package_limits": {"max_size": 8192, "receive_timeout_secs": 10},
+				"node": {"block_send_retries": 0},
+				"tracing": {
+					"client": {"level": "TRACE", "path": "client-logs.log"},
+					"node": {"level": "TRACE", "path": "node-logs.log"},
+				},
+			}))
+			.unwrap();
+		let queue: &'static super::Queue =
+			Box::leak(Box::new(super::Queue::new()));
+
+		let started = Instant::now();
+		queue.enqueue(Package::new(Action::AddBlock, "block"), &config);
+		assert!(started.elapsed() < Duration::from_secs(1));
+
+		let (_slow_stream, _) = slow.accept().unwrap();
+		let (mut stream, _) = fast.accept().unwrap();
+		let package = Package::receive(&config, &mut stream, None).unwrap();
+		assert_eq!(package.action(), Action::AddBlock);
+		// Sooner than the timeout of the slow node
+		assert!(started.elapsed() < Duration::from_secs(5));
+	}
+}
@@ -10,48 +10,153 @@ pub(crate) fn stream(
 ) -> Result<()> {
 	use common::package::{Action, Package};
 
-	// Receive package
+	// Receive package, if its action is handled and not disallowed. The
+	// actions of nodes are rejected from unknown senders, if configured, so
+	// their data is not parsed
+	let mut disallowed = config.node().disallowed_actions().clone();
+	if config.node().reject_unknown_writers() && !is_node(config, sender) {
+		disallowed.extend([
+			Action::AddBlock,
+			Action::BroadcastTransaction,
+			Action::ForceMine,
+		]);
+	}
 	let package = Package::receive(
 		config,
 		&mut stream,
-		Some(common::set![
-			Action::AddBlock,
-			Action::AddTransaction,
-			Action::GetBalance,
-			Action::GetBlockchainLen,
-			Action::GetBlocks,
-			Action::GetLastBlockHash
-		]),
+		Some(
+			&common::set![
+				Action::AddBlock,
+				Action::AddTransaction,
+				Action::BroadcastTransaction,
+				Action::ForceMine,
+				Action::GetAddresses,
+				Action::GetBalance,
+				Action::GetBalances,
+				Action::GetBlockByHash,
+				Action::GetBlockchainLen,
+				Action::GetBlocks,
+				Action::GetBlocksSince,
+				Action::GetBlockStats,
+				Action::GetConfig,
+				Action::GetFeeHistogram,
+				Action::GetInclusionProof,
+				Action::GetLastBlockHash,
+				Action::GetLocator,
+				Action::GetStatus
+			] - &disallowed,
+		),
 	)
 	.context("Failed to receive a package.")?;
 	tracing::debug!("Received a packaeg with action {:?}.", package.action());
 	match package.action() {
 		Action::AddBlock => {
-			add_block(blockchain, sender, &package, config)
-				.context("Failed to handle block addition.")?;
+			add_block_and_respond(
+				stream, blockchain, sender, &package, config,
+			)
+			.context("Failed to handle block addition.")?;
 		}
 		Action::AddTransaction => {
 			add_transaction(stream, blockchain, &package, config)
 				.context("Failed to handle transaction addition.")?;
 		}
+		Action::BroadcastTransaction => {
+			broadcast_transaction(blockchain, &package, config)
+				.context("Failed to handle transaction broadcast.")?;
+		}
+		Action::ForceMine => {
+			force_mine(stream, blockchain, sender, config)
+				.context("Failed to handle forced mining.")?;
+		}
+		Action::GetAddresses => {
+			get_addresses(stream, blockchain, config)
+				.context("Failed to handle addresses getting.")?;
+		}
 		Action::GetBalance => {
 			get_balance(stream, blockchain, &package, config)
 				.context("Failed to handle balance getting.")?;
 		}
+		Action::GetBalances => {
+			get_balances(stream, blockchain, config)
+				.context("Failed to handle balances getting.")?;
+		}
+		Action::GetBlockByHash => {
+			get_block_by_hash(stream, blockchain, &package, config)
+				.context("Failed to handle block by hash getting.")?;
+		}
 		Action::GetBlockchainLen => {
 			get_len(stream, blockchain, config)
 				.context("Failed to handle len getting.")?;
 		}
 		Action::GetBlocks => {
-			get_blocks(stream, blockchain, config)
+			get_blocks(stream, blockchain, &package, config)
 				.context("Failed to handle blocks getting.")?;
 		}
+		Action::GetBlocksSince => {
+			get_blocks_since(stream, blockchain, &package, config)
+				.context("Failed to handle blocks since hash getting.")?;
+		}
+		Action::GetBlockStats => {
+			get_block_stats(stream, blockchain, &package, config)
+				.context("Failed to handle block stats getting.")?;
+		}
+		Action::GetConfig => {
+			get_config(stream, config)
+				.context("Failed to handle config getting.")?;
+		}
+		Action::GetFeeHistogram => {
+			get_fee_histogram(stream, blockchain, config)
+				.context("Failed to handle fee histogram getting.")?;
+		}
+		Action::GetInclusionProof => {
+			get_inclusion_proof(stream, blockchain, &package, config)
+				.context("Failed to handle inclusion proof getting.")?;
+		}
 		Action::GetLastBlockHash => {
 			get_last_block_hash(stream, blockchain, config)
 				.context("Failed to handle last block hash getting.")?;
 		}
+		Action::GetLocator => {
+			get_locator(stream, blockchain, &package, config)
+				.context("Failed to handle locator getting.")?;
+		}
+		Action::GetStatus => {
+			get_status(stream, blockchain, config)
+				.context("Failed to handle status getting.")?;
+		}
 		_ => unreachable!(),
-	};
+	}
+	Ok(())
+}
+
+/// Whether the `sender` has the IP address of one of the `config.nodes()`.
+/// Its port is not checked, because the outgoing ones are ephemeral.
+fn is_node(
+	config: &common::config::Config,
+	sender: common::nodes::Node,
+) -> bool {
+	config.nodes().iter().any(|n| n.ip() == sender.ip())
+}
+
+/// Processes the user's request for all addresses which have ever held a
+/// balance.
+fn get_addresses(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	config: &common::config::Config,
+) -> Result<()> {
+	let addresses = blockchain
+		.read()
+		.unwrap()
+		.all_known_addresses()
+		.context("Failed to get addresses.")?;
+	common::package::Package::new(
+		common::package::Action::GetAddressesSuccess,
+		serde_json::to_string(&addresses)
+			.context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
 	Ok(())
 }
 
@@ -77,39 +182,166 @@ fn get_balance(
 	Ok(())
 }
 
+/// Processes the user's request for the latest balances of all addresses.
+fn get_balances(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	config: &common::config::Config,
+) -> Result<()> {
+	let balances = blockchain
+		.read()
+		.unwrap()
+		.snapshot_balances()
+		.context("Failed to get balances.")?;
+	common::package::Package::new(
+		common::package::Action::GetBalancesSuccess,
+		serde_json::to_string(&balances)
+			.context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// How [`add_block`] handled the block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum AddBlockStatus {
+	/// The block or a branch with it was added to the blockchain.
+	Added,
+	/// The block was kept aside: as an orphan or as the fork candidate.
+	Buffered,
+}
+
+/// Same as [`add_block`], but the sender is told whether the block was
+/// added with `AddBlockSuccess`, buffered with `AddBlockBuffered` or
+/// rejected with `AddBlockFail`, so it can send it again.
+fn add_block_and_respond(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	sender: common::nodes::Node,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let added = add_block(blockchain, sender, package, config);
+	let response = match added {
+		Ok(AddBlockStatus::Added) => common::package::Package::new(
+			common::package::Action::AddBlockSuccess,
+			"",
+		),
+		Ok(AddBlockStatus::Buffered) => common::package::Package::new(
+			common::package::Action::AddBlockBuffered,
+			"",
+		),
+		Err(ref e) => common::package::Package::new(
+			common::package::Action::AddBlockFail,
+			e.to_string(),
+		),
+	};
+	// The block is handled anyway, so only the result of adding matters
+	if let Err(e) = response.send(config, &mut stream) {
+		tracing::debug!("Failed to send the add block response: {e}");
+	}
+	added.map(|_| ())
+}
+
 /// Processes a request to add a new block to the blockchain. Such a request is
 /// accepted only from other nodes if mining is successful.
 ///
-/// If the block does not fit the blockchain and the passed `blockchain_len`
-/// in `crate::block_add_info::BlockAddInfo` is greater than the current
-/// length, this function will call `create::helperstransfer_blockchain_from`.
+/// If the parent of the block is unknown, but the sender's chain is only one
+/// block longer than the one with the parent, the block is validated without
+/// the chain and buffered with `crate::orphans::insert` until the parent is
+/// added. The buffered blocks are added with `crate::orphans::add_children`
+/// whenever the last block changes.
+///
+/// If the block competes with the last one, the one with the lower hash wins
+/// and the chain is switched with `blockchain::Blockchain::tie_break`. The
+/// lost block is kept as the fork candidate, so its branch is switched to
+/// with `blockchain::Blockchain::try_reorg` when its child arrives.
+///
+/// Otherwise, if the block does not fit the blockchain and the passed
+/// `blockchain_len` in `crate::block_add_info::BlockAddInfo` is greater than
+/// the current length, this function will call `crate::helpers::reorg_from`.
 fn add_block(
 	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
 	sender: common::nodes::Node,
 	package: &common::package::Package,
 	config: &common::config::Config,
-) -> Result<()> {
+) -> Result<AddBlockStatus> {
 	anyhow::ensure!(config.nodes().contains(&sender), "Invalid sender.");
 
-	let info: crate::block_add_info::BlockAddInfo =
+	let info: crate::block_add_info::BlockAddInfo<'static> =
 		serde_json::from_str(package.data())
 			.context("Failed to convert JSON to add info.")?;
 	let mut lock = blockchain.write().unwrap();
-	// Add a block and, if our blockchain is lagging, move it from another node
+
+	// Warn about the old blocks, which may mean that the network is stalled
+	let stale_secs =
+		std::time::Duration::from_secs(config.node().stale_block_secs())
+			.as_secs_f64();
+	if info.block().age() > stale_secs {
+		tracing::warn!(
+			"The received block is {:.0} seconds old.",
+			info.block().age()
+		);
+	}
+	if let Some(last) =
+		lock.get_last_block().context("Failed to get last block.")?
+	{
+		if last.age() > stale_secs {
+			tracing::warn!(
+				"The last block was not replaced for {:.0} seconds.",
+				last.age()
+			);
+		}
+	}
+
+	// Add a block and, if our blockchain is lagging, move it from another
+	// node. If the block competes with our last one, the one with the lower
+	// hash wins
 	if let Err(e) = lock.add_block(info.block(), false) {
 		let blockchain_len =
 			lock.len().context("Failed to get blockchain len.")?;
+		let parent_known = match info.block().previous_hash() {
+			Some(h) => lock
+				.get_block_by_hash(h)
+				.context("Failed to get parent block.")?
+				.is_some(),
+			None => true,
+		};
+		if !parent_known && info.blockchain_len() <= blockchain_len + 2 {
+			// Junk would evict the real orphans otherwise
+			info.block()
+				.validate_standalone(&lock)
+				.context("Failed to validate the orphan block.")?;
+			tracing::info!(
+				"The parent of the received block is unknown, it is buffered."
+			);
+			crate::orphans::insert(info.into_block(), config);
+			return Ok(AddBlockStatus::Buffered);
+		}
+		if let Some(status) = add_fork_block(
+			&mut lock,
+			info.block(),
+			info.blockchain_len() == blockchain_len,
+		)? {
+			if status == AddBlockStatus::Added {
+				crate::orphans::add_children(&mut lock)
+					.context("Failed to add orphan blocks.")?;
+			}
+			return Ok(status);
+		}
 		if info.blockchain_len() > blockchain_len {
 			tracing::warn!(
 				"info.blockchain_len() > current blockchain length."
 			);
 			drop(lock);
-			return crate::helpers::transfer_blockchain_from(
-				sender, blockchain, config,
-			)
-			.with_context(|| {
-				format!("Failed to transfer blockchain from {sender}.")
-			});
+			crate::helpers::reorg_from(sender, blockchain, config)
+				.with_context(|| {
+					format!("Failed to switch to the branch of {sender}.")
+				})?;
+			crate::orphans::add_children(&mut blockchain.write().unwrap())
+				.context("Failed to add orphan blocks.")?;
+			return Ok(AddBlockStatus::Added);
 		}
 		return Err(e).context("Failed to add block.");
 	}
@@ -122,7 +354,59 @@ fn add_block(
 			.context("Failed to compute block hash.")?,
 		info.block().nonce()
 	);
-	Ok(())
+	crate::orphans::add_children(&mut lock)
+		.context("Failed to add orphan blocks.")?;
+	Ok(AddBlockStatus::Added)
+}
+
+/// Handles the `block`, which does not fit the blockchain, if it competes
+/// with the last block or continues the fork candidate, see [`add_block`].
+/// The block competes only if the chain of the sender has the `same_len`.
+///
+/// Returns `None` if the block was not handled.
+fn add_fork_block<'a>(
+	lock: &mut blockchain::Blockchain<'a>,
+	block: &blockchain::block::Block<'a>,
+	same_len: bool,
+) -> Result<Option<AddBlockStatus>> {
+	if same_len {
+		match lock.tie_break(block).context("Failed to tie-break.")? {
+			blockchain::blockchain::TieBreak::Won => {
+				tracing::info!(
+					"The received block won the tie-break, switched to it."
+				);
+				return Ok(Some(AddBlockStatus::Added));
+			}
+			blockchain::blockchain::TieBreak::Lost => {
+				tracing::info!(
+					"The received block lost the tie-break, it is kept as the \
+					 fork candidate."
+				);
+				return Ok(Some(AddBlockStatus::Buffered));
+			}
+			blockchain::blockchain::TieBreak::NotCompeting => {}
+		}
+	}
+
+	// The fork candidate and its child are enough to switch to their branch
+	// without the blocks of the sender
+	let Some(candidate) = lock.fork_candidate().cloned() else {
+		return Ok(None);
+	};
+	let candidate_hash = candidate
+		.compute_hash()
+		.context("Failed to compute the fork candidate hash.")?;
+	if block.previous_hash() != Some(candidate_hash.as_str()) {
+		return Ok(None);
+	}
+	let reorg = lock
+		.try_reorg(&[candidate, block.clone()])
+		.context("Failed to switch to the fork candidate branch.")?;
+	if reorg != blockchain::blockchain::Reorg::Switched {
+		return Ok(None);
+	}
+	tracing::info!("The branch of the fork candidate won.");
+	Ok(Some(AddBlockStatus::Added))
 }
 
 /// Processes user request to check and add transaction, JSON dump of which is
@@ -154,9 +438,15 @@ fn add_transaction(
 	if let Err(e) =
 		blockchain.write().unwrap().add_transaction(transaction.clone())
 	{
+		let reason = match e {
+			blockchain::error::AddTransactionError::Sealed => {
+				"a block is being mined, try again."
+			}
+			_ => "error. Maybe invalid balance?.",
+		};
 		common::package::Package::new(
 			common::package::Action::AddTransactionFail,
-			"error. Maybe invalid balance?.",
+			reason,
 		)
 		.send(config, &mut stream)
 		.context("Failed to send on-fail package when addition failed.")?;
@@ -176,28 +466,137 @@ fn add_transaction(
 	)
 	.send(config, &mut stream)
 	.context("Failed to send successful package.")?;
+	transaction_added(blockchain, package, config)
+}
+
+/// Processes a transaction relayed by another node. It is not responded.
+///
+/// A transaction which is already pending is ignored without relaying, so
+/// the relays of the nodes which know each other do not loop.
+fn broadcast_transaction(
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let transaction: blockchain::transaction::Transaction =
+		serde_json::from_str(package.data())
+			.context("Failed to convert JSON to transaction")?;
+	match blockchain.write().unwrap().add_transaction(transaction.clone()) {
+		Ok(()) => {}
+		Err(blockchain::error::AddTransactionError::Duplicate) => {
+			tracing::debug!("The relayed transaction is already pending.");
+			return Ok(());
+		}
+		Err(e) => return Err(e).context("Failed to add transaction."),
+	}
+	tracing::info!(
+		"New relayed transaction added: {} -> {} ({})",
+		transaction.sender(),
+		transaction.recipient(),
+		transaction.amount()
+	);
+	transaction_added(blockchain, package, config)
+}
 
-	// Mining a new block if there are enough transactions
-	if blockchain.read().unwrap().minable() {
+/// Relays the added transaction of the `package`, if configured, and mines a
+/// new block if there are enough transactions or the oldest one has waited
+/// for `config.node().partial_block_grace_ms()`. With the background mining,
+/// the miner is only requested.
+fn transaction_added(
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	if config.node().relay_transactions() {
+		crate::helpers::relay_transaction(package.data(), config);
+	}
+	if config.node().background_mining() {
+		crate::mining::request();
+	} else if blockchain.read().unwrap().minable_with_grace(
+		std::time::Duration::from_millis(
+			config.node().partial_block_grace_ms(),
+		),
+	) {
 		crate::helpers::mine_block(blockchain, config)
 			.context("Failed to mine block.")?;
 	}
 	Ok(())
 }
 
-/// Sends blockchain blocks in response to a user request. This only happens
-/// when requested by another node, in
-/// `crate::helpers::transfer_blockchain_from`.
-fn get_blocks(
+/// Processes a request to mine the pending transactions immediately, even if
+/// there are not enough of them for a full block. Such a request is accepted
+/// only from the hosts of the configured nodes.
+///
+/// The request fails if a block is already being mined or the pending
+/// transactions are not enough for a partial block.
+fn force_mine(
 	mut stream: std::net::TcpStream,
 	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	sender: common::nodes::Node,
 	config: &common::config::Config,
 ) -> Result<()> {
-	let data = blockchain
+	let fail = |mut stream: std::net::TcpStream, reason: &str| {
+		common::package::Package::new(
+			common::package::Action::ForceMineFail,
+			reason,
+		)
+		.send(config, &mut stream)
+		.context("Failed to send on-fail package.")
+	};
+
+	// Validate the request
+	if !is_node(config, sender) {
+		fail(stream, "unauthorized sender.")?;
+		anyhow::bail!("Invalid sender.");
+	} else if !blockchain.read().unwrap().partially_minable() {
+		return fail(stream, "not enough pending transactions.");
+	}
+
+	match crate::helpers::mine_block(blockchain, config) {
+		Ok(true) => {}
+		Ok(false) => return fail(stream, "a block is already being mined."),
+		Err(e) => {
+			fail(stream, "mining failed.")?;
+			return Err(e).context("Failed to mine block.");
+		}
+	}
+	let len = blockchain
 		.read()
 		.unwrap()
-		.to_string()
-		.context("Failed to convert blockchain to string.")?;
+		.len()
+		.context("Failed to get blockchain len.")?;
+	common::package::Package::new(
+		common::package::Action::ForceMineSuccess,
+		len.to_string(),
+	)
+	.send(config, &mut stream)
+	.context("Failed to send successful package.")?;
+	Ok(())
+}
+
+/// Sends blockchain blocks in response to a user request. This only happens
+/// when requested by another node, in `crate::helpers::reorg_from`.
+///
+/// If `config.node().validate_served_chain()`, the stored chain is validated
+/// first, so a corrupted database is not spread to other nodes.
+fn get_blocks(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let lock = blockchain.read().unwrap();
+	if config.node().validate_served_chain() {
+		lock.validate_stored_chain()
+			.context("Failed to validate the stored chain.")?;
+	}
+	let mut data =
+		lock.to_string().context("Failed to convert blockchain to string.")?;
+	drop(lock);
+	if package.data() == common::consts::GET_BLOCKS_GZIP {
+		data = crate::helpers::compress(&data)
+			.context("Failed to compress blocks.")?;
+	}
 	common::package::Package::new(
 		common::package::Action::GetBlocksSuccess,
 		data,
@@ -227,6 +626,178 @@ fn get_len(
 	Ok(())
 }
 
+/// Sends the health of the node, see [`crate::status::Status`].
+fn get_status(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	config: &common::config::Config,
+) -> Result<()> {
+	let len = blockchain
+		.read()
+		.unwrap()
+		.len()
+		.context("Failed to get blockchain len")?;
+	let data = serde_json::to_string(&crate::status::Status::new(len))
+		.context("Failed to convert status to JSON.")?;
+	common::package::Package::new(
+		common::package::Action::GetStatusSuccess,
+		data,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes the user's request for the stats of the last blocks, whose
+/// number is specified in the `package.data()`.
+fn get_block_stats(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let n = package.data().parse().context("Failed to parse blocks count.")?;
+	let stats = blockchain
+		.read()
+		.unwrap()
+		.recent_block_stats(n)
+		.context("Failed to get block stats.")?;
+	common::package::Package::new(
+		common::package::Action::GetBlockStatsSuccess,
+		serde_json::to_string(&stats).context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes a request for the blocks after the one whose hash is specified
+/// in the `package.data()`. `null` is sent if there is no such block.
+fn get_blocks_since(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let lock = blockchain.read().unwrap();
+	let blocks = lock
+		.get_blocks_since_hash(package.data())
+		.context("Failed to get blocks since hash.")?;
+	common::package::Package::new(
+		common::package::Action::GetBlocksSinceSuccess,
+		serde_json::to_string(&blocks)
+			.context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes the user's request for the block with the hash, which is
+/// specified in the `package.data()`. The `null` is sent if there is no
+/// such block.
+fn get_block_by_hash(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let lock = blockchain.read().unwrap();
+	let block = lock
+		.get_block_by_hash(package.data())
+		.context("Failed to get block by hash.")?;
+	common::package::Package::new(
+		common::package::Action::GetBlockByHashSuccess,
+		serde_json::to_string(&block).context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes the user's request for the parameters of the network, which
+/// are needed to make valid transactions.
+fn get_config(
+	mut stream: std::net::TcpStream,
+	config: &common::config::Config,
+) -> Result<()> {
+	let data = serde_json::to_string(
+		&crate::network_params::NetworkParams::new(config),
+	)
+	.context("Failed to convert network parameters to JSON.")?;
+	common::package::Package::new(
+		common::package::Action::GetConfigSuccess,
+		data,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes the user's request for the number of the pending transactions
+/// by fee, see [`blockchain::Blockchain::mempool_fee_histogram`].
+fn get_fee_histogram(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	config: &common::config::Config,
+) -> Result<()> {
+	let histogram = blockchain.read().unwrap().mempool_fee_histogram();
+	common::package::Package::new(
+		common::package::Action::GetFeeHistogramSuccess,
+		serde_json::to_string(&histogram)
+			.context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes the user's request for the proof that the transaction, whose
+/// random string is specified in the `package.data()`, was included in a
+/// block. `null` is sent if there is no such transaction.
+fn get_inclusion_proof(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let lock = blockchain.read().unwrap();
+	let proof = lock
+		.inclusion_proof(package.data())
+		.context("Failed to get inclusion proof.")?;
+	common::package::Package::new(
+		common::package::Action::GetInclusionProofSuccess,
+		serde_json::to_string(&proof).context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
+/// Processes a request for the hashes of the last blocks, whose number is
+/// specified in the `package.data()`.
+fn get_locator(
+	mut stream: std::net::TcpStream,
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	package: &common::package::Package,
+	config: &common::config::Config,
+) -> Result<()> {
+	let n = package.data().parse().context("Failed to parse blocks count.")?;
+	let hashes = blockchain
+		.read()
+		.unwrap()
+		.last_n_block_hashes(n)
+		.context("Failed to get block hashes.")?;
+	common::package::Package::new(
+		common::package::Action::GetLocatorSuccess,
+		serde_json::to_string(&hashes)
+			.context("Failed to convert to JSON.")?,
+	)
+	.send(config, &mut stream)
+	.context("Failed to send package.")?;
+	Ok(())
+}
+
 /// Processes the user's request for blockchain last block hash.
 fn get_last_block_hash(
 	mut stream: std::net::TcpStream,
@@ -246,3 +817,456 @@ fn get_last_block_hash(
 	.context("Failed to send package.")?;
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_get_blocks_from_corrupted_db() {
+		let _lock = crate::test_helpers::lock_mining();
+		let config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"validate_served_chain": true}),
+		);
+		let (blockchain, dir) = crate::test_helpers::create_test_blockchain();
+
+		// Corrupt the genesis balance state
+		rusqlite::Connection::open(
+			dir.path().join(crate::test_helpers::TEST_DB_NAME),
+		)
+		.unwrap()
+		.execute("UPDATE block SET json = replace(json, '100', '999')", [])
+		.unwrap();
+
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let package = common::package::Package::new(
+			common::package::Action::GetBlocks,
+			"",
+		);
+		assert!(
+			super::get_blocks(stream, &blockchain, &package, &config).is_err()
+		);
+		assert!(common::package::Package::receive(&config, &mut peer, None)
+			.is_err());
+	}
+
+	#[test]
+	fn test_disallowed_action() {
+		use common::{
+			error::ReceivePackageError,
+			package::{Action, Package},
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let config =
+			crate::test_helpers::create_test_config(&serde_json::json!({
+				"disallowed_actions": ["AddBlock", "AddTransaction"],
+			}));
+
+		// Rejected before the data is parsed
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		Package::new(Action::AddTransaction, "{}")
+			.send(&config, &mut peer)
+			.unwrap();
+		let error =
+			super::stream(stream, sender, &config, &blockchain).unwrap_err();
+		assert!(matches!(
+			error.downcast_ref(),
+			Some(ReceivePackageError::InvalidAction)
+		));
+
+		// Queries are still served
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		Package::new(Action::GetBlockchainLen, "")
+			.send(&config, &mut peer)
+			.unwrap();
+		super::stream(stream, sender, &config, &blockchain).unwrap();
+		let response = Package::receive(&config, &mut peer, None).unwrap();
+		assert_eq!(response.action(), Action::GetBlockchainLenSuccess);
+	}
+
+	#[test]
+	fn test_reject_unknown_writer() {
+		use {
+			common::{
+				error::ReceivePackageError,
+				package::{Action, Package},
+			},
+			std::io::Read as _,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&["10.0.0.1:8888".parse().unwrap()],
+			&serde_json::json!({"reject_unknown_writers": true}),
+		);
+
+		// The data is not a block add info, but it is not parsed
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		Package::new(Action::AddBlock, "not a block")
+			.send(&config, &mut peer)
+			.unwrap();
+		let error =
+			super::stream(stream, sender, &config, &blockchain).unwrap_err();
+		assert!(matches!(
+			error.downcast_ref(),
+			Some(ReceivePackageError::InvalidAction)
+		));
+		assert_eq!(peer.read(&mut [0]).unwrap(), 0);
+
+		// Read actions are still open
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		Package::new(Action::GetBlockchainLen, "")
+			.send(&config, &mut peer)
+			.unwrap();
+		super::stream(stream, sender, &config, &blockchain).unwrap();
+		let response = Package::receive(&config, &mut peer, None).unwrap();
+		assert_eq!(response.action(), Action::GetBlockchainLenSuccess);
+	}
+
+	#[test]
+	fn test_add_stale_block() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let (other, _other_dir) =
+			crate::test_helpers::create_test_blockchain();
+		let sender = "127.0.0.1:1".parse().unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[sender],
+			&serde_json::json!({"stale_block_secs": 0}),
+		);
+		let other = other.read().unwrap();
+		let block = other.get_last_block().unwrap().unwrap();
+		assert!(block.age() > 0.0);
+		let package = common::package::Package::new(
+			common::package::Action::AddBlock,
+			serde_json::to_string(&crate::block_add_info::BlockAddInfo::new(
+				&block, 1,
+			))
+			.unwrap(),
+		);
+
+		let logs = common::test_helpers::Logs::capture(|| {
+			// The block does not fit, but it is checked before
+			let _ = super::add_block(&blockchain, sender, &package, &config);
+		});
+		assert!(logs.contains("The received block is"));
+		assert!(logs.contains("The last block was not replaced for"));
+	}
+
+	#[test]
+	fn test_add_orphan_block() {
+		let _lock = crate::test_helpers::lock_mining();
+		let consensus: common::config::Consensus =
+			serde_json::from_value(serde_json::json!({
+				"require_proof_of_work": false
+			}))
+			.unwrap();
+		let (source, _source_dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				consensus.clone(),
+			);
+		// The blockchain has the same genesis block
+		let (blockchain, dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				consensus.clone(),
+			);
+		{
+			let mut lock = blockchain.write().unwrap();
+			*lock = blockchain::Blockchain::from_str_into(
+				lock.miner().clone(),
+				consensus,
+				&source.read().unwrap().to_string().unwrap(),
+				&dir.path().join(crate::test_helpers::TEST_DB_NAME),
+			)
+			.unwrap();
+		}
+
+		// Mine the blocks N and N + 1
+		let mut source = source.into_inner().unwrap();
+		let miner = source.miner().clone();
+		let mut packages = Vec::new();
+		for len in 2..=3 {
+			let transaction =
+				crate::test_helpers::create_test_transaction(&source, &miner);
+			source.add_transaction(transaction).unwrap();
+			let block = source.mine_block().unwrap();
+			packages.push(common::package::Package::new(
+				common::package::Action::AddBlock,
+				serde_json::to_string(
+					&crate::block_add_info::BlockAddInfo::new(&block, len),
+				)
+				.unwrap(),
+			));
+		}
+
+		// The block N + 1 waits for the block N
+		let sender = "127.0.0.1:1".parse().unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[sender],
+			&serde_json::json!({}),
+		);
+		// The junk is not buffered
+		let mut junk: serde_json::Value =
+			serde_json::from_str(packages[1].data()).unwrap();
+		junk["block"]["nonce"] = serde_json::json!(u64::MAX);
+		let junk = common::package::Package::new(
+			common::package::Action::AddBlock,
+			junk.to_string(),
+		);
+		assert!(super::add_block(&blockchain, sender, &junk, &config).is_err());
+
+		assert_eq!(
+			super::add_block(&blockchain, sender, &packages[1], &config)
+				.unwrap(),
+			super::AddBlockStatus::Buffered
+		);
+		assert_eq!(blockchain.read().unwrap().len().unwrap(), 1);
+		assert_eq!(
+			super::add_block(&blockchain, sender, &packages[0], &config)
+				.unwrap(),
+			super::AddBlockStatus::Added
+		);
+		let lock = blockchain.read().unwrap();
+		assert_eq!(lock.len().unwrap(), 3);
+		assert_eq!(lock.to_string().unwrap(), source.to_string().unwrap());
+	}
+
+	#[test]
+	fn test_force_mine_single_transaction() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let (other_stream, mut other_peer) =
+			crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[sender],
+			&serde_json::json!({}),
+		);
+
+		// Add a single pending transaction
+		let recipient = {
+			let mut lock = blockchain.write().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&lock,
+				lock.miner(),
+			);
+			let recipient = transaction.recipient().clone();
+			lock.add_transaction(transaction).unwrap();
+			assert!(!lock.minable());
+			recipient
+		};
+
+		// The concurrent requests mine only one block
+		std::thread::scope(|s| {
+			s.spawn(|| {
+				let _ = super::force_mine(
+					other_stream,
+					&blockchain,
+					sender,
+					&config,
+				);
+			});
+			super::force_mine(stream, &blockchain, sender, &config).unwrap();
+		});
+		let mut responses = [
+			Package::receive(&config, &mut peer, None).unwrap(),
+			Package::receive(&config, &mut other_peer, None).unwrap(),
+		];
+		if responses[0].action() == Action::ForceMineFail {
+			responses.swap(0, 1);
+		}
+		assert_eq!(responses[0].action(), Action::ForceMineSuccess);
+		assert_eq!(responses[0].data(), "2");
+		assert_eq!(responses[1].action(), Action::ForceMineFail);
+		assert_eq!(
+			blockchain
+				.read()
+				.unwrap()
+				.get_balance(recipient.as_str())
+				.unwrap(),
+			5
+		);
+	}
+
+	#[test]
+	fn test_relay_transaction() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		// The other node has the same chain
+		let (other, other_dir) = crate::test_helpers::create_test_blockchain();
+		{
+			let mut other = other.write().unwrap();
+			*other = blockchain::Blockchain::from_str_into(
+				other.miner().clone(),
+				common::config::Consensus::default(),
+				&blockchain.read().unwrap().to_string().unwrap(),
+				&other_dir.path().join(crate::test_helpers::TEST_DB_NAME),
+			)
+			.unwrap();
+		}
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[listener.local_addr().unwrap()],
+			&serde_json::json!({"relay_transactions": true}),
+		);
+		let other_config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"relay_transactions": true}),
+		);
+
+		let (recipient, transaction) = {
+			let lock = blockchain.read().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&lock,
+				lock.miner(),
+			);
+			(
+				transaction.recipient().clone(),
+				serde_json::to_string(&transaction).unwrap(),
+			)
+		};
+
+		std::thread::scope(|s| {
+			let relayed = s.spawn(|| {
+				let (stream, sender) = listener.accept().unwrap();
+				super::stream(stream, sender, &other_config, &other)
+			});
+			let (stream, mut peer) =
+				crate::test_helpers::create_test_streams();
+			let sender = peer.local_addr().unwrap();
+			Package::new(Action::AddTransaction, transaction.as_str())
+				.send(&config, &mut peer)
+				.unwrap();
+			super::stream(stream, sender, &config, &blockchain).unwrap();
+			let response = Package::receive(&config, &mut peer, None).unwrap();
+			assert_eq!(response.action(), Action::AddTransactionSuccess);
+			relayed.join().unwrap().unwrap();
+		});
+		assert_eq!(
+			other.read().unwrap().get_balance(recipient.as_str()).unwrap(),
+			5
+		);
+
+		// The relay of the pending transaction is ignored
+		let package = Package::new(Action::BroadcastTransaction, transaction);
+		super::broadcast_transaction(&other, &package, &other_config).unwrap();
+	}
+
+	#[test]
+	fn test_get_block_by_hash() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let config =
+			crate::test_helpers::create_test_config(&serde_json::json!({}));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let hash = blockchain.read().unwrap().get_last_block_hash().unwrap();
+
+		for (data, found) in [(hash.clone(), true), ("0a".repeat(32), false)] {
+			let (stream, mut peer) =
+				crate::test_helpers::create_test_streams();
+			let sender = peer.local_addr().unwrap();
+			Package::new(Action::GetBlockByHash, data)
+				.send(&config, &mut peer)
+				.unwrap();
+			super::stream(stream, sender, &config, &blockchain).unwrap();
+			let response = Package::receive(&config, &mut peer, None).unwrap();
+			assert_eq!(response.action(), Action::GetBlockByHashSuccess);
+			let block: Option<blockchain::block::Block> =
+				serde_json::from_str(response.data()).unwrap();
+			assert_eq!(block.is_some(), found);
+			if let Some(b) = block {
+				assert_eq!(b.compute_hash().unwrap(), hash);
+			}
+		}
+	}
+
+	#[test]
+	fn test_get_fee_histogram() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let config =
+			crate::test_helpers::create_test_config(&serde_json::json!({}));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		{
+			let mut lock = blockchain.write().unwrap();
+			let miner = lock.miner().clone();
+			let mut transaction =
+				crate::test_helpers::create_test_transaction(&lock, &miner)
+					.with_fee(3);
+			transaction.sign(&miner).unwrap();
+			lock.add_transaction(transaction).unwrap();
+		}
+
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		Package::new(Action::GetFeeHistogram, "")
+			.send(&config, &mut peer)
+			.unwrap();
+		super::stream(stream, sender, &config, &blockchain).unwrap();
+		let response = Package::receive(&config, &mut peer, None).unwrap();
+		assert_eq!(response.action(), Action::GetFeeHistogramSuccess);
+		let histogram: Vec<(u64, usize)> =
+			serde_json::from_str(response.data()).unwrap();
+		assert_eq!(histogram, [(3, 1)]);
+	}
+
+	#[test]
+	fn test_get_config() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let config: common::config::Config =
+			serde_json::from_value(serde_json::json!({
+				"nodes": ["127.0.0.1:8888"],
+				"package_limits": {"max_size": 8192, "receive_timeout_secs": 5},
+				"consensus": {"chain_id": 7, "mining_reward": 3},
+				"node": {"max_connections": 2},
+				"tracing": {
+					"client": {"level": "TRACE", "path": "client-logs.log"},
+					"node": {"level": "TRACE", "path": "node-logs.log"},
+				},
+			}))
+			.unwrap();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		Package::new(Action::GetConfig, "").send(&config, &mut peer).unwrap();
+		super::stream(stream, sender, &config, &blockchain).unwrap();
+		let response = Package::receive(&config, &mut peer, None).unwrap();
+		assert_eq!(response.action(), Action::GetConfigSuccess);
+
+		let params: serde_json::Value =
+			serde_json::from_str(response.data()).unwrap();
+		assert_eq!(
+			params["consensus"],
+			serde_json::to_value(config.consensus()).unwrap()
+		);
+		assert_eq!(params["consensus"]["chain_id"], 7);
+		assert_eq!(params["consensus"]["mining_reward"], 3);
+		assert_eq!(
+			params["user_transactions_per_block"],
+			blockchain::consts::USER_TRANSACTIONS_PER_BLOCK
+		);
+		assert_eq!(
+			params["proof_of_work_difficulty"],
+			blockchain::consts::PROOF_OF_WORK_DIFFICULTY
+		);
+		// The settings of the node are private
+		for key in ["nodes", "node", "package_limits", "tracing"] {
+			assert!(params.get(key).is_none());
+		}
+		let params: crate::network_params::NetworkParams =
+			serde_json::from_str(response.data()).unwrap();
+		assert_eq!(params.consensus().chain_id(), 7);
+	}
+}
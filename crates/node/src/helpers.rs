@@ -1,64 +1,331 @@
 use anyhow::{Context as _, Result};
 
+/// Whether [`mine_block`] is running, so the blocks are mined one at a time.
+static MINING: std::sync::atomic::AtomicBool =
+	std::sync::atomic::AtomicBool::new(false);
+
 /// Starts mining a new block.
+///
+/// If the mining is stopped by a new block, it is retried
+/// `config.node().mining_retries()` times with the pending transactions,
+/// which are still valid, see [`retry_pending`].
+///
+/// Returns `false`, if a block is already being mined.
 #[tracing::instrument(skip(blockchain))]
 pub(crate) fn mine_block(
 	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
 	config: &common::config::Config,
-) -> Result<()> {
-	// In order not to interfere with other requests to `RwLock<Blockchain>`
-	let mut blockchain_clone = (*blockchain.read().unwrap()).clone();
+) -> Result<bool> {
+	use {
+		blockchain::error::{GenerateBlockProofOfWorkError, MineBlockError},
+		std::sync::atomic::Ordering,
+	};
+
+	// Checked and set at once, so the concurrent callers do not both mine
+	if MINING
+		.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+		.is_err()
+	{
+		tracing::debug!("A block is already being mined.");
+		return Ok(false);
+	}
+	let _mining = Mining;
+
+	// In order not to interfere with other requests to `RwLock<Blockchain>`.
+	// The new transactions are rejected until the blockchain is replaced,
+	// because they would be lost with it
+	let mut new_blockchain = blockchain.write().unwrap().seal();
+	let sealed = Sealed(Some(blockchain));
 
 	// Mine block
-	let new_block =
-		blockchain_clone.mine_block().context("Failed to mine block.")?;
+	let mut retries = config.node().mining_retries();
+	let new_block = loop {
+		match new_blockchain.mine_block() {
+			Ok(b) => break b,
+			Err(MineBlockError::GenerateBlockProofOfWork(
+				GenerateBlockProofOfWorkError::Stopped,
+			)) if retries > 0 => {
+				retries -= 1;
+				match retry_pending(blockchain, &new_blockchain) {
+					Some(b) => new_blockchain = b,
+					None => return Ok(true),
+				}
+				tracing::info!("Retrying to mine the pending transactions.");
+			}
+			Err(e) => return Err(e).context("Failed to mine block."),
+		}
+	};
 	// Make add info
-	let len = blockchain
-		.read()
-		.unwrap()
-		.len()
-		.context("Failed to get blockchain len.")?;
+	let len = new_blockchain.len().context("Failed to get blockchain len.")?;
 	let info = crate::block_add_info::BlockAddInfo::new(&new_block, len);
 	let info_json = serde_json::to_string(&info)
 		.context("Failed to convert add info to JSON.")?;
 
-	// Replace blockchain and drop some values because of move out of
-	// blockchain
-	drop(info);
-	drop(new_block);
-	*blockchain.write().unwrap() = blockchain_clone;
+	// Replace blockchain
+	sealed.replace(new_blockchain);
 
-	// Send a new block to nodes
+	// Send a new block to nodes in the background
 	tracing::info!(
-		"Sending a new block to the nodes ({})...",
+		"Queueing a new block for the nodes ({})...",
 		config.nodes().len()
 	);
 	let package = common::package::Package::new(
 		common::package::Action::AddBlock,
 		info_json,
 	);
-	for node in config.nodes() {
-		let mut stream = common::connect_or_continue!(node);
-		common::send_package_or_continue!(config, package, &mut stream, node);
+	crate::broadcast::enqueue(package, config);
+	Ok(true)
+}
+
+/// Releases [`MINING`] on drop, so it is released on any path.
+struct Mining;
+
+impl Drop for Mining {
+	fn drop(&mut self) {
+		MINING.store(false, std::sync::atomic::Ordering::Release);
+	}
+}
+
+/// Unseals the blockchain on drop, so it accepts new transactions again if
+/// the mining fails on any path, see [`blockchain::Blockchain::seal`].
+struct Sealed<'b, 'a>(
+	Option<&'b std::sync::RwLock<blockchain::Blockchain<'a>>>,
+);
+
+impl<'a> Sealed<'_, 'a> {
+	/// Replaces the sealed blockchain with the `mined` one, which is not
+	/// sealed.
+	fn replace(mut self, mined: blockchain::Blockchain<'a>) {
+		if let Some(blockchain) = self.0.take() {
+			*blockchain.write().unwrap() = mined;
+		}
+	}
+}
+
+impl Drop for Sealed<'_, '_> {
+	fn drop(&mut self) {
+		if let Some(blockchain) = self.0 {
+			blockchain
+				.write()
+				.unwrap_or_else(std::sync::PoisonError::into_inner)
+				.unseal();
+		}
+	}
+}
+
+/// Adds the pending transactions of the `stopped` blockchain to the
+/// `blockchain` again, because they were removed by the block which stopped
+/// the mining. The transactions which do not fit the new last block are
+/// dropped.
+///
+/// Returns the clone to mine on, or `None` if nothing is left to mine.
+fn retry_pending<'a>(
+	blockchain: &std::sync::RwLock<blockchain::Blockchain<'a>>,
+	stopped: &blockchain::Blockchain<'a>,
+) -> Option<blockchain::Blockchain<'a>> {
+	let mut lock = blockchain.write().unwrap();
+	lock.unseal();
+	for t in stopped.pending_transactions() {
+		match lock.add_transaction(t.clone()) {
+			Ok(())
+			| Err(blockchain::error::AddTransactionError::Duplicate) => {}
+			Err(e) => tracing::info!(
+				"The pending transaction {} is not retried: {e}",
+				t.random_string()
+			),
+		}
+	}
+	if lock.pending_transactions().is_empty() {
+		return None;
 	}
-	Ok(())
+	Some(lock.seal())
+}
+
+/// Relays the accepted transaction JSON to all `config.nodes()`. It is sent
+/// once, because the transaction also reaches them with the mined block.
+pub(crate) fn relay_transaction(
+	transaction_json: &str,
+	config: &common::config::Config,
+) {
+	tracing::debug!(
+		"Relaying the transaction to the nodes ({})...",
+		config.nodes().len()
+	);
+	let package = common::package::Package::new(
+		common::package::Action::BroadcastTransaction,
+		transaction_json,
+	);
+	send_to_nodes(&package, config, 0);
+}
+
+/// Waits for the next check of a background task, which runs every period
+/// returned by `period_of`, but not longer than a second. The config may be
+/// reloaded, so it is read again each time and the task is checked even if it
+/// is disabled with a zero period.
+///
+/// Returns the current config and the period.
+pub(crate) fn wait_next_check(
+	config: &crate::SharedConfig,
+	period_of: impl Fn(&common::config::Config) -> std::time::Duration,
+) -> (std::sync::Arc<common::config::Config>, std::time::Duration) {
+	const MAX_WAIT: std::time::Duration = std::time::Duration::from_secs(1);
+
+	let config = std::sync::Arc::clone(
+		&config.read().unwrap_or_else(std::sync::PoisonError::into_inner),
+	);
+	let period = period_of(&config);
+	std::thread::sleep(if period.is_zero() {
+		MAX_WAIT
+	} else {
+		period.min(MAX_WAIT)
+	});
+	(config, period)
+}
+
+/// The longest delay between the resendings to a node, so a node, which is
+/// down for a long time, does not hold the next broadcasts.
+const MAX_BLOCK_SEND_BACKOFF: std::time::Duration =
+	std::time::Duration::from_secs(10);
+
+/// Sends the `package` to all `config.nodes()` concurrently, so a slow node
+/// does not delay the others. See [`send_to_node`].
+pub(crate) fn send_to_nodes(
+	package: &common::package::Package,
+	config: &common::config::Config,
+	retries: u32,
+) {
+	std::thread::scope(|s| {
+		for &node in config.nodes() {
+			s.spawn(move || send_to_node(package, config, node, retries));
+		}
+	});
+}
+
+/// Sends the `package` to the `node`, connecting and writing with the
+/// `config.node().block_send_timeout_ms()` timeout. If the node did not
+/// receive it, it is retried `retries` times, with a delay starting from
+/// `config.node().block_send_backoff_ms()` and doubling before each retry up
+/// to [`MAX_BLOCK_SEND_BACKOFF`]. The retries are made by the broadcast
+/// worker, so they do not hold the handling of the requests.
+///
+/// A block is also retried if the node did not respond with
+/// `AddBlockSuccess` or `AddBlockBuffered`.
+///
+/// Returns `true`, if the node received the package.
+fn send_to_node(
+	package: &common::package::Package,
+	config: &common::config::Config,
+	node: common::nodes::Node,
+	retries: u32,
+) -> bool {
+	let timeout = std::time::Duration::from_millis(
+		config.node().block_send_timeout_ms(),
+	);
+	let mut backoff = std::time::Duration::from_millis(
+		config.node().block_send_backoff_ms(),
+	)
+	.min(MAX_BLOCK_SEND_BACKOFF);
+	for retry in 0..=retries {
+		if retry != 0 {
+			std::thread::sleep(backoff);
+			backoff = backoff.saturating_mul(2).min(MAX_BLOCK_SEND_BACKOFF);
+		}
+		let sent = std::net::TcpStream::connect_timeout(&node, timeout)
+			.and_then(|s| s.set_write_timeout(Some(timeout)).map(|()| s))
+			.map_err(anyhow::Error::from)
+			.and_then(|mut s| {
+				package.send(config, &mut s)?;
+				if package.action() == common::package::Action::AddBlock {
+					return receive_add_block_response(config, &mut s)
+						.map(Some);
+				}
+				Ok(None)
+			});
+		match sent {
+			Ok(Some(common::package::Action::AddBlockBuffered)) => {
+				tracing::info!("The block was buffered by {node}.");
+				return true;
+			}
+			Ok(Some(_)) => {
+				tracing::info!("The block was added by {node}.");
+				return true;
+			}
+			Ok(None) => return true,
+			Err(e) => tracing::debug!("Failed to send to {node}: {e:#}"),
+		}
+	}
+	tracing::warn!("The package was not sent to {node}.");
+	false
+}
+
+/// Receives the response of a node to `AddBlock`, failing if the block was
+/// neither added, nor buffered. Returns the action of the response.
+fn receive_add_block_response(
+	config: &common::config::Config,
+	stream: &mut std::net::TcpStream,
+) -> Result<common::package::Action> {
+	use common::package::Action;
+
+	let response = common::package::Package::receive(
+		config,
+		stream,
+		Some(common::set![
+			Action::AddBlockBuffered,
+			Action::AddBlockFail,
+			Action::AddBlockSuccess
+		]),
+	)
+	.context("Failed to receive a response.")?;
+	anyhow::ensure!(
+		response.action() != Action::AddBlockFail,
+		"The block was not added: {}",
+		response.data()
+	);
+	Ok(response.action())
 }
 
-/// Needed to move the valid blockchain from a specified `node`.
+/// Switches to the branch of the blockchain of the `node` with
+/// [`blockchain::Blockchain::try_reorg`], so the current blockchain is kept
+/// if the branch does not have more work or is invalid.
 #[tracing::instrument(skip(blockchain))]
-pub(crate) fn transfer_blockchain_from(
+pub(crate) fn reorg_from(
 	node: common::nodes::Node,
 	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
 	config: &common::config::Config,
-) -> Result<()> {
-	use std::sync::atomic::Ordering;
+) -> Result<blockchain::blockchain::Reorg> {
+	let blocks = request_blocks(node, config)?;
+	let blocks: Vec<blockchain::block::Block> = serde_json::from_str(&blocks)
+		.context("Failed to convert JSON to blocks.")?;
+	let reorg = blockchain
+		.write()
+		.unwrap()
+		.try_reorg(&blocks)
+		.context("Failed to switch to the branch.")?;
+	match reorg {
+		blockchain::blockchain::Reorg::Switched => {
+			tracing::info!("Switched to the branch of the {node}.");
+		}
+		blockchain::blockchain::Reorg::NotHeavier => {
+			tracing::info!("The branch of the {node} is not heavier.");
+		}
+	}
+	Ok(reorg)
+}
 
-	let mut stream =
-		std::net::TcpStream::connect(node).context("Failed to connect.")?;
+/// Requests the blocks JSON from the `node`.
+fn request_blocks(
+	node: common::nodes::Node,
+	config: &common::config::Config,
+) -> Result<String> {
+	let mut stream = connect(node, config)?;
 	// Sending request for blocks
-	common::package::Package::new(common::package::Action::GetBlocks, "")
-		.send(config, &mut stream)
-		.context("Failed to send request.")?;
+	let compressed = config.node().compress_blocks_transfer();
+	common::package::Package::new(
+		common::package::Action::GetBlocks,
+		if compressed { common::consts::GET_BLOCKS_GZIP } else { "" },
+	)
+	.send(config, &mut stream)
+	.context("Failed to send request.")?;
 	// Receiving node blocks
 	let response = common::package::Package::receive(
 		config,
@@ -67,18 +334,371 @@ pub(crate) fn transfer_blockchain_from(
 	)
 	.context("Failed to receive a response.")?;
 
-	// Attempting to rebuild the blockchain
-	let mut lock = blockchain.write().unwrap();
-	let miner = lock.miner().clone();
-	let new_blockchain =
-		blockchain::Blockchain::from_str(miner, response.data())
-			.context("Failed to build blockchain from str.")?;
-	// Last steps and blockchain replacement
-	if blockchain::IS_MINING.load(Ordering::SeqCst) {
-		blockchain::IS_MINING.store(false, Ordering::SeqCst);
-	}
-	*lock = new_blockchain;
-
-	tracing::info!("The blockchain has been replaced from the {node}.");
-	Ok(())
+	if compressed {
+		decompress(response.data(), config.package_limits().max_size())
+			.context("Failed to decompress blocks.")
+	} else {
+		Ok(response.data().to_owned())
+	}
+}
+
+/// Connects to the `node` with the `config.package_limits()` receive timeout,
+/// so an unreachable node does not hold the caller.
+pub(crate) fn connect(
+	node: common::nodes::Node,
+	config: &common::config::Config,
+) -> Result<std::net::TcpStream> {
+	std::net::TcpStream::connect_timeout(
+		&node,
+		std::time::Duration::from_secs(
+			config.package_limits().receive_timeout_secs(),
+		),
+	)
+	.context("Failed to connect.")
+}
+
+/// Compresses the `data` with gzip and encodes it with Base64, so it can be
+/// sent in a package.
+pub(crate) fn compress(data: &str) -> Result<String> {
+	use {base64::Engine as _, std::io::Write as _};
+
+	let mut encoder = flate2::write::GzEncoder::new(
+		Vec::new(),
+		flate2::Compression::default(),
+	);
+	encoder.write_all(data.as_bytes()).context("Failed to write.")?;
+	let bytes = encoder.finish().context("Failed to finish.")?;
+	Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// Reverses [`compress`]. The `data` must not expand to more than
+/// `max_size` bytes, so a small package can not take a lot of memory.
+pub(crate) fn decompress(data: &str, max_size: usize) -> Result<String> {
+	use {base64::Engine as _, std::io::Read as _};
+
+	let bytes = base64::engine::general_purpose::STANDARD
+		.decode(data)
+		.context("Failed to decode Base64.")?;
+	let mut rv = String::new();
+	flate2::read::GzDecoder::new(bytes.as_slice())
+		.take(max_size as u64 + 1)
+		.read_to_string(&mut rv)
+		.context("Failed to read.")?;
+	if rv.len() > max_size {
+		anyhow::bail!("The decompressed data is too big.");
+	}
+	Ok(rv)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_send_to_nodes_retries_down_node() {
+		use common::package::{Action, Package};
+
+		// Take a free port, which refuses connections for now
+		let node = std::net::TcpListener::bind("127.0.0.1:0")
+			.unwrap()
+			.local_addr()
+			.unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[node],
+			&serde_json::json!({
+				"block_send_retries": 3,
+				"block_send_backoff_ms": 200,
+			}),
+		);
+
+		// The node comes up after the first attempt
+		let receiver = std::thread::spawn(move || {
+			std::thread::sleep(std::time::Duration::from_millis(100));
+			let listener = std::net::TcpListener::bind(node).unwrap();
+			let (mut stream, _) = listener.accept().unwrap();
+			let config = crate::test_helpers::create_test_config(
+				&serde_json::json!({}),
+			);
+			let action =
+				Package::receive(&config, &mut stream, None).unwrap().action();
+			Package::new(Action::AddBlockSuccess, "")
+				.send(&config, &mut stream)
+				.unwrap();
+			action
+		});
+
+		super::send_to_nodes(
+			&Package::new(Action::AddBlock, "block"),
+			&config,
+			config.node().block_send_retries(),
+		);
+		assert_eq!(receiver.join().unwrap(), Action::AddBlock);
+	}
+
+	#[test]
+	fn test_retry_stopped_mining() {
+		use std::sync::atomic::Ordering;
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let (other, _other_dir) =
+			crate::test_helpers::create_test_blockchain();
+		let config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"mining_retries": 1}),
+		);
+		let mut pending = Vec::new();
+		{
+			let mut lock = blockchain.write().unwrap();
+			let miner = lock.miner().clone();
+			for _ in 0..blockchain::consts::USER_TRANSACTIONS_PER_BLOCK {
+				let transaction = crate::test_helpers::create_test_transaction(
+					&lock, &miner,
+				);
+				pending.push(transaction.random_string().to_owned());
+				lock.add_transaction(transaction).unwrap();
+			}
+		}
+
+		std::thread::scope(|s| {
+			let mining = s.spawn(|| super::mine_block(&blockchain, &config));
+			while !blockchain::IS_MINING.load(Ordering::SeqCst)
+				&& !mining.is_finished()
+			{
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+			// The block, which does not fit, stops the mining anyway
+			let other = other.read().unwrap();
+			let block = other.get_last_block().unwrap().unwrap();
+			assert!(blockchain
+				.write()
+				.unwrap()
+				.add_block(&block, false)
+				.is_err());
+			mining.join().unwrap().unwrap();
+		});
+
+		let lock = blockchain.read().unwrap();
+		assert_eq!(lock.len().unwrap(), 2);
+		let block = lock.get_last_block().unwrap().unwrap();
+		for random_string in &pending {
+			assert!(block
+				.transactions()
+				.iter()
+				.any(|t| t.random_string() == random_string));
+		}
+	}
+
+	#[test]
+	fn test_add_transaction_while_mining() {
+		use {
+			common::package::{Action, Package},
+			std::sync::atomic::Ordering,
+		};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"mining_retries": 0}),
+		);
+		let transaction = |lock: &blockchain::Blockchain| {
+			crate::test_helpers::create_test_transaction(lock, lock.miner())
+		};
+		let submit = |transaction: &blockchain::transaction::Transaction| {
+			let (stream, mut peer) =
+				crate::test_helpers::create_test_streams();
+			let sender = peer.local_addr().unwrap();
+			Package::new(
+				Action::AddTransaction,
+				serde_json::to_string(transaction).unwrap(),
+			)
+			.send(&config, &mut peer)
+			.unwrap();
+			let _ =
+				crate::handle::stream(stream, sender, &config, &blockchain);
+			Package::receive(&config, &mut peer, None).unwrap().action()
+		};
+		{
+			let mut lock = blockchain.write().unwrap();
+			let first = transaction(&lock);
+			lock.add_transaction(first).unwrap();
+		}
+
+		std::thread::scope(|s| {
+			let mining = s.spawn(|| super::mine_block(&blockchain, &config));
+			while !blockchain::IS_MINING.load(Ordering::SeqCst) {
+				assert!(!mining.is_finished());
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+			// The transaction would point to the replaced last block
+			let during = transaction(&blockchain.read().unwrap());
+			assert_eq!(submit(&during), Action::AddTransactionFail);
+
+			// The stopped mining fails, but unseals the blockchain
+			blockchain::IS_MINING.store(false, Ordering::SeqCst);
+			let _ = mining.join().unwrap();
+		});
+
+		assert!(!blockchain.read().unwrap().sealed());
+		let after = transaction(&blockchain.read().unwrap());
+		assert_eq!(submit(&after), Action::AddTransactionSuccess);
+	}
+
+	#[test]
+	fn test_transfer_compressed_blockchain() {
+		let _lock = crate::test_helpers::lock_mining();
+		let shared_config: &'static crate::SharedConfig =
+			Box::leak(Box::new(std::sync::RwLock::new(std::sync::Arc::new(
+				crate::test_helpers::create_test_config(
+					&serde_json::json!({}),
+				),
+			))));
+		let (other, _other_dir) =
+			crate::test_helpers::create_test_blockchain();
+		let other: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(other));
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let node = listener.local_addr().unwrap();
+		std::thread::spawn(move || {
+			crate::serve(&listener, shared_config, other)
+		});
+
+		let expected = other.read().unwrap().to_string().unwrap();
+		for compressed in [false, true] {
+			let config = crate::test_helpers::create_test_config_with_nodes(
+				&[node],
+				&serde_json::json!({"compress_blocks_transfer": compressed}),
+			);
+			assert_eq!(
+				super::request_blocks(node, &config).unwrap(),
+				expected
+			);
+		}
+
+		let compressed = super::compress(&expected).unwrap();
+		assert_ne!(compressed, expected);
+		assert_eq!(
+			super::decompress(&compressed, expected.len()).unwrap(),
+			expected
+		);
+		assert!(super::decompress(&compressed, expected.len() - 1).is_err());
+
+		// A few bytes of the gzip bomb must not be inflated entirely
+		let bomb = super::compress(&"0".repeat(1 << 20)).unwrap();
+		assert!(bomb.len() < 4096);
+		assert!(super::decompress(&bomb, 8192).is_err());
+	}
+
+	#[test]
+	fn test_reorg_from() {
+		let _lock = crate::test_helpers::lock_mining();
+		let shared_config: &'static crate::SharedConfig =
+			Box::leak(Box::new(std::sync::RwLock::new(std::sync::Arc::new(
+				crate::test_helpers::create_test_config(
+					&serde_json::json!({}),
+				),
+			))));
+		// The other chain is one block longer
+		let (other, _other_dir) =
+			crate::test_helpers::create_test_blockchain();
+		let other: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(other));
+		{
+			let mut other = other.write().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&other,
+				other.miner(),
+			);
+			other.add_transaction(transaction).unwrap();
+			other.mine_block().unwrap();
+		}
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let node = listener.local_addr().unwrap();
+		std::thread::spawn(move || {
+			crate::serve(&listener, shared_config, other)
+		});
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[node],
+			&serde_json::json!({}),
+		);
+
+		// The branch is switched to, and then it is not longer
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let expected = other.read().unwrap().to_string().unwrap();
+		for _ in 0..2 {
+			super::reorg_from(node, &blockchain, &config).unwrap();
+			assert_eq!(
+				blockchain.read().unwrap().to_string().unwrap(),
+				expected
+			);
+		}
+	}
+
+	#[test]
+	fn test_send_block_added() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let consensus: common::config::Consensus =
+			serde_json::from_value(serde_json::json!({
+				"require_proof_of_work": false
+			}))
+			.unwrap();
+		let (blockchain, _dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				consensus.clone(),
+			);
+		// The other node has the same chain
+		let (other, other_dir) =
+			crate::test_helpers::create_test_blockchain_with(
+				consensus.clone(),
+			);
+		{
+			let mut other = other.write().unwrap();
+			*other = blockchain::Blockchain::from_str_into(
+				other.miner().clone(),
+				consensus,
+				&blockchain.read().unwrap().to_string().unwrap(),
+				&other_dir.path().join(crate::test_helpers::TEST_DB_NAME),
+			)
+			.unwrap();
+		}
+
+		// Mine a block
+		let info = {
+			let mut lock = blockchain.write().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&lock,
+				lock.miner(),
+			);
+			lock.add_transaction(transaction).unwrap();
+			let block = lock.mine_block().unwrap();
+			// Same as `BlockAddInfo`
+			serde_json::json!({"block": block, "blockchain_len": 2})
+				.to_string()
+		};
+
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let node = listener.local_addr().unwrap();
+		let sender = "127.0.0.1:1".parse().unwrap();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[node],
+			&serde_json::json!({}),
+		);
+		let other_config = crate::test_helpers::create_test_config_with_nodes(
+			&[sender],
+			&serde_json::json!({}),
+		);
+		std::thread::scope(|s| {
+			s.spawn(|| {
+				let (stream, _) = listener.accept().unwrap();
+				crate::handle::stream(stream, sender, &other_config, &other)
+					.unwrap();
+			});
+			assert!(super::send_to_node(
+				&Package::new(Action::AddBlock, info),
+				&config,
+				node,
+				0
+			));
+		});
+		assert_eq!(other.read().unwrap().len().unwrap(), 2);
+	}
 }
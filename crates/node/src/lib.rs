@@ -1,5 +1,4 @@
 #![deny(clippy::correctness)]
-#![feature(error_iter)]
 #![warn(
 	clippy::complexity,
 	clippy::pedantic,
@@ -15,56 +14,326 @@
 )]
 
 mod block_add_info;
+mod broadcast;
 mod handle;
 mod helpers;
+mod mining;
+mod network_params;
+mod orphans;
+mod status;
+#[cfg(test)]
+mod test_helpers;
+mod watchdog;
 
 use anyhow::{Context as _, Result};
 
-/// An entrypoint that starts a new node at the specified `address`.
-pub fn launch(address: common::nodes::Node) -> Result<()> {
+/// An entrypoint that starts a new node at the specified `address`. The
+/// config is loaded from the `config_path`, if it is passed, see
+/// [`common::config::Config::resolve_path`].
+pub fn launch(
+	address: common::nodes::Node,
+	config_path: Option<&std::path::Path>,
+) -> Result<()> {
 	// Load the config, user and a blockchain
-	let config = common::config::Config::load(Some(address))
+	let config = common::config::Config::load(config_path, Some(address))
 		.context("Failed to load the config.")?;
 	let user = blockchain::user::User::load_or_create()
 		.context("Failed to load or create a user.")?;
-	let mut blockchain = blockchain::Blockchain::load_or_create(user)
-		.context("Failed to load or create the blockchain.")?;
+	let mut blockchain = blockchain::Blockchain::load_or_create(
+		user,
+		config.consensus().clone(),
+	)
+	.context("Failed to load or create the blockchain.")?;
 	if blockchain.is_empty().context("Failed to get blockchain len.")? {
 		blockchain
 			.mine_genesis_block()
 			.context("Failed to mine genesis block.")?;
 	}
 
-	let _tracing_guard =
+	let tracing_guard =
 		common::tracing::set_subscriber(config.tracing().node())
 			.context("Failed to set tracing subscriber.")?;
 
 	// Leak a blockchain and the config
 	let blockchain_leaked: &'static std::sync::RwLock<blockchain::Blockchain> =
 		Box::leak(Box::new(std::sync::RwLock::new(blockchain)));
-	let config_leaked: &'static common::config::Config =
-		Box::leak(Box::new(config));
+	let config_leaked: &'static SharedConfig = Box::leak(Box::new(
+		std::sync::RwLock::new(std::sync::Arc::new(config)),
+	));
+
+	handle_signals(
+		tracing_guard,
+		config_leaked,
+		config_path.map(std::path::Path::to_path_buf),
+		address,
+	)
+	.context("Failed to handle signals.")?;
+	mining::spawn(blockchain_leaked, config_leaked);
+	mining::spawn_grace_timer(blockchain_leaked, config_leaked);
+	watchdog::spawn(blockchain_leaked, config_leaked);
 
 	let node = std::net::TcpListener::bind(address)
 		.context("Failed to bind listener.")?;
 	println!("Listening at {address}...");
+	serve(&node, config_leaked, blockchain_leaked)
+}
+
+/// An offline entrypoint that recomputes the balance states of the stored
+/// blocks and prints the discrepancies. It fails, if there is no database.
+/// If `rewrite` is `true`, the stored balance states are replaced, see
+/// [`blockchain::Blockchain::recompute_balance_states`].
+///
+/// The `config_path` is the same as in [`launch`].
+pub fn repair_balance_states(
+	rewrite: bool,
+	config_path: Option<&std::path::Path>,
+) -> Result<()> {
+	let config = common::config::Config::load(config_path, None)
+		.context("Failed to load the config.")?;
+	// The miner is not used to recompute, so the key is not loaded
+	let user = blockchain::user::User::generate()
+		.context("Failed to make a user.")?;
+	let blockchain = blockchain::Blockchain::load_at(
+		user,
+		config.consensus().clone(),
+		&blockchain::consts::DB_PATH,
+	)
+	.context("Failed to load the blockchain.")?;
+
+	let discrepancies = blockchain
+		.recompute_balance_states(rewrite)
+		.context("Failed to recompute balance states.")?;
+	for d in &discrepancies {
+		println!(
+			"Block {}: {} has {} instead of {}.",
+			d.height(),
+			d.address(),
+			d.stored(),
+			d.computed()
+		);
+	}
+	if discrepancies.is_empty() {
+		println!("Balance states are correct.");
+	} else if rewrite {
+		println!("Balance states are rewritten.");
+	}
+	Ok(())
+}
+
+/// The config, which is swapped on reload. Connections take a snapshot of it
+/// when accepted.
+type SharedConfig = std::sync::RwLock<std::sync::Arc<common::config::Config>>;
+
+/// Waits for signals in a new thread, because `serve` never returns.
+///
+/// On SIGHUP, reloads the config from the `config_path`, excluding `address`
+/// from the nodes, and keeps the old one if the new one is invalid. On SIGINT
+/// or SIGTERM, drops `tracing_guard` to flush the buffered log lines and
+/// exits.
+fn handle_signals<G>(
+	tracing_guard: G,
+	config: &'static SharedConfig,
+	config_path: Option<std::path::PathBuf>,
+	address: common::nodes::Node,
+) -> Result<()>
+where
+	G: Send + 'static,
+{
+	use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+
+	let mut signals =
+		signal_hook::iterator::Signals::new([SIGHUP, SIGINT, SIGTERM])
+			.context("Failed to register signals.")?;
+	std::thread::spawn(move || {
+		for signal in signals.forever() {
+			if signal == SIGHUP {
+				match common::config::Config::reload(
+					config,
+					config_path.as_deref(),
+					Some(address),
+				) {
+					Ok(()) => tracing::info!("Config reloaded."),
+					Err(e) => tracing::warn!(
+						"Failed to reload the config, keeping the old one: {e}"
+					),
+				}
+				continue;
+			}
+			tracing::info!("Received signal {signal}, shutting down...");
+			drop(tracing_guard);
+			std::process::exit(0);
+		}
+	});
+	Ok(())
+}
+
+/// Accepts connections and handles each of them in a new thread.
+///
+/// If there are already `config.node().max_connections()` connections, a new
+/// one gets `Action::ServerBusy` package and is closed.
+///
+/// Each connection is handled with the config that is current when it is
+/// accepted, so a reloaded config is applied to subsequent connections.
+///
+/// The failed handlers are counted in `status::HANDLER_FAILURES`.
+fn serve(
+	node: &std::net::TcpListener,
+	shared_config: &'static SharedConfig,
+	blockchain: &'static std::sync::RwLock<blockchain::Blockchain>,
+) -> ! {
+	use std::sync::atomic::Ordering;
+
+	let connections: &'static std::sync::atomic::AtomicUsize =
+		Box::leak(Box::new(std::sync::atomic::AtomicUsize::new(0)));
 	loop {
-		let Ok((stream, from_address)) = node.accept() else {
+		let Ok((mut stream, from_address)) = node.accept() else {
 			tracing::debug!("Failed to accept connection.");
 			continue;
 		};
 		tracing::debug!("New connection from {from_address}.");
-		std::thread::spawn(move || {
-			if let Err(e) = handle::stream(
-				stream,
-				from_address,
-				config_leaked,
-				blockchain_leaked,
+		let config = std::sync::Arc::clone(
+			&shared_config
+				.read()
+				.unwrap_or_else(std::sync::PoisonError::into_inner),
+		);
+
+		// Reject the connection if the cap is reached
+		if connections.fetch_add(1, Ordering::AcqRel)
+			>= config.node().max_connections()
+		{
+			connections.fetch_sub(1, Ordering::AcqRel);
+			tracing::warn!("Too many connections, rejecting {from_address}.");
+			if let Err(e) = common::package::Package::new(
+				common::package::Action::ServerBusy,
+				"",
 			)
-			.context("Failed to handle stream.")
+			.send(&config, &mut stream)
+			{
+				tracing::debug!("Failed to send busy package: {e}");
+			}
+			continue;
+		}
+
+		let connection = Connection(connections);
+		std::thread::spawn(move || {
+			let _connection = connection;
+			if let Err(e) =
+				handle::stream(stream, from_address, &config, blockchain)
+					.context("Failed to handle stream.")
 			{
 				tracing::warn!("\n{:?}\n", e);
+				status::HANDLER_FAILURES.fetch_add(1, Ordering::Relaxed);
 			}
 		});
 	}
 }
+
+/// Counted connection of [`serve`]. It is uncounted on drop, so a panicked
+/// handler does not hold the place of a connection.
+struct Connection(&'static std::sync::atomic::AtomicUsize);
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_serve_max_connections() {
+		let _lock = crate::test_helpers::lock_mining();
+		let config =
+			std::sync::Arc::new(crate::test_helpers::create_test_config(
+				&serde_json::json!({"max_connections": 1}),
+			));
+		let shared_config: &'static super::SharedConfig = Box::leak(Box::new(
+			std::sync::RwLock::new(std::sync::Arc::clone(&config)),
+		));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let blockchain: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(blockchain));
+
+		let node = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = node.local_addr().unwrap();
+		std::thread::spawn(move || {
+			super::serve(&node, shared_config, blockchain)
+		});
+
+		// The first connection is handled and waits for a package
+		let _first = std::net::TcpStream::connect(address).unwrap();
+		let mut second = std::net::TcpStream::connect(address).unwrap();
+		let package =
+			common::package::Package::receive(&config, &mut second, None)
+				.unwrap();
+		assert_eq!(package.action(), common::package::Action::ServerBusy);
+		assert!(common::package::Package::receive(&config, &mut second, None)
+			.is_err());
+	}
+
+	#[test]
+	fn test_uncount_panicked_connection() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		let connections: &'static AtomicUsize =
+			Box::leak(Box::new(AtomicUsize::new(1)));
+		let connection = super::Connection(connections);
+		assert!(std::thread::spawn(move || {
+			let _connection = connection;
+			panic!("The handler panicked.");
+		})
+		.join()
+		.is_err());
+		assert_eq!(connections.load(Ordering::Acquire), 0);
+	}
+
+	#[test]
+	fn test_handler_failures_in_status() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let config = std::sync::Arc::new(
+			crate::test_helpers::create_test_config(&serde_json::json!({})),
+		);
+		let shared_config: &'static super::SharedConfig = Box::leak(Box::new(
+			std::sync::RwLock::new(std::sync::Arc::clone(&config)),
+		));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let blockchain: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(blockchain));
+		let node = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = node.local_addr().unwrap();
+		std::thread::spawn(move || {
+			super::serve(&node, shared_config, blockchain)
+		});
+		let status = || -> crate::status::Status {
+			let mut stream = std::net::TcpStream::connect(address).unwrap();
+			Package::new(Action::GetStatus, "")
+				.send(&config, &mut stream)
+				.unwrap();
+			let response =
+				Package::receive(&config, &mut stream, None).unwrap();
+			assert_eq!(response.action(), Action::GetStatusSuccess);
+			serde_json::from_str(response.data()).unwrap()
+		};
+
+		let before = status();
+		assert_eq!(before.blockchain_len(), 1);
+		for _ in 0..3 {
+			let mut stream = std::net::TcpStream::connect(address).unwrap();
+			Package::new(Action::AddTransaction, "not a transaction")
+				.send(&config, &mut stream)
+				.unwrap();
+			let response =
+				Package::receive(&config, &mut stream, None).unwrap();
+			assert_eq!(response.action(), Action::AddTransactionFail);
+		}
+
+		// The handlers fail after responding
+		let started = std::time::Instant::now();
+		while status().handler_failures() < before.handler_failures() + 3 {
+			assert!(started.elapsed() < std::time::Duration::from_secs(5));
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+	}
+}
@@ -0,0 +1,170 @@
+/// Whether the background miner should check the pending transactions.
+static REQUESTED: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
+static REQUESTED_CHANGED: std::sync::Condvar = std::sync::Condvar::new();
+
+/// Wakes up the background miner. The request is kept until the miner takes
+/// it, so it is not lost if the miner is busy.
+pub(crate) fn request() {
+	*REQUESTED.lock().unwrap_or_else(std::sync::PoisonError::into_inner) =
+		true;
+	REQUESTED_CHANGED.notify_one();
+}
+
+/// Starts the background miner, which mines a new block on each
+/// [`request`], if there are enough transactions or the oldest one has
+/// waited for `config.node().partial_block_grace_ms()`. The config is taken
+/// when the request is taken. The mining is delayed by
+/// `config.node().last_slot_window_ms()`.
+pub(crate) fn spawn(
+	blockchain: &'static std::sync::RwLock<blockchain::Blockchain>,
+	config: &'static crate::SharedConfig,
+) {
+	std::thread::spawn(move || loop {
+		{
+			let mut requested = REQUESTED_CHANGED
+				.wait_while(
+					REQUESTED
+						.lock()
+						.unwrap_or_else(std::sync::PoisonError::into_inner),
+					|r| !*r,
+				)
+				.unwrap_or_else(std::sync::PoisonError::into_inner);
+			*requested = false;
+		}
+		let config = std::sync::Arc::clone(
+			&config.read().unwrap_or_else(std::sync::PoisonError::into_inner),
+		);
+		if !blockchain.read().unwrap().minable_with_grace(
+			std::time::Duration::from_millis(
+				config.node().partial_block_grace_ms(),
+			),
+		) {
+			continue;
+		}
+		// Let the higher fee transactions replace the cheap ones
+		std::thread::sleep(std::time::Duration::from_millis(
+			config.node().last_slot_window_ms(),
+		));
+		if let Err(e) = crate::helpers::mine_block(blockchain, &config) {
+			tracing::warn!("Failed to mine block in the background: {e:?}");
+		}
+	});
+}
+
+/// Starts the timer, which mines a partial block when the oldest pending
+/// transaction has waited for `config.node().partial_block_grace_ms()`,
+/// because no more transactions may come to fill the block. With the
+/// background mining, the miner is only requested.
+pub(crate) fn spawn_grace_timer(
+	blockchain: &'static std::sync::RwLock<blockchain::Blockchain>,
+	config: &'static crate::SharedConfig,
+) {
+	use std::{sync::atomic::Ordering, time::Duration};
+
+	std::thread::spawn(move || loop {
+		let (config, grace) = crate::helpers::wait_next_check(config, |c| {
+			Duration::from_millis(c.node().partial_block_grace_ms())
+		});
+		if blockchain::IS_MINING.load(Ordering::Acquire)
+			|| !blockchain
+				.read()
+				.unwrap_or_else(std::sync::PoisonError::into_inner)
+				.minable_with_grace(grace)
+		{
+			continue;
+		}
+		if config.node().background_mining() {
+			request();
+		} else if let Err(e) = crate::helpers::mine_block(blockchain, &config)
+		{
+			tracing::warn!("Failed to mine a partial block: {e:?}");
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_response_before_mining() {
+		use common::package::{Action, Package};
+
+		let _lock = crate::test_helpers::lock_mining();
+		let config =
+			std::sync::Arc::new(crate::test_helpers::create_test_config(
+				&serde_json::json!({"background_mining": true}),
+			));
+		let shared_config: &'static crate::SharedConfig = Box::leak(Box::new(
+			std::sync::RwLock::new(std::sync::Arc::clone(&config)),
+		));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let blockchain: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(blockchain));
+		let transaction = |lock: &blockchain::Blockchain| {
+			crate::test_helpers::create_test_transaction(lock, lock.miner())
+		};
+		{
+			let mut lock = blockchain.write().unwrap();
+			let first = transaction(&lock);
+			lock.add_transaction(first).unwrap();
+		}
+
+		// The block is filled, but the response does not wait for it
+		let second = transaction(&blockchain.read().unwrap());
+		let (stream, mut peer) = crate::test_helpers::create_test_streams();
+		let sender = peer.local_addr().unwrap();
+		Package::new(
+			Action::AddTransaction,
+			serde_json::to_string(&second).unwrap(),
+		)
+		.send(&config, &mut peer)
+		.unwrap();
+		crate::handle::stream(stream, sender, &config, blockchain).unwrap();
+		let response = Package::receive(&config, &mut peer, None).unwrap();
+		assert_eq!(response.action(), Action::AddTransactionSuccess);
+		assert!(blockchain.read().unwrap().minable());
+		assert_eq!(blockchain.read().unwrap().len().unwrap(), 1);
+
+		// The request is kept for the miner
+		super::spawn(blockchain, shared_config);
+		let started = std::time::Instant::now();
+		while blockchain.read().unwrap().len().unwrap() == 1 {
+			assert!(started.elapsed() < std::time::Duration::from_secs(30));
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		assert_eq!(blockchain.read().unwrap().len().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_grace_timer() {
+		let _lock = crate::test_helpers::lock_mining();
+		let config =
+			std::sync::Arc::new(crate::test_helpers::create_test_config(
+				&serde_json::json!({"partial_block_grace_ms": 100}),
+			));
+		let shared_config: &'static crate::SharedConfig = Box::leak(Box::new(
+			std::sync::RwLock::new(std::sync::Arc::clone(&config)),
+		));
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let blockchain: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(blockchain));
+		{
+			let mut lock = blockchain.write().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&lock,
+				lock.miner(),
+			);
+			lock.add_transaction(transaction).unwrap();
+			assert!(!lock.minable());
+		}
+
+		// The single transaction is mined without a full block
+		let started = std::time::Instant::now();
+		super::spawn_grace_timer(blockchain, shared_config);
+		while blockchain.read().unwrap().len().unwrap() == 1 {
+			assert!(started.elapsed() < std::time::Duration::from_secs(30));
+			std::thread::sleep(std::time::Duration::from_millis(10));
+		}
+		assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+		assert_eq!(blockchain.read().unwrap().len().unwrap(), 2);
+	}
+}
@@ -0,0 +1,30 @@
+/// The parameters of the network, which a client needs to make valid
+/// transactions. They are sent in response to `GetConfig`. The settings of
+/// the node itself are not included.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct NetworkParams {
+	consensus: common::config::Consensus,
+	proof_of_work_difficulty: u8,
+	user_transactions_per_block: u8,
+	storage_reward: u64,
+	storage_reward_starting_from: u64,
+}
+
+impl NetworkParams {
+	common::accessor!(& consensus -> &common::config::Consensus);
+
+	/// Takes the `config.consensus()` and the constants of the blockchain.
+	#[must_use]
+	pub fn new(config: &common::config::Config) -> Self {
+		Self {
+			consensus: config.consensus().clone(),
+			proof_of_work_difficulty:
+				blockchain::consts::PROOF_OF_WORK_DIFFICULTY,
+			user_transactions_per_block:
+				blockchain::consts::USER_TRANSACTIONS_PER_BLOCK,
+			storage_reward: blockchain::consts::STORAGE_REWARD,
+			storage_reward_starting_from:
+				blockchain::consts::STORAGE_REWARD_STARTING_FROM,
+		}
+	}
+}
@@ -0,0 +1,101 @@
+/// Blocks, which arrived before their parents, by their previous hashes.
+static POOL: std::sync::Mutex<Option<Pool>> = std::sync::Mutex::new(None);
+
+#[derive(Default)]
+struct Pool {
+	blocks:
+		std::collections::HashMap<String, blockchain::block::Block<'static>>,
+	/// The previous hashes in the order of arrival, so the oldest blocks are
+	/// evicted first.
+	order: std::collections::VecDeque<String>,
+}
+
+/// Buffers the `block` until its parent is added. A buffered block with the
+/// same parent is replaced.
+///
+/// If there are already `config.node().orphan_pool_size()` blocks, the
+/// oldest ones are evicted.
+pub(crate) fn insert(
+	block: blockchain::block::Block<'static>,
+	config: &common::config::Config,
+) {
+	let Some(previous_hash) = block.previous_hash().map(str::to_owned) else {
+		return;
+	};
+	let mut pool =
+		POOL.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+	let pool = pool.get_or_insert_with(Pool::default);
+	if pool.blocks.insert(previous_hash.clone(), block).is_none() {
+		pool.order.push_back(previous_hash);
+	}
+	while pool.blocks.len() > config.node().orphan_pool_size() {
+		let Some(oldest) = pool.order.pop_front() else {
+			break;
+		};
+		pool.blocks.remove(&oldest);
+		tracing::warn!("The orphan pool is full, evicted the oldest block.");
+	}
+}
+
+/// Adds the buffered blocks, whose parents are added, one by one. A block
+/// which fails to be added is dropped.
+pub(crate) fn add_children(
+	blockchain: &mut blockchain::Blockchain,
+) -> anyhow::Result<()> {
+	use anyhow::Context as _;
+
+	loop {
+		let hash = blockchain
+			.get_last_block_hash()
+			.context("Failed to get last block hash.")?;
+		let Some(block) = take(&hash) else {
+			return Ok(());
+		};
+		if let Err(e) = blockchain.add_block(&block, false) {
+			tracing::warn!("Failed to add the orphan block: {e}.");
+			return Ok(());
+		}
+		tracing::info!("The orphan block with the parent {hash} is added.");
+	}
+}
+
+/// Takes the buffered block, whose parent has the `hash`.
+fn take(hash: &str) -> Option<blockchain::block::Block<'static>> {
+	let mut pool =
+		POOL.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+	let pool = pool.as_mut()?;
+	let block = pool.blocks.remove(hash)?;
+	pool.order.retain(|h| h != hash);
+	Some(block)
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_evict_oldest() {
+		let _lock = crate::test_helpers::lock_mining();
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let genesis = serde_json::to_value(
+			blockchain.read().unwrap().get_last_block().unwrap().unwrap(),
+		)
+		.unwrap();
+		let config = crate::test_helpers::create_test_config(
+			&serde_json::json!({"orphan_pool_size": 2}),
+		);
+
+		// The pool is shared, but the other tests which use it hold the
+		// mining lock too
+		let parents: Vec<String> =
+			(0..3).map(|i| format!("test-evict-oldest-{i}")).collect();
+		for parent in &parents {
+			let mut block = genesis.clone();
+			block["previous_hash"] = parent.as_str().into();
+			super::insert(serde_json::from_value(block).unwrap(), &config);
+		}
+		assert!(super::take(&parents[0]).is_none());
+		for parent in &parents[1..] {
+			let block = super::take(parent).unwrap();
+			assert_eq!(block.previous_hash(), Some(parent.as_str()));
+		}
+	}
+}
@@ -0,0 +1,28 @@
+/// How many connection handlers failed since the start. Repeated failures
+/// point to a systemic problem, for example, a corrupted database.
+pub(crate) static HANDLER_FAILURES: std::sync::atomic::AtomicUsize =
+	std::sync::atomic::AtomicUsize::new(0);
+
+/// Health of the node, which is sent in response to `GetStatus`.
+#[derive(serde::Deserialize, serde::Serialize)]
+pub(crate) struct Status {
+	blockchain_len: usize,
+	handler_failures: usize,
+}
+
+impl Status {
+	common::accessor!(copy blockchain_len -> usize);
+
+	common::accessor!(copy handler_failures -> usize);
+
+	/// Takes the current `HANDLER_FAILURES`.
+	#[inline]
+	#[must_use]
+	pub fn new(blockchain_len: usize) -> Self {
+		Self {
+			blockchain_len,
+			handler_failures: HANDLER_FAILURES
+				.load(std::sync::atomic::Ordering::Relaxed),
+		}
+	}
+}
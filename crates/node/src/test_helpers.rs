@@ -0,0 +1,89 @@
+static MINING_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Serializes tests that use the global `blockchain::IS_MINING`.
+pub fn lock_mining() -> std::sync::MutexGuard<'static, ()> {
+	MINING_LOCK.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Creates a config without nodes and with the passed `node` options.
+#[must_use]
+pub fn create_test_config(node: &serde_json::Value) -> common::config::Config {
+	create_test_config_with_nodes(&[], node)
+}
+
+/// Same as [`create_test_config`], but with the passed `nodes`.
+#[must_use]
+pub fn create_test_config_with_nodes(
+	nodes: &[common::nodes::Node],
+	node: &serde_json::Value,
+) -> common::config::Config {
+	serde_json::from_value(serde_json::json!({
+		"nodes": nodes,
+		"package_limits": {"max_size": 8192, "receive_timeout_secs": 5},
+		"node": node,
+		"tracing": {
+			"client": {"level": "TRACE", "path": "client-logs.log"},
+			"node": {"level": "TRACE", "path": "node-logs.log"},
+		},
+	}))
+	.unwrap()
+}
+
+/// The name of the database of [`create_test_blockchain`] in its directory.
+pub const TEST_DB_NAME: &str = "blockchain.db";
+
+/// Creates a blockchain with a mined genesis block in a temporary database.
+/// Returns it with the directory of the database, [`TEST_DB_NAME`], which is
+/// removed on drop, so keep it while the blockchain is used.
+///
+/// Hold [`lock_mining`] while using it.
+#[must_use]
+pub fn create_test_blockchain<'a>(
+) -> (std::sync::RwLock<blockchain::Blockchain<'a>>, tempfile::TempDir) {
+	create_test_blockchain_with(common::config::Consensus::default())
+}
+
+/// Same as [`create_test_blockchain`], but with the passed `consensus`.
+#[must_use]
+pub fn create_test_blockchain_with<'a>(
+	consensus: common::config::Consensus,
+) -> (std::sync::RwLock<blockchain::Blockchain<'a>>, tempfile::TempDir) {
+	let dir = tempfile::tempdir().unwrap();
+	let user = blockchain::user::User::generate().unwrap();
+	let mut blockchain = blockchain::Blockchain::load_or_create_at(
+		user,
+		consensus,
+		&dir.path().join(TEST_DB_NAME),
+	)
+	.unwrap();
+	blockchain.mine_genesis_block().unwrap();
+	(std::sync::RwLock::new(blockchain), dir)
+}
+
+/// Creates a transaction of 5 from the `sender` to a new user on top of the
+/// last block of the `blockchain`, signed by the `sender`.
+#[must_use]
+pub fn create_test_transaction<'a>(
+	blockchain: &blockchain::Blockchain,
+	sender: &blockchain::user::User,
+) -> blockchain::transaction::Transaction<'a> {
+	let mut transaction = blockchain::transaction::Transaction::new(
+		sender.address().clone(),
+		blockchain::user::User::generate().unwrap().address().clone(),
+		std::num::NonZeroU64::new(5).unwrap(),
+		blockchain.get_last_block_hash().unwrap(),
+		blockchain.consensus().chain_id(),
+	);
+	transaction.sign(sender).unwrap();
+	transaction
+}
+
+/// Creates two connected streams.
+#[must_use]
+pub fn create_test_streams() -> (std::net::TcpStream, std::net::TcpStream) {
+	let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+	let peer =
+		std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+	let (stream, _) = listener.accept().unwrap();
+	(stream, peer)
+}
@@ -0,0 +1,188 @@
+use anyhow::{Context as _, Result};
+
+/// The last block hash and since when it is the last one.
+type Tip = Option<(String, std::time::Instant)>;
+
+/// Starts the watchdog, which switches to the branch of the longest node, if
+/// the last block was not replaced for `config.node().stall_resync_secs()`
+/// and the node has a longer blockchain.
+pub(crate) fn spawn(
+	blockchain: &'static std::sync::RwLock<blockchain::Blockchain>,
+	config: &'static crate::SharedConfig,
+) {
+	use std::time::Duration;
+
+	std::thread::spawn(move || {
+		let mut tip = None;
+		loop {
+			let (config, stall) =
+				crate::helpers::wait_next_check(config, |c| {
+					Duration::from_secs(c.node().stall_resync_secs())
+				});
+			if stall.is_zero() {
+				tip = None;
+				continue;
+			}
+			if let Err(e) =
+				resync_if_stalled(blockchain, &config, stall, &mut tip)
+			{
+				tracing::warn!(
+					"Failed to resync the stalled blockchain: {e:?}"
+				);
+			}
+		}
+	});
+}
+
+/// Updates the `tip` and, if it was not replaced for `stall`, switches to the
+/// branch of the longest node with `crate::helpers::reorg_from`, if that node
+/// reports a longer blockchain. The reported length is not trusted: the
+/// branch is switched to only if it is valid and has more work.
+///
+/// Returns `true`, if the branch is switched to.
+fn resync_if_stalled(
+	blockchain: &std::sync::RwLock<blockchain::Blockchain>,
+	config: &common::config::Config,
+	stall: std::time::Duration,
+	tip: &mut Tip,
+) -> Result<bool> {
+	let (hash, len) = {
+		let lock = blockchain
+			.read()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+		let hash = lock
+			.get_last_block_hash()
+			.context("Failed to get last block hash.")?;
+		(hash, lock.len().context("Failed to get blockchain len.")?)
+	};
+	let since = match tip {
+		Some((last, since)) if *last == hash => *since,
+		_ => {
+			*tip = Some((hash, std::time::Instant::now()));
+			return Ok(false);
+		}
+	};
+	if since.elapsed() < stall {
+		return Ok(false);
+	}
+
+	// Find the longest node
+	let mut longest: Option<(common::nodes::Node, usize)> = None;
+	for node in config.nodes() {
+		match request_len(*node, config) {
+			Ok(node_len) => {
+				if longest.map_or(len, |(_, l)| l) < node_len {
+					longest = Some((*node, node_len));
+				}
+			}
+			Err(e) => tracing::debug!("Failed to get len from {node}: {e}"),
+		}
+	}
+	let Some((node, node_len)) = longest else {
+		return Ok(false);
+	};
+
+	*tip = None;
+	tracing::warn!(
+		"The last block was not replaced for {} seconds, switching to the \
+		 blockchain with length {node_len} from {node}.",
+		since.elapsed().as_secs()
+	);
+	let reorg = crate::helpers::reorg_from(node, blockchain, config)
+		.with_context(|| {
+			format!("Failed to switch to the branch of {node}.")
+		})?;
+	crate::orphans::add_children(&mut blockchain.write().unwrap())
+		.context("Failed to add orphan blocks.")?;
+	Ok(reorg == blockchain::blockchain::Reorg::Switched)
+}
+
+/// Requests the blockchain length from the `node`.
+fn request_len(
+	node: common::nodes::Node,
+	config: &common::config::Config,
+) -> Result<usize> {
+	let mut stream = crate::helpers::connect(node, config)?;
+	common::package::Package::new(
+		common::package::Action::GetBlockchainLen,
+		"",
+	)
+	.send(config, &mut stream)
+	.context("Failed to send request.")?;
+	let response = common::package::Package::receive(
+		config,
+		&mut stream,
+		Some(common::set![common::package::Action::GetBlockchainLenSuccess]),
+	)
+	.context("Failed to receive a response.")?;
+	response.data().parse().context("Invalid length.")
+}
+
+#[cfg(test)]
+mod tests {
+	#[test]
+	fn test_resync_stalled_blockchain() {
+		let _lock = crate::test_helpers::lock_mining();
+
+		// The other node mines one more block
+		let peer_config = std::sync::Arc::new(
+			crate::test_helpers::create_test_config(&serde_json::json!({})),
+		);
+		let (peer, _peer_dir) = crate::test_helpers::create_test_blockchain();
+		let peer: &'static std::sync::RwLock<blockchain::Blockchain> =
+			Box::leak(Box::new(peer));
+		{
+			let mut lock = peer.write().unwrap();
+			let transaction = crate::test_helpers::create_test_transaction(
+				&lock,
+				lock.miner(),
+			);
+			lock.add_transaction(transaction).unwrap();
+		}
+		crate::helpers::mine_block(peer, &peer_config).unwrap();
+		assert_eq!(peer.read().unwrap().len().unwrap(), 2);
+		let shared_config: &'static crate::SharedConfig =
+			Box::leak(Box::new(std::sync::RwLock::new(peer_config)));
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = listener.local_addr().unwrap();
+		std::thread::spawn(move || {
+			crate::serve(&listener, shared_config, peer)
+		});
+
+		// Our node is stuck at the genesis block
+		let (blockchain, _dir) = crate::test_helpers::create_test_blockchain();
+		let config = crate::test_helpers::create_test_config_with_nodes(
+			&[address],
+			&serde_json::json!({}),
+		);
+		let stall = std::time::Duration::from_millis(50);
+		let mut tip = None;
+		assert!(!super::resync_if_stalled(
+			&blockchain,
+			&config,
+			stall,
+			&mut tip
+		)
+		.unwrap());
+		assert!(!super::resync_if_stalled(
+			&blockchain,
+			&config,
+			stall,
+			&mut tip
+		)
+		.unwrap());
+		std::thread::sleep(stall);
+		assert!(super::resync_if_stalled(
+			&blockchain,
+			&config,
+			stall,
+			&mut tip
+		)
+		.unwrap());
+		assert_eq!(blockchain.read().unwrap().len().unwrap(), 2);
+		assert_eq!(
+			blockchain.read().unwrap().get_last_block_hash().unwrap(),
+			peer.read().unwrap().get_last_block_hash().unwrap()
+		);
+	}
+}